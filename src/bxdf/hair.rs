@@ -0,0 +1,326 @@
+use std::f64::consts::PI;
+use rand::Rng;
+use math::*;
+use core::*;
+use super::perfect_specular_transmission::evaluate_fresnel;
+
+// How many bounces inside the hair are modeled separately (R, TT, TRT), after which the rest are lumped
+// together.
+const P_MAX: usize = 3;
+
+// pbrt's hair BSDF (https://www.pbrt.org/hair.pdf), after Marschner et al. 2003 and Chiang et al. 2016.
+// The local coordinate system has x along the hair and z facing out of it, and h is where across the hair
+// (-1 to 1) the ray hit it.
+pub struct Hair {
+    h: f64,
+    gamma_o: f64,
+    eta: f64,
+    sigma_a: Color,
+    // Longitudinal variance per lobe.
+    v: [f64; P_MAX + 1],
+    // Azimuthal logistic scale.
+    s: f64,
+    // Scales tilt the lobes by multiples of alpha.
+    sin_2k_alpha: [f64; 3],
+    cos_2k_alpha: [f64; 3],
+}
+
+impl Hair {
+    // beta_m and beta_n are longitudinal and azimuthal roughness from 0 to 1, and alpha is the angle of the
+    // hair's scales in radians.
+    pub fn new(h: f64, eta: f64, sigma_a: Color, beta_m: f64, beta_n: f64, alpha: f64) -> Hair {
+        assert!(h >= -1f64 && h <= 1f64, "hair offset {} must be between -1 and 1", h);
+        let v0 = (0.726f64 * beta_m + 0.812f64 * beta_m.powi(2) + 3.7f64 * beta_m.powi(20)).powi(2);
+        let s = (PI / 8f64).sqrt() * (0.265f64 * beta_n + 1.194f64 * beta_n.powi(2) + 5.372f64 * beta_n.powi(22));
+        let mut sin_2k_alpha = [alpha.sin(), 0f64, 0f64];
+        let mut cos_2k_alpha = [safe_sqrt(1f64 - sin_2k_alpha[0].powi(2)), 0f64, 0f64];
+        for i in 1..3 {
+            sin_2k_alpha[i] = 2f64 * cos_2k_alpha[i - 1] * sin_2k_alpha[i - 1];
+            cos_2k_alpha[i] = cos_2k_alpha[i - 1].powi(2) - sin_2k_alpha[i - 1].powi(2);
+        }
+
+        Hair {
+            h,
+            gamma_o: safe_asin(h),
+            eta,
+            sigma_a,
+            v: [v0, 0.25f64 * v0, 4f64 * v0, 4f64 * v0],
+            s,
+            sin_2k_alpha,
+            cos_2k_alpha,
+        }
+    }
+
+    // The absorption that gives roughly this color after many bounces.
+    pub fn sigma_a_from_reflectance(color: Color, beta_n: f64) -> Color {
+        let denominator = 5.969f64 - 0.215f64 * beta_n + 2.532f64 * beta_n.powi(2) - 10.73f64 * beta_n.powi(3)
+            + 5.574f64 * beta_n.powi(4) + 0.245f64 * beta_n.powi(5);
+        let sigma = |c: f64| (c.max(1e-6f64).ln() / denominator).powi(2);
+        Color::new(sigma(color.r), sigma(color.g), sigma(color.b))
+    }
+
+    // The absorption of hair with this much black-brown eumelanin and red-yellow pheomelanin.
+    pub fn sigma_a_from_melanin(eumelanin: f64, pheomelanin: f64) -> Color {
+        Color::new(0.419f64, 0.697f64, 1.37f64) * eumelanin + Color::new(0.187f64, 0.4f64, 1.05f64) * pheomelanin
+    }
+
+    // sin and cos of theta_o, tilted for lobe p by the scales.
+    fn tilt(&self, p: usize, sin_theta_o: f64, cos_theta_o: f64) -> (f64, f64) {
+        let (sin, cos) = match p {
+            0 => (
+                sin_theta_o * self.cos_2k_alpha[1] - cos_theta_o * self.sin_2k_alpha[1],
+                cos_theta_o * self.cos_2k_alpha[1] + sin_theta_o * self.sin_2k_alpha[1],
+            ),
+            1 => (
+                sin_theta_o * self.cos_2k_alpha[0] + cos_theta_o * self.sin_2k_alpha[0],
+                cos_theta_o * self.cos_2k_alpha[0] - sin_theta_o * self.sin_2k_alpha[0],
+            ),
+            2 => (
+                sin_theta_o * self.cos_2k_alpha[2] + cos_theta_o * self.sin_2k_alpha[2],
+                cos_theta_o * self.cos_2k_alpha[2] - sin_theta_o * self.sin_2k_alpha[2],
+            ),
+            _ => (sin_theta_o, cos_theta_o),
+        };
+        (sin, cos.abs())
+    }
+
+    // How much light ends up in each lobe, given how much one pass through the hair transmits.
+    fn attenuation(&self, cos_theta_o: f64, transmittance: Color) -> [Color; P_MAX + 1] {
+        let cos_gamma_o = safe_sqrt(1f64 - self.h * self.h);
+        let f = evaluate_fresnel(cos_theta_o * cos_gamma_o, 1f64, self.eta);
+        let mut a = [Color::BLACK; P_MAX + 1];
+        a[0] = Color::WHITE * f;
+        a[1] = transmittance * (1f64 - f).powi(2);
+        for p in 2..P_MAX {
+            a[p] = a[p - 1] * transmittance * f;
+        }
+        let remainder = a[P_MAX - 1] * transmittance * f;
+        let tf = transmittance * f;
+        a[P_MAX] = Color::new(remainder.r / (1f64 - tf.r), remainder.g / (1f64 - tf.g), remainder.b / (1f64 - tf.b));
+        a
+    }
+
+    // The transmittance of one pass through the hair, and the angle it's refracted to.
+    fn transmittance(&self, sin_theta_o: f64, cos_theta_o: f64) -> (Color, f64) {
+        let sin_theta_t = sin_theta_o / self.eta;
+        let cos_theta_t = safe_sqrt(1f64 - sin_theta_t * sin_theta_t);
+        let eta_p = (self.eta * self.eta - sin_theta_o * sin_theta_o).sqrt() / cos_theta_o;
+        let sin_gamma_t = self.h / eta_p;
+        let cos_gamma_t = safe_sqrt(1f64 - sin_gamma_t * sin_gamma_t);
+        let length = 2f64 * cos_gamma_t / cos_theta_t;
+        let a = self.sigma_a;
+        (Color::new((-a.r * length).exp(), (-a.g * length).exp(), (-a.b * length).exp()), safe_asin(sin_gamma_t))
+    }
+
+    fn lobe_pdfs(&self, sin_theta_o: f64, cos_theta_o: f64) -> [f64; P_MAX + 1] {
+        let (transmittance, _) = self.transmittance(sin_theta_o, cos_theta_o);
+        let a = self.attenuation(cos_theta_o, transmittance);
+        let total: f64 = a.iter().map(|a| a.average()).sum();
+        let mut pdfs = [0f64; P_MAX + 1];
+        for p in 0..(P_MAX + 1) {
+            pdfs[p] = a[p].average() / total;
+        }
+        pdfs
+    }
+
+    fn pdf_for_angles(&self, w_o: &Vec3, sin_theta_i: f64, cos_theta_i: f64, phi: f64) -> f64 {
+        let (sin_theta_o, cos_theta_o) = (w_o.x, safe_sqrt(1f64 - w_o.x * w_o.x));
+        let (_, gamma_t) = self.transmittance(sin_theta_o, cos_theta_o);
+        let pdfs = self.lobe_pdfs(sin_theta_o, cos_theta_o);
+        let mut pdf = 0f64;
+        for p in 0..P_MAX {
+            let (sin_theta_op, cos_theta_op) = self.tilt(p, sin_theta_o, cos_theta_o);
+            pdf += longitudinal(cos_theta_i, cos_theta_op, sin_theta_i, sin_theta_op, self.v[p])
+                * pdfs[p] * azimuthal(phi, p, self.s, self.gamma_o, gamma_t);
+        }
+        pdf + longitudinal(cos_theta_i, cos_theta_o, sin_theta_i, sin_theta_o, self.v[P_MAX]) * pdfs[P_MAX] / (2f64 * PI)
+    }
+}
+
+impl Bxdf for Hair {
+    fn bxdf_type(&self) -> BxdfType {
+        (TransportType::Reflective, SpectrumType::GlossySpecular)
+    }
+
+    fn evaluate(&self, w_o: Vec3, w_i: Vec3) -> Color {
+        let (sin_theta_o, cos_theta_o, phi_o) = (w_o.x, safe_sqrt(1f64 - w_o.x * w_o.x), w_o.z.atan2(w_o.y));
+        let (sin_theta_i, cos_theta_i, phi_i) = (w_i.x, safe_sqrt(1f64 - w_i.x * w_i.x), w_i.z.atan2(w_i.y));
+        let (transmittance, gamma_t) = self.transmittance(sin_theta_o, cos_theta_o);
+        let a = self.attenuation(cos_theta_o, transmittance);
+        let phi = phi_i - phi_o;
+
+        let mut sum = Color::BLACK;
+        for p in 0..P_MAX {
+            let (sin_theta_op, cos_theta_op) = self.tilt(p, sin_theta_o, cos_theta_o);
+            sum += a[p] * (longitudinal(cos_theta_i, cos_theta_op, sin_theta_i, sin_theta_op, self.v[p])
+                * azimuthal(phi, p, self.s, self.gamma_o, gamma_t));
+        }
+        sum += a[P_MAX] * (longitudinal(cos_theta_i, cos_theta_o, sin_theta_i, sin_theta_o, self.v[P_MAX]) / (2f64 * PI));
+        // The renderer multiplies by the cosine to the normal, which doesn't mean anything for hair.
+        if w_i.z != 0f64 { sum / w_i.z.abs() } else { sum }
+    }
+
+    fn choose_and_evaluate(&self, w_o: Vec3, rng: &mut Rng) -> BxdfSample {
+        let (sin_theta_o, cos_theta_o, phi_o) = (w_o.x, safe_sqrt(1f64 - w_o.x * w_o.x), w_o.z.atan2(w_o.y));
+
+        // Pick a lobe by how much light it carries...
+        let pdfs = self.lobe_pdfs(sin_theta_o, cos_theta_o);
+        let mut choice = rng.next_f64();
+        let mut p = 0;
+        while p < P_MAX && choice >= pdfs[p] {
+            choice -= pdfs[p];
+            p += 1;
+        }
+
+        // ...then a longitudinal angle within it...
+        let (sin_theta_op, cos_theta_op) = self.tilt(p, sin_theta_o, cos_theta_o);
+        let u = rng.next_f64().max(1e-5f64);
+        let cos_theta = 1f64 + self.v[p] * (u + (1f64 - u) * (-2f64 / self.v[p]).exp()).ln();
+        let sin_theta = safe_sqrt(1f64 - cos_theta * cos_theta);
+        let cos_phi = (2f64 * PI * rng.next_f64()).cos();
+        let sin_theta_i = -cos_theta * sin_theta_op + sin_theta * cos_phi * cos_theta_op;
+        let cos_theta_i = safe_sqrt(1f64 - sin_theta_i * sin_theta_i);
+
+        // ...and an azimuthal one.
+        let (_, gamma_t) = self.transmittance(sin_theta_o, cos_theta_o);
+        let dphi = if p < P_MAX {
+            phi(p, self.gamma_o, gamma_t) + sample_trimmed_logistic(rng.next_f64(), self.s, -PI, PI)
+        } else {
+            2f64 * PI * rng.next_f64()
+        };
+        let phi_i = phi_o + dphi;
+        let w_i = Vec3::new(sin_theta_i, cos_theta_i * phi_i.cos(), cos_theta_i * phi_i.sin());
+        BxdfSample::new(self.evaluate(w_o, w_i), self.pdf_for_angles(&w_o, sin_theta_i, cos_theta_i, dphi), w_i)
+    }
+
+    fn pdf(&self, w_o: Vec3, w_i: Vec3) -> f64 {
+        let (sin_theta_i, cos_theta_i) = (w_i.x, safe_sqrt(1f64 - w_i.x * w_i.x));
+        self.pdf_for_angles(&w_o, sin_theta_i, cos_theta_i, w_i.z.atan2(w_i.y) - w_o.z.atan2(w_o.y))
+    }
+}
+
+fn safe_sqrt(x: f64) -> f64 {
+    x.max(0f64).sqrt()
+}
+
+fn safe_asin(x: f64) -> f64 {
+    x.max(-1f64).min(1f64).asin()
+}
+
+// The modified Bessel function of the first kind, order 0.
+fn bessel_i0(x: f64) -> f64 {
+    let mut value = 0f64;
+    let (mut x2i, mut factorial, mut four_i) = (1f64, 1f64, 1f64);
+    for i in 0..10 {
+        if i > 1 {
+            factorial *= i as f64;
+        }
+        value += x2i / (four_i * factorial * factorial);
+        x2i *= x * x;
+        four_i *= 4f64;
+    }
+    value
+}
+
+fn log_bessel_i0(x: f64) -> f64 {
+    if x > 12f64 {
+        x + 0.5f64 * (-(2f64 * PI).ln() + (1f64 / x).ln() + 1f64 / (8f64 * x))
+    } else {
+        bessel_i0(x).ln()
+    }
+}
+
+// M_p: how light scatters along the hair.
+fn longitudinal(cos_theta_i: f64, cos_theta_o: f64, sin_theta_i: f64, sin_theta_o: f64, v: f64) -> f64 {
+    let a = cos_theta_i * cos_theta_o / v;
+    let b = sin_theta_i * sin_theta_o / v;
+    if v <= 0.1f64 {
+        // Narrow lobes overflow the direct formula.
+        (log_bessel_i0(a) - b - 1f64 / v + 2f64.ln() + (1f64 / (2f64 * v)).ln()).exp()
+    } else {
+        ((-b).exp() * bessel_i0(a)) / ((1f64 / v).sinh() * 2f64 * v)
+    }
+}
+
+// The azimuthal angle lobe p leaves at.
+fn phi(p: usize, gamma_o: f64, gamma_t: f64) -> f64 {
+    2f64 * p as f64 * gamma_t - 2f64 * gamma_o + p as f64 * PI
+}
+
+fn logistic(x: f64, s: f64) -> f64 {
+    let x = x.abs();
+    (-x / s).exp() / (s * (1f64 + (-x / s).exp()).powi(2))
+}
+
+fn logistic_cdf(x: f64, s: f64) -> f64 {
+    1f64 / (1f64 + (-x / s).exp())
+}
+
+fn trimmed_logistic(x: f64, s: f64, a: f64, b: f64) -> f64 {
+    logistic(x, s) / (logistic_cdf(b, s) - logistic_cdf(a, s))
+}
+
+fn sample_trimmed_logistic(u: f64, s: f64, a: f64, b: f64) -> f64 {
+    let k = logistic_cdf(b, s) - logistic_cdf(a, s);
+    let x = -s * (1f64 / (u * k + logistic_cdf(a, s)) - 1f64).ln();
+    x.max(a).min(b)
+}
+
+// N_p: how light scatters around the hair.
+fn azimuthal(phi_difference: f64, p: usize, s: f64, gamma_o: f64, gamma_t: f64) -> f64 {
+    let mut dphi = phi_difference - phi(p, gamma_o, gamma_t);
+    while dphi > PI {
+        dphi -= 2f64 * PI;
+    }
+    while dphi < -PI {
+        dphi += 2f64 * PI;
+    }
+    trimmed_logistic(dphi, s, -PI, PI)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{ Rng, SeedableRng, StdRng };
+
+    const TEST_RNG_SEED: [usize; 1] = [5];
+
+    fn random_direction(rng: &mut StdRng) -> Vec3 {
+        let z = 1f64 - 2f64 * rng.next_f64();
+        let r = safe_sqrt(1f64 - z * z);
+        let phi = 2f64 * PI * rng.next_f64();
+        Vec3::new(r * phi.cos(), r * phi.sin(), z)
+    }
+
+    #[test]
+    fn it_should_conserve_energy_without_absorption() {
+        // A white furnace: with nothing absorbed, all the light has to come back out somewhere.
+        let mut rng = StdRng::from_seed(&TEST_RNG_SEED);
+        for &beta in &[0.2f64, 0.5f64, 0.8f64] {
+            let w_o = random_direction(&mut rng);
+            let samples = 50000;
+            let mut sum = 0f64;
+            for _ in 0..samples {
+                let hair = Hair::new(-1f64 + 2f64 * rng.next_f64(), 1.55f64, Color::BLACK, beta, beta, 0f64);
+                let w_i = random_direction(&mut rng);
+                sum += hair.evaluate(w_o, w_i).average() * w_i.z.abs();
+            }
+            let average = sum * 4f64 * PI / samples as f64;
+            assert!(average > 0.95f64 && average < 1.05f64, "white furnace gave {} for beta {}", average, beta);
+        }
+    }
+
+    #[test]
+    fn it_should_sample_what_it_evaluates() {
+        let mut rng = StdRng::from_seed(&TEST_RNG_SEED);
+        let hair = Hair::new(0.3f64, 1.55f64, Hair::sigma_a_from_melanin(1.3f64, 0f64), 0.3f64, 0.3f64, 2f64.to_radians());
+        for _ in 0..100 {
+            let w_o = random_direction(&mut rng);
+            let sample = hair.choose_and_evaluate(w_o, &mut rng);
+            sample.w_i.assert_normalized();
+            let expected = hair.evaluate(w_o, sample.w_i);
+            assert!((sample.color.r - expected.r).abs() < 1e-9);
+            assert!((sample.pdf - hair.pdf(w_o, sample.w_i)).abs() < 1e-6f64 * sample.pdf.max(1f64));
+        }
+    }
+}
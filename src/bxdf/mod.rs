@@ -1,9 +1,11 @@
 mod bxdf_trig;
+mod hair;
 mod lambertian;
 mod measured;
 mod perfect_specular_reflection;
 mod perfect_specular_transmission;
 
+pub use self::hair::*;
 pub use self::lambertian::*;
 pub use self::measured::*;
 pub use self::perfect_specular_reflection::*;
@@ -57,7 +57,7 @@ impl Bxdf for PerfectSpecularTransmission {
 }
 
 // TODO: Factor this out for use in reflection. Is this dielectric or conductor?
-fn evaluate_fresnel(cos_i: f64, eta_i: f64, eta_t: f64) -> f64 {
+pub fn evaluate_fresnel(cos_i: f64, eta_i: f64, eta_t: f64) -> f64 {
     let cos_i = cos_i.clamp(-1f64, 1f64);
 
     let is_entering = cos_i > 0f64;
@@ -24,6 +24,25 @@ impl BoundingBox {
         }
     }
 
+    // For geometry that goes on forever, like planes.
+    pub fn infinite() -> BoundingBox {
+        BoundingBox {
+            min: Point::uniform(f64::NEG_INFINITY),
+            max: Point::uniform(f64::INFINITY),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    // Whether this is a box that can be meaningfully subdivided, i.e. it's neither empty nor infinite.
+    pub fn is_bounded(&self) -> bool {
+        !self.is_empty() &&
+            self.min.x.is_finite() && self.min.y.is_finite() && self.min.z.is_finite() &&
+            self.max.x.is_finite() && self.max.y.is_finite() && self.max.z.is_finite()
+    }
+
     pub fn union(bb1: &BoundingBox, bb2: &BoundingBox) -> BoundingBox {
         BoundingBox {
             min: Point::new(
@@ -39,6 +58,23 @@ impl BoundingBox {
         }
     }
 
+    // Boxes that don't overlap give the empty box, rather than one that's inside out.
+    pub fn intersection(bb1: &BoundingBox, bb2: &BoundingBox) -> BoundingBox {
+        let overlap = BoundingBox {
+            min: Point::new(
+                non_nan_max(bb1.min.x, bb2.min.x),
+                non_nan_max(bb1.min.y, bb2.min.y),
+                non_nan_max(bb1.min.z, bb2.min.z),
+            ),
+            max: Point::new(
+                non_nan_min(bb1.max.x, bb2.max.x),
+                non_nan_min(bb1.max.y, bb2.max.y),
+                non_nan_min(bb1.max.z, bb2.max.z),
+            ),
+        };
+        if overlap.is_empty() { BoundingBox::empty() } else { overlap }
+    }
+
     pub fn with_point(&self, p: &Point) -> BoundingBox {
         BoundingBox {
             min: Point::new(
@@ -56,6 +92,10 @@ impl BoundingBox {
 
     // pbrt pg. 194
     pub fn intersect(&self, ray: &Ray) -> Option<(f64, f64)> {
+        // The slabs of an empty box are inside out, which swapping t_near and t_far would undo.
+        if self.is_empty() {
+            return None;
+        }
         let (mut t0, mut t1) = (ray.t_min, ray.t_max);
 
         foreach_axis!(a in {
@@ -91,6 +131,13 @@ impl BoundingBox {
 macro_rules! apply_transform {
     ($self:ident, $fnname:ident, $transform:ident) => {
         {
+            // Transforming infinite corners mixes infinities (and makes NaNs), so don't bother trying to
+            // keep any of the box's finite extents.
+            if $self.is_empty() {
+                return $self;
+            } else if !$self.is_bounded() {
+                return BoundingBox::infinite();
+            }
             let candidates = [
                 Point::new($self.min.x, $self.min.y, $self.min.z).$fnname($transform),
                 Point::new($self.min.x, $self.min.y, $self.max.z).$fnname($transform),
@@ -221,4 +268,30 @@ mod tests {
         let r = Ray::finite(Point::new(0f64, 0f64, 0f64), Vec3::Z_AXIS, 0f64, 0.5f64);
         assert!(SIMPLE_BOUNDING_BOX.intersect(&r).is_some());
     }
+
+    #[test]
+    fn it_should_stay_infinite_under_rotation() {
+        let plane = BoundingBox {
+            min: Point::new(f64::NEG_INFINITY, 0f64, f64::NEG_INFINITY),
+            max: Point::new(f64::INFINITY, 0f64, f64::INFINITY),
+        };
+        let rotated = plane.transform(&Transform::new(Mat4::create_rotation(0.5f64, Vec3::X_AXIS)));
+        assert!(!rotated.is_bounded() && !rotated.is_empty());
+        assert_eq!(rotated.min.y, f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn it_should_give_an_empty_box_for_disjoint_intersections() {
+        let far = BoundingBox { min: Point::uniform(2f64), max: Point::uniform(3f64) };
+        let overlap = BoundingBox::intersection(&SIMPLE_BOUNDING_BOX, &far);
+        assert!(overlap.is_empty());
+        assert_eq!(overlap.min, Point::uniform(f64::INFINITY));
+        assert_eq!(overlap.max, Point::uniform(f64::NEG_INFINITY));
+    }
+
+    #[test]
+    fn it_should_not_intersect_an_empty_box() {
+        let r = Ray::half_infinite(Point::new(-5f64, 0f64, 0f64), Vec3::X_AXIS);
+        assert!(BoundingBox::empty().intersect(&r).is_none());
+    }
 }
@@ -29,6 +29,11 @@ impl Bsdf {
         }
     }
 
+    // The shading normal, which materials may have perturbed away from the intersection's.
+    pub fn normal(&self) -> Normal {
+        self.normal
+    }
+
     fn world_to_local(&self, v: &Vec3) -> Vec3 {
         Vec3::new(v.dot(&self.primary_tangent), v.dot(&self.secondary_tangent), v.dot(&self.normal))
     }
@@ -0,0 +1,8 @@
+use std::fmt::Debug;
+use math::*;
+
+// A signed distance to some surface: negative inside, positive outside. It doesn't have to be exact, but
+// it must never overestimate, or sphere tracing will step through the surface.
+pub trait DistanceField: Sync + Send + Debug {
+    fn distance(&self, point: Point) -> f64;
+}
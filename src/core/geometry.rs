@@ -7,4 +7,9 @@ pub trait Geometry: Debug + Send + Sync {
     fn does_intersect(&self, ray: &Ray) -> bool {
         self.intersect(ray).is_some()
     }
+    // Every span of the ray's line that's inside this geometry, in order. See RayInterval. The default
+    // re-casts the ray until it runs out of surfaces, which is correct but slow; override it if you can.
+    fn intervals(&self, ray: &Ray) -> Vec<RayInterval> {
+        march_intervals(self, ray)
+    }
 }
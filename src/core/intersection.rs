@@ -4,7 +4,7 @@ use super::material::Material;
 use super::transform::{ Transform, Transformable };
 use super::uv::Uv;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct IntersectionGeometry {
     pub normal: Normal,
     pub u_axis: Vec3,
@@ -19,9 +19,18 @@ impl IntersectionGeometry {
             v_axis,
         }
     }
+
+    // For surfaces without a natural parameterization. u runs around +y, like Sphere's, as far as the
+    // normal allows.
+    pub fn from_normal(normal: Vec3) -> IntersectionGeometry {
+        let normal = normal.as_normalized();
+        let around = Vec3::Y_AXIS.cross(normal);
+        let u_axis = if around.magnitude2() > 1e-12 { around.as_normalized() } else { Vec3::X_AXIS.cross(normal).as_normalized() };
+        IntersectionGeometry::new(u_axis, normal.cross(u_axis))
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Intersection {
     pub distance: f64,
     pub location: Point,
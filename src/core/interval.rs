@@ -0,0 +1,123 @@
+use std::f64;
+use math::*;
+use super::geometry::Geometry;
+use super::intersection::Intersection;
+use super::ray::Ray;
+use super::transform::{ Transform, Transformable };
+
+// A span of a ray's line that is inside some geometry. A missing intersection means the span is unbounded in
+// that direction, as with a ray that starts inside a half-space. Intervals are computed along the ray's
+// entire line, regardless of its t_min and t_max, so callers can always tell what the ray starts inside of.
+#[derive(Debug)]
+pub struct RayInterval {
+    pub enter: Option<Intersection>,
+    pub exit: Option<Intersection>,
+}
+
+impl RayInterval {
+    pub fn start(&self) -> f64 {
+        self.enter.as_ref().map_or(f64::NEG_INFINITY, |i| i.distance)
+    }
+
+    pub fn end(&self) -> f64 {
+        self.exit.as_ref().map_or(f64::INFINITY, |i| i.distance)
+    }
+
+    // The nearest surface crossing within the ray's bounds, if any.
+    pub fn first_crossing(self, ray: &Ray) -> Option<Intersection> {
+        let is_in_range = |i: &Intersection| i.distance >= ray.t_min && i.distance <= ray.t_max;
+        match self.enter {
+            Some(ref enter) if is_in_range(enter) => { return self.enter; },
+            _ => {},
+        }
+        match self.exit {
+            Some(ref exit) if is_in_range(exit) => self.exit,
+            _ => None,
+        }
+    }
+}
+
+// Note that this only tells you the direction of the crossing if the intersection's normal faces outward.
+pub fn is_exiting(intersection: &Intersection, direction: &Vec3) -> bool {
+    intersection.geometry.normal.dot(direction) > 0f64
+}
+
+// Pairs up a sorted list of surface crossings into intervals. This is deliberately forgiving about surfaces
+// that aren't closed: an unpaired entry extends to infinity, and an unpaired exit extends back from it, so a
+// lone sheet behaves like a half-space.
+pub fn intervals_from_crossings(crossings: Vec<Intersection>, direction: &Vec3) -> Vec<RayInterval> {
+    let mut intervals = vec![];
+    let mut open: Option<Option<Intersection>> = None;
+
+    for crossing in crossings {
+        if is_exiting(&crossing, direction) {
+            let enter = match open.take() {
+                Some(enter) => enter,
+                None => {
+                    // Either this is the start of the line, or we missed an entry. In the latter case,
+                    // extend the previous interval rather than inventing a new one.
+                    match intervals.pop() {
+                        Some(RayInterval { enter, .. }) => enter,
+                        None => None,
+                    }
+                },
+            };
+            intervals.push(RayInterval { enter, exit: Some(crossing) });
+        } else if open.is_none() {
+            open = Some(Some(crossing));
+        }
+    }
+
+    if let Some(enter) = open {
+        intervals.push(RayInterval { enter, exit: None });
+    }
+
+    intervals
+}
+
+// A fallback for computing intervals with nothing but Geometry::intersect, by repeatedly re-casting the ray
+// from just past each crossing. This requires the geometry to have a finite bound.
+pub fn march_intervals<G: Geometry + ?Sized>(geometry: &G, ray: &Ray) -> Vec<RayInterval> {
+    const EPSILON: f64 = 1e-10f64;
+
+    let line = Ray { t_min: f64::NEG_INFINITY, t_max: f64::INFINITY, ..ray.clone() };
+    let bound = geometry.bound();
+    if bound.is_empty() {
+        return vec![];
+    }
+    let t_start = match bound.intersect(&line) {
+        Some((t0, _)) => t0 - 1f64,
+        None => { return vec![]; },
+    };
+    assert!(t_start.is_finite(), "cannot march intervals through unbounded geometry");
+
+    // Not all acceleration structures handle negative t well, so start the ray outside the bound instead.
+    let mut marching_ray = Ray {
+        origin: ray.origin + ray.direction * t_start,
+        t_min: 0f64,
+        ..line
+    };
+    let mut crossings = vec![];
+    while let Some(crossing) = geometry.intersect(&marching_ray) {
+        marching_ray.t_min = crossing.distance + EPSILON;
+        crossings.push(Intersection { distance: crossing.distance + t_start, ..crossing });
+    }
+
+    intervals_from_crossings(crossings, &ray.direction)
+}
+
+impl Transformable for RayInterval {
+    fn transform(self, transform: &Transform) -> RayInterval {
+        RayInterval {
+            enter: self.enter.map(|i| i.transform(transform)),
+            exit: self.exit.map(|i| i.transform(transform)),
+        }
+    }
+
+    fn invert_transform(self, transform: &Transform) -> RayInterval {
+        RayInterval {
+            enter: self.enter.map(|i| i.invert_transform(transform)),
+            exit: self.exit.map(|i| i.invert_transform(transform)),
+        }
+    }
+}
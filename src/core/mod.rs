@@ -3,8 +3,10 @@ pub mod bsdf;
 pub mod bxdf;
 pub mod camera;
 pub mod color;
+pub mod distance_field;
 pub mod geometry;
 pub mod intersection;
+pub mod interval;
 pub mod light;
 pub mod material;
 pub mod point_kd_tree;
@@ -22,8 +24,10 @@ pub use self::bsdf::*;
 pub use self::bxdf::*;
 pub use self::camera::*;
 pub use self::color::*;
+pub use self::distance_field::*;
 pub use self::geometry::*;
 pub use self::intersection::*;
+pub use self::interval::*;
 pub use self::light::*;
 pub use self::material::*;
 pub use self::point_kd_tree::*;
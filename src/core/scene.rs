@@ -7,11 +7,78 @@ use super::light::LightType;
 use super::ray::Ray;
 use super::shape::Shape;
 use super::material::Material;
+use super::texture::Texture;
+
+// How far past a hit that was masked out the ray picks up again, relative to the hit's distance.
+const ALPHA_EPSILON: f64 = 1e-9f64;
+
+// Punches holes in an object wherever the texture's average is low, so a few triangles can stand in for
+// leaves, fences and the like.
+#[derive(Debug)]
+pub struct AlphaMask {
+    pub texture: Box<Texture>,
+    // Hits where alpha is below this are skipped. Without one, hits are kept with a probability of alpha.
+    pub cutoff: Option<f64>,
+}
+
+impl AlphaMask {
+    pub fn is_opaque(&self, intersection: &Intersection, ray: &Ray) -> bool {
+        let alpha = self.texture.get_color(intersection.uv).average();
+        match self.cutoff {
+            Some(cutoff) => alpha >= cutoff,
+            None => alpha >= 1f64 || (alpha > 0f64 && hash_hit(ray, intersection.distance) < alpha),
+        }
+    }
+}
+
+// A number in [0, 1) that's fixed for a given hit, rather than random, since intersection has no rng and
+// a shadow ray should agree with itself no matter how often it's cast.
+fn hash_hit(ray: &Ray, distance: f64) -> f64 {
+    let values = [ray.origin.x, ray.origin.y, ray.origin.z, ray.direction.x, ray.direction.y, ray.direction.z, distance];
+    let mut hash = 0xcbf29ce484222325u64;
+    for value in values.iter() {
+        // splitmix64's finalizer, folded over each value's bits.
+        hash = (hash ^ value.to_bits()).wrapping_add(0x9e3779b97f4a7c15u64);
+        hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58476d1ce4e5b9u64);
+        hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d049bb133111ebu64);
+        hash = hash ^ (hash >> 31);
+    }
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
 
 #[derive(Debug)]
 pub struct SceneObject {
     pub shape: Shape,
-    pub material: Arc<Material>,
+    // None means the geometry supplies its own materials (i.e. it's a SceneGroup) or that an enclosing group
+    // does. Either way, the material nearest the surface wins.
+    pub material: Option<Arc<Material>>,
+    pub alpha: Option<AlphaMask>,
+}
+
+impl SceneObject {
+    // Keeps casting past the hits that the mask lets through.
+    fn intersect_masked(&self, alpha: &AlphaMask, ray: &Ray) -> Option<Intersection> {
+        let mut r = ray.clone();
+        loop {
+            let intersection = self.shape.intersect(&r)?;
+            if alpha.is_opaque(&intersection, &r) {
+                return Some(intersection);
+            }
+            r.t_min = intersection.distance + ALPHA_EPSILON * intersection.distance.abs().max(1f64);
+            if r.t_min > r.t_max {
+                return None;
+            }
+        }
+    }
+}
+
+// Hits come back up through every object and group they're nested in, innermost first, so only fill in a
+// material where nothing deeper already has.
+fn fill_in_material(intersection: Intersection, material: &Option<Arc<Material>>) -> Intersection {
+    match (&intersection.material, material) {
+        (&None, &Some(ref material)) => intersection.with_material(Arc::clone(material)),
+        _ => intersection,
+    }
 }
 
 impl Geometry for SceneObject {
@@ -20,7 +87,43 @@ impl Geometry for SceneObject {
     }
 
     fn intersect(&self, ray: &Ray) -> Option<Intersection> {
-        self.shape.intersect(ray).map(|i| i.with_material(Arc::clone(&self.material)))
+        let intersection = match self.alpha {
+            Some(ref alpha) => self.intersect_masked(alpha, ray),
+            None => self.shape.intersect(ray),
+        };
+        intersection.map(|i| fill_in_material(i, &self.material))
+    }
+}
+
+// A group is its own spatial index over its objects, in group-local space. Placing it in the scene (or in
+// another group) as a SceneObject makes the scene a tree of kd-trees, so each level only has to search the
+// handful of groups and objects that are directly inside it.
+#[derive(Debug)]
+pub struct SceneGroup {
+    objects: VolumeKdTree<SceneObject>,
+    material: Option<Arc<Material>>,
+}
+
+impl SceneGroup {
+    pub fn new(objects: Vec<SceneObject>, material: Option<Arc<Material>>) -> SceneGroup {
+        SceneGroup {
+            objects: VolumeKdTree::from(objects),
+            material,
+        }
+    }
+}
+
+impl Geometry for SceneGroup {
+    fn bound(&self) -> BoundingBox {
+        self.objects.bound()
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+        self.objects.intersect(ray).map(|i| fill_in_material(i, &self.material))
+    }
+
+    fn does_intersect(&self, ray: &Ray) -> bool {
+        self.objects.does_intersect(ray)
     }
 }
 
@@ -28,3 +131,52 @@ pub struct Scene {
     pub objects: VolumeKdTree<SceneObject>,
     pub lights: Vec<LightType>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::*;
+    use math::*;
+    use geometry::Parallelogram;
+
+    #[derive(Debug)]
+    struct LeftHalf;
+
+    impl Texture for LeftHalf {
+        fn get_color(&self, uv: Uv) -> Color {
+            if uv.0 < 0.5f64 { Color::WHITE } else { Color::BLACK }
+        }
+    }
+
+    fn masked_quad(texture: Box<Texture>, cutoff: Option<f64>) -> SceneObject {
+        let quad = Parallelogram::new(Point::uniform(0f64), Vec3::X_AXIS, Vec3::Y_AXIS, Uv(1f64, 1f64));
+        SceneObject {
+            shape: Shape::new(Arc::new(quad), IDENTITY_TRANSFORM.clone()),
+            material: None,
+            alpha: Some(AlphaMask { texture, cutoff }),
+        }
+    }
+
+    fn ray_at(x: f64, y: f64) -> Ray {
+        Ray::half_infinite(Point::new(x, y, 1f64), -Vec3::Z_AXIS)
+    }
+
+    #[test]
+    fn it_should_skip_hits_below_the_cutoff() {
+        let object = masked_quad(Box::new(LeftHalf), Some(0.5f64));
+        assert!(object.intersect(&ray_at(0.25f64, 0.5f64)).is_some());
+        assert!(object.does_intersect(&ray_at(0.25f64, 0.5f64)));
+        assert!(object.intersect(&ray_at(0.75f64, 0.5f64)).is_none());
+        assert!(!object.does_intersect(&ray_at(0.75f64, 0.5f64)));
+    }
+
+    #[test]
+    fn it_should_pass_through_fractional_alpha_in_proportion() {
+        let object = masked_quad(Box::new(Color::new(0.3f64, 0.3f64, 0.3f64)), None);
+        let rays: Vec<Ray> = (0..100).flat_map(|i| (0..100).map(move |j| ray_at(i as f64 / 100f64, j as f64 / 100f64))).collect();
+        let hits = rays.iter().filter(|r| object.does_intersect(r)).count();
+        assert!(hits > 2700 && hits < 3300, "{} of 10000 rays hit", hits);
+        // The same ray always gets the same answer.
+        assert!(rays.iter().all(|r| object.does_intersect(r) == object.does_intersect(r)));
+    }
+}
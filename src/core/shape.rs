@@ -28,4 +28,11 @@ impl Geometry for Shape {
         self.geometry.intersect(&world_ray.clone().invert_transform(&self.object_to_world))
             .map(|i| i.transform(&self.object_to_world))
     }
+
+    fn intervals(&self, world_ray: &Ray) -> Vec<RayInterval> {
+        self.geometry.intervals(&world_ray.clone().invert_transform(&self.object_to_world))
+            .into_iter()
+            .map(|i| i.transform(&self.object_to_world))
+            .collect()
+    }
 }
@@ -5,6 +5,14 @@ use math::*;
 #[derive(Debug, Clone, Copy)]
 pub struct Uv(pub f64, pub f64);
 
+impl Uv {
+    // Wraps both coordinates into [0, 1), so that textures repeat across surfaces whose UVs run further.
+    pub fn wrap(self) -> Uv {
+        let wrap = |x: f64| x - x.floor();
+        Uv(wrap(self.0), wrap(self.1))
+    }
+}
+
 impl Mul<f64> for Uv {
     type Output = Uv;
 
@@ -58,4 +66,11 @@ mod tests {
         assert_eq!(product.0, 10f64);
         assert_eq!(product.1, 20f64);
     }
+
+    #[test]
+    fn it_should_wrap_uv() {
+        let wrapped = Uv(2.25f64, -0.25f64).wrap();
+        assert_eq!(wrapped.0, 0.25f64);
+        assert_eq!(wrapped.1, 0.75f64);
+    }
 }
@@ -58,6 +58,7 @@ fn intersect<T: Geometry>(tree: &VolumeKdTree<T>, ray: Ray) -> Option<Intersecti
     while node_stack.len() > 0 {
         let (node, t_min, t_max) = node_stack.pop().unwrap();
         if t_min < r.t_max {
+            record_stat!(NodeVisited);
             match node {
                 &Node::Internal(axis, distance, ref left, ref right) => {
                     let origin_component = r.origin[axis];
@@ -87,6 +88,7 @@ fn intersect<T: Geometry>(tree: &VolumeKdTree<T>, ray: Ray) -> Option<Intersecti
                 &Node::Leaf(ref items) => {
                     for item in items {
                         // TODO: Should we bother checking the bounding box before testing intersection?
+                        record_stat!(PrimitiveTested);
                         match item.intersect(&r) {
                             Some(intersection) => {
                                 r.t_max = intersection.distance;
@@ -106,6 +108,11 @@ fn intersect<T: Geometry>(tree: &VolumeKdTree<T>, ray: Ray) -> Option<Intersecti
 pub struct VolumeKdTree<T: Geometry> {
     root: Node<T>,
     bound: BoundingBox,
+    // Items with infinite bounds, like planes, can't be placed in the tree -- they'd drag its bound out to
+    // infinity and make every split's surface area meaningless -- so they're tested separately.
+    unbounded: Vec<Arc<T>>,
+    // The tree's bound plus the unbounded items', which is what everything above this sees.
+    total_bound: BoundingBox,
 }
 
 fn surface_area(bound: &BoundingBox) -> f64 {
@@ -245,32 +252,50 @@ fn recursively_build_tree<T: Geometry>(items: Vec<(Arc<T>, BoundingBox)>, node_b
 
 impl <T: Geometry> VolumeKdTree<T> {
     pub fn from(items: Vec<T>) -> VolumeKdTree<T> {
-        let pairs: Vec<(Arc<T>, BoundingBox)> = items
+        // Items with empty bounds (e.g. disjoint CSG intersections) can't be hit, so they're dropped.
+        let (pairs, unbounded): (Vec<(Arc<T>, BoundingBox)>, Vec<(Arc<T>, BoundingBox)>) = items
             .into_iter()
             .map(|i| {
                 let bound = i.bound();
                 (Arc::new(i), bound)
             })
-            .collect();
+            .filter(|&(_, ref bound)| !bound.is_empty())
+            .partition(|&(_, ref bound)| bound.is_bounded());
 
         let tree_bound = pairs
             .iter()
             .fold(BoundingBox::empty(), |unioned_bounds, &(_, ref bound)| BoundingBox::union(&unioned_bounds, bound));
 
+        let total_bound = unbounded
+            .iter()
+            .fold(tree_bound.clone(), |unioned_bounds, &(_, ref bound)| BoundingBox::union(&unioned_bounds, bound));
+
         VolumeKdTree {
             root: recursively_build_tree(pairs, tree_bound.clone()),
             bound: tree_bound,
+            unbounded: unbounded.into_iter().map(|(i, _)| i).collect(),
+            total_bound,
         }
     }
 }
 
 impl <T: Geometry> Geometry for VolumeKdTree<T> {
     fn bound(&self) -> BoundingBox {
-        self.bound.clone()
+        self.total_bound.clone()
     }
 
     fn intersect(&self, ray: &Ray) -> Option<Intersection> {
-        intersect(&self, ray.clone())
+        let mut r = ray.clone();
+        let mut closest: Option<Intersection> = None;
+        for item in &self.unbounded {
+            record_stat!(PrimitiveTested);
+            if let Some(intersection) = item.intersect(&r) {
+                r.t_max = intersection.distance;
+                closest = Some(intersection);
+            }
+        }
+        // Anything the tree finds is necessarily closer, since the ray has been shortened.
+        intersect(&self, r).or(closest)
     }
 
     fn does_intersect(&self, ray: &Ray) -> bool {
@@ -281,6 +306,43 @@ impl <T: Geometry> Geometry for VolumeKdTree<T> {
 
 impl <T: Geometry> Debug for VolumeKdTree<T> {
     fn fmt(&self, f: &mut Formatter) -> Result {
+        if self.unbounded.len() > 0 {
+            write!(f, "{} unbounded objects\n", self.unbounded.len())?;
+        }
         self.root.fmt_indented(f, 0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct Bounded(BoundingBox);
+
+    impl Geometry for Bounded {
+        fn bound(&self) -> BoundingBox {
+            self.0.clone()
+        }
+
+        fn intersect(&self, _ray: &Ray) -> Option<Intersection> {
+            None
+        }
+    }
+
+    #[test]
+    fn it_should_only_test_infinite_items_separately() {
+        let unit = BoundingBox { min: Point::uniform(-1f64), max: Point::uniform(1f64) };
+        let tree = VolumeKdTree::from(vec![Bounded(unit), Bounded(BoundingBox::empty()), Bounded(BoundingBox::infinite())]);
+        assert_eq!(tree.unbounded.len(), 1);
+        assert_eq!(tree.root.size(), 1);
+        assert!(!tree.bound().is_bounded() && !tree.bound().is_empty());
+    }
+
+    #[test]
+    fn it_should_drop_items_with_empty_bounds() {
+        let tree = VolumeKdTree::from(vec![Bounded(BoundingBox::empty())]);
+        assert!(tree.unbounded.is_empty());
+        assert!(tree.bound().is_empty());
+    }
+}
@@ -0,0 +1,189 @@
+use core::*;
+use math::*;
+
+#[derive(Debug)]
+pub struct UnionField {
+    pub operands: Vec<Box<DistanceField>>,
+}
+
+impl DistanceField for UnionField {
+    fn distance(&self, point: Point) -> f64 {
+        self.operands.iter().fold(f64::INFINITY, |d, o| d.min(o.distance(point)))
+    }
+}
+
+#[derive(Debug)]
+pub struct IntersectionField {
+    pub operands: Vec<Box<DistanceField>>,
+}
+
+impl DistanceField for IntersectionField {
+    fn distance(&self, point: Point) -> f64 {
+        self.operands.iter().fold(f64::NEG_INFINITY, |d, o| d.max(o.distance(point)))
+    }
+}
+
+#[derive(Debug)]
+pub struct DifferenceField {
+    pub positive: Box<DistanceField>,
+    pub negatives: Vec<Box<DistanceField>>,
+}
+
+impl DistanceField for DifferenceField {
+    fn distance(&self, point: Point) -> f64 {
+        self.negatives.iter().fold(self.positive.distance(point), |d, n| d.max(-n.distance(point)))
+    }
+}
+
+// Blends the operands together over about `smoothness` units, using the polynomial smooth minimum.
+#[derive(Debug)]
+pub struct SmoothUnionField {
+    pub operands: Vec<Box<DistanceField>>,
+    pub smoothness: f64,
+}
+
+fn smooth_min(a: f64, b: f64, k: f64) -> f64 {
+    let h = (0.5f64 + 0.5f64 * (b - a) / k).max(0f64).min(1f64);
+    b + (a - b) * h - k * h * (1f64 - h)
+}
+
+impl DistanceField for SmoothUnionField {
+    fn distance(&self, point: Point) -> f64 {
+        let mut distances = self.operands.iter().map(|o| o.distance(point));
+        let first = distances.next().expect("smooth union requires at least one operand");
+        if self.smoothness <= 0f64 {
+            distances.fold(first, f64::min)
+        } else {
+            distances.fold(first, |d, next| smooth_min(d, next, self.smoothness))
+        }
+    }
+}
+
+// Tiles space with copies of the field, one per cell of size `period` centered on the origin. A zero
+// component leaves that axis alone. The field should fit inside a single cell, or the distances will be
+// wrong near the cell walls.
+#[derive(Debug)]
+pub struct RepetitionField {
+    pub field: Box<DistanceField>,
+    pub period: Vec3,
+}
+
+impl DistanceField for RepetitionField {
+    fn distance(&self, point: Point) -> f64 {
+        let repeat = |x: f64, period: f64| if period == 0f64 { x } else { x - period * (x / period).round() };
+        self.field.distance(Point::new(
+            repeat(point.x, self.period.x),
+            repeat(point.y, self.period.y),
+            repeat(point.z, self.period.z),
+        ))
+    }
+}
+
+// Rotates each horizontal slice of the field around +y by `rate` radians per unit of height.
+#[derive(Debug)]
+pub struct TwistField {
+    pub field: Box<DistanceField>,
+    pub rate: f64,
+}
+
+impl DistanceField for TwistField {
+    fn distance(&self, point: Point) -> f64 {
+        let (sin, cos) = (self.rate * point.y).sin_cos();
+        let twisted = Point::new(cos * point.x - sin * point.z, point.y, sin * point.x + cos * point.z);
+        // Twisting stretches space more the further out from the axis it is, so the distance needs to be
+        // shrunk by the local stretch factor to remain a lower bound.
+        let radius = (point.x * point.x + point.z * point.z).sqrt();
+        self.field.distance(twisted) / (1f64 + self.rate * self.rate * radius * radius).sqrt()
+    }
+}
+
+// Moves the field by an arbitrary transform. Distances are shrunk by how much the transform can stretch
+// space, which is exact for rotations, translations and uniform scales.
+#[derive(Debug)]
+pub struct TransformedField {
+    field: Box<DistanceField>,
+    transform: Transform,
+    max_inverse_stretch: f64,
+}
+
+impl TransformedField {
+    pub fn new(field: Box<DistanceField>, transform: Transform) -> TransformedField {
+        let m = &transform.m_inverse.cells;
+        let column = |c: usize| Vec3::new(m[0][c], m[1][c], m[2][c]);
+        let columns = [column(0), column(1), column(2)];
+        let is_orthogonal = (0..3).all(|i| (0..i).all(|j| {
+            columns[i].dot(&columns[j]).abs() <= 1e-9 * columns[i].magnitude() * columns[j].magnitude()
+        }));
+        // The largest singular value of the inverse's linear part. When the columns aren't orthogonal,
+        // settle for the Frobenius norm, which is an upper bound on it.
+        let max_inverse_stretch = if is_orthogonal {
+            columns.iter().map(|c| c.magnitude()).fold(0f64, f64::max)
+        } else {
+            columns.iter().map(|c| c.magnitude2()).sum::<f64>().sqrt()
+        };
+        TransformedField { field, transform, max_inverse_stretch }
+    }
+}
+
+impl DistanceField for TransformedField {
+    fn distance(&self, point: Point) -> f64 {
+        self.field.distance(point.invert_transform(&self.transform)) / self.max_inverse_stretch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use distance_field::*;
+
+    fn unit_sphere_at(x: f64) -> Box<DistanceField> {
+        Box::new(TransformedField::new(
+            Box::new(SphereField { radius: 1f64 }),
+            Transform::new(Mat4::create_translation(Vec3::new(x, 0f64, 0f64))),
+        ))
+    }
+
+    #[test]
+    fn it_should_take_the_closest_operand_of_a_union() {
+        let union = UnionField { operands: vec![unit_sphere_at(-2f64), unit_sphere_at(2f64)] };
+        assert!((union.distance(Point::new(4f64, 0f64, 0f64)) - 1f64).abs() < 1e-9);
+        assert!((union.distance(Point::new(0f64, 0f64, 0f64)) - 1f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_should_carve_out_negatives() {
+        let difference = DifferenceField { positive: unit_sphere_at(0f64), negatives: vec![unit_sphere_at(1f64)] };
+        assert!(difference.distance(Point::new(0.5f64, 0f64, 0f64)) > 0f64);
+        assert!(difference.distance(Point::new(-0.5f64, 0f64, 0f64)) < 0f64);
+    }
+
+    #[test]
+    fn it_should_fill_in_between_smoothly_unioned_operands() {
+        let hard = UnionField { operands: vec![unit_sphere_at(-1.1f64), unit_sphere_at(1.1f64)] };
+        let smooth = SmoothUnionField { operands: vec![unit_sphere_at(-1.1f64), unit_sphere_at(1.1f64)], smoothness: 0.5f64 };
+        let between = Point::new(0f64, 0.6f64, 0f64);
+        assert!(smooth.distance(between) < hard.distance(between));
+    }
+
+    #[test]
+    fn it_should_repeat_fields() {
+        let repeated = RepetitionField { field: Box::new(SphereField { radius: 1f64 }), period: Vec3::new(4f64, 0f64, 0f64) };
+        assert!((repeated.distance(Point::new(8f64, 2f64, 0f64)) - 1f64).abs() < 1e-9);
+        assert!((repeated.distance(Point::new(8f64, 0f64, 2f64)) - 1f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_should_scale_distances_with_transforms() {
+        let scaled = TransformedField::new(
+            Box::new(SphereField { radius: 1f64 }),
+            Transform::new(Mat4::create_scale(Vec3::new(2f64, 2f64, 2f64))),
+        );
+        assert!((scaled.distance(Point::new(5f64, 0f64, 0f64)) - 3f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_should_leave_the_axis_of_a_twist_alone() {
+        let twisted = TwistField { field: Box::new(BoxField { half_size: Vec3::new(1f64, 2f64, 1f64), rounding: 0f64 }), rate: 1f64 };
+        assert!((twisted.distance(Point::new(0f64, 3f64, 0f64)) - 1f64).abs() < 1e-9);
+    }
+}
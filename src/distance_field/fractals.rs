@@ -0,0 +1,98 @@
+use core::*;
+use math::*;
+
+// Distance estimators for 3D fractals. They're only approximately lower bounds, so they're best traced
+// with a step_scale somewhat below 1.
+
+// The power-n Mandelbulb, which fits in a sphere of radius about 1.2 around the origin. See
+// http://blog.hvidtfeldts.net/index.php/2011/09/distance-estimated-3d-fractals-v-the-mandelbulb-different-de-approximations/
+#[derive(Debug)]
+pub struct MandelbulbField {
+    pub power: f64,
+    pub iterations: usize,
+}
+
+impl DistanceField for MandelbulbField {
+    fn distance(&self, point: Point) -> f64 {
+        const BAILOUT: f64 = 2f64;
+
+        let c = point.as_vector();
+        let mut z = c;
+        let mut dr = 1f64;
+        let mut r = 0f64;
+        for _ in 0..self.iterations {
+            r = z.magnitude();
+            if r > BAILOUT {
+                break;
+            }
+            // Spherical coordinates with +y as the pole, like everything else.
+            let theta = (z.y / r).max(-1f64).min(1f64).acos() * self.power;
+            let phi = z.z.atan2(z.x) * self.power;
+            dr = r.powf(self.power - 1f64) * self.power * dr + 1f64;
+            let zr = r.powf(self.power);
+            z = Vec3::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin()) * zr + c;
+        }
+        if r == 0f64 {
+            // The origin is deep inside; any small negative number will do.
+            -1f64
+        } else {
+            0.5f64 * r.ln() * r / dr
+        }
+    }
+}
+
+// The Menger sponge filling the cube from -1 to 1. See https://iquilezles.org/articles/menger/.
+#[derive(Debug)]
+pub struct MengerSpongeField {
+    pub iterations: usize,
+}
+
+impl DistanceField for MengerSpongeField {
+    fn distance(&self, point: Point) -> f64 {
+        let cube = |p: Vec3| {
+            let q = Vec3::new(p.x.abs() - 1f64, p.y.abs() - 1f64, p.z.abs() - 1f64);
+            Vec3::new(q.x.max(0f64), q.y.max(0f64), q.z.max(0f64)).magnitude() + q.x.max(q.y).max(q.z).min(0f64)
+        };
+        // Like GLSL's mod, which always returns something with the sign of the divisor.
+        let modulo = |x: f64, y: f64| x - y * (x / y).floor();
+
+        let p = point.as_vector();
+        let mut d = cube(p);
+        let mut scale = 1f64;
+        for _ in 0..self.iterations {
+            let a = Vec3::new(modulo(p.x * scale, 2f64) - 1f64, modulo(p.y * scale, 2f64) - 1f64, modulo(p.z * scale, 2f64) - 1f64);
+            scale *= 3f64;
+            let r = Vec3::new(
+                (1f64 - 3f64 * a.x.abs()).abs(),
+                (1f64 - 3f64 * a.y.abs()).abs(),
+                (1f64 - 3f64 * a.z.abs()).abs(),
+            );
+            let (da, db, dc) = (r.x.max(r.y), r.y.max(r.z), r.z.max(r.x));
+            let cross = (da.min(db).min(dc) - 1f64) / scale;
+            d = d.max(cross);
+        }
+        d
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_be_inside_the_mandelbulb_at_the_origin() {
+        let bulb = MandelbulbField { power: 8f64, iterations: 10 };
+        assert!(bulb.distance(Point::new(0f64, 0f64, 0f64)) < 0f64);
+        assert!(bulb.distance(Point::new(0f64, 3f64, 0f64)) > 1f64);
+    }
+
+    #[test]
+    fn it_should_hollow_out_the_middle_of_the_menger_sponge() {
+        let sponge = MengerSpongeField { iterations: 3 };
+        // The center of the cube is on all three of the first iteration's tunnels.
+        assert!(sponge.distance(Point::new(0f64, 0f64, 0f64)) > 0f64);
+        // A corner is solid at every iteration.
+        assert!(sponge.distance(Point::new(0.99f64, 0.99f64, 0.99f64)) < 0f64);
+        assert!((sponge.distance(Point::new(3f64, 0.5f64, 0.5f64)) - 2f64).abs() < 1e-9);
+    }
+}
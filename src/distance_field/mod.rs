@@ -0,0 +1,7 @@
+mod combinators;
+mod fractals;
+mod primitives;
+
+pub use self::combinators::*;
+pub use self::fractals::*;
+pub use self::primitives::*;
@@ -0,0 +1,135 @@
+use core::*;
+use math::*;
+
+// Most of these are from Inigo Quilez's catalog: https://iquilezles.org/articles/distfunctions/. Like the
+// quadrics, everything with an axis of symmetry is symmetric about +y.
+
+#[derive(Debug)]
+pub struct SphereField {
+    pub radius: f64,
+}
+
+impl DistanceField for SphereField {
+    fn distance(&self, point: Point) -> f64 {
+        point.as_vector().magnitude() - self.radius
+    }
+}
+
+// An axis-aligned box centered on the origin, with its edges rounded off by `rounding` (which doesn't
+// change its overall size).
+#[derive(Debug)]
+pub struct BoxField {
+    pub half_size: Vec3,
+    pub rounding: f64,
+}
+
+impl DistanceField for BoxField {
+    fn distance(&self, point: Point) -> f64 {
+        let q = Vec3::new(
+            point.x.abs() - self.half_size.x + self.rounding,
+            point.y.abs() - self.half_size.y + self.rounding,
+            point.z.abs() - self.half_size.z + self.rounding,
+        );
+        let outside = Vec3::new(q.x.max(0f64), q.y.max(0f64), q.z.max(0f64)).magnitude();
+        let inside = q.x.max(q.y).max(q.z).min(0f64);
+        outside + inside - self.rounding
+    }
+}
+
+#[derive(Debug)]
+pub struct TorusField {
+    pub major_radius: f64,
+    pub minor_radius: f64,
+}
+
+impl DistanceField for TorusField {
+    fn distance(&self, point: Point) -> f64 {
+        let ring = (point.x * point.x + point.z * point.z).sqrt() - self.major_radius;
+        (ring * ring + point.y * point.y).sqrt() - self.minor_radius
+    }
+}
+
+// A capped cylinder centered on the origin.
+#[derive(Debug)]
+pub struct CylinderField {
+    pub radius: f64,
+    pub half_height: f64,
+}
+
+impl DistanceField for CylinderField {
+    fn distance(&self, point: Point) -> f64 {
+        let radial = (point.x * point.x + point.z * point.z).sqrt() - self.radius;
+        let axial = point.y.abs() - self.half_height;
+        let outside = (radial.max(0f64) * radial.max(0f64) + axial.max(0f64) * axial.max(0f64)).sqrt();
+        radial.max(axial).min(0f64) + outside
+    }
+}
+
+// Everything within `radius` of the segment from `from` to `to`.
+#[derive(Debug)]
+pub struct CapsuleField {
+    pub from: Point,
+    pub to: Point,
+    pub radius: f64,
+}
+
+impl DistanceField for CapsuleField {
+    fn distance(&self, point: Point) -> f64 {
+        let (pa, ba) = (point - self.from, self.to - self.from);
+        let h = (pa.dot(&ba) / ba.magnitude2()).max(0f64).min(1f64);
+        (pa - ba * h).magnitude() - self.radius
+    }
+}
+
+// The half-space y <= 0.
+#[derive(Debug)]
+pub struct PlaneField;
+
+impl DistanceField for PlaneField {
+    fn distance(&self, point: Point) -> f64 {
+        point.y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_measure_a_sphere() {
+        let sphere = SphereField { radius: 1f64 };
+        assert_eq!(sphere.distance(Point::new(0f64, 3f64, 0f64)), 2f64);
+        assert_eq!(sphere.distance(Point::new(0f64, 0f64, 0f64)), -1f64);
+    }
+
+    #[test]
+    fn it_should_measure_a_box_from_its_faces_and_corners() {
+        let cube = BoxField { half_size: Vec3::new(1f64, 1f64, 1f64), rounding: 0f64 };
+        assert_eq!(cube.distance(Point::new(3f64, 0f64, 0f64)), 2f64);
+        assert_eq!(cube.distance(Point::new(0f64, 0.5f64, 0f64)), -0.5f64);
+        assert!((cube.distance(Point::new(2f64, 2f64, 2f64)) - 3f64.sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_should_round_a_box_without_growing_it() {
+        let rounded = BoxField { half_size: Vec3::new(1f64, 1f64, 1f64), rounding: 0.25f64 };
+        assert!((rounded.distance(Point::new(2f64, 0f64, 0f64)) - 1f64).abs() < 1e-9);
+        assert!(rounded.distance(Point::new(1f64, 1f64, 0f64)) > 0f64);
+    }
+
+    #[test]
+    fn it_should_measure_a_torus_and_cylinder() {
+        let torus = TorusField { major_radius: 2f64, minor_radius: 0.5f64 };
+        assert_eq!(torus.distance(Point::new(0f64, 0f64, 0f64)), 1.5f64);
+        let cylinder = CylinderField { radius: 1f64, half_height: 1f64 };
+        assert_eq!(cylinder.distance(Point::new(0f64, 3f64, 0f64)), 2f64);
+        assert_eq!(cylinder.distance(Point::new(3f64, 0f64, 0f64)), 2f64);
+    }
+
+    #[test]
+    fn it_should_measure_a_capsule() {
+        let capsule = CapsuleField { from: Point::new(0f64, -1f64, 0f64), to: Point::new(0f64, 1f64, 0f64), radius: 0.5f64 };
+        assert_eq!(capsule.distance(Point::new(0f64, 3f64, 0f64)), 1.5f64);
+        assert_eq!(capsule.distance(Point::new(2f64, 0f64, 0f64)), 1.5f64);
+    }
+}
@@ -0,0 +1,18 @@
+mod obj;
+mod ply;
+
+pub use self::obj::*;
+pub use self::ply::*;
+
+use std::path::Path;
+use geometry::*;
+
+// Formats a mesh as OBJ or PLY, depending on the file's extension, so it can be looked at or reused elsewhere.
+pub fn format_mesh(mesh: &TriangleMeshData, path: &Path) -> Result<Vec<u8>, String> {
+    let extension = path.extension().and_then(|extension| extension.to_str()).map(|extension| extension.to_lowercase());
+    match extension.as_ref().map(|extension| extension.as_str()) {
+        Some("obj") => Ok(format_obj(mesh).into_bytes()),
+        Some("ply") => Ok(format_ply(mesh)),
+        _ => Err(format!("can only export meshes to .obj or .ply files, not {:?}", path)),
+    }
+}
@@ -0,0 +1,51 @@
+use std::fmt::Write;
+use geometry::*;
+
+// A Wavefront OBJ of the mesh, with its normals and uvs if it has them. Indices start from 1.
+pub fn format_obj(mesh: &TriangleMeshData) -> String {
+    let mut obj = String::new();
+    for p in &mesh.positions {
+        writeln!(obj, "v {} {} {}", p.x, p.y, p.z).unwrap();
+    }
+    if let Some(ref uvs) = mesh.uvs {
+        for uv in uvs {
+            writeln!(obj, "vt {} {}", uv.0, uv.1).unwrap();
+        }
+    }
+    if let Some(ref normals) = mesh.normals {
+        for n in normals {
+            writeln!(obj, "vn {} {} {}", n.x, n.y, n.z).unwrap();
+        }
+    }
+
+    let vertex = |i: usize| match (mesh.uvs.is_some(), mesh.normals.is_some()) {
+        (false, false) => format!("{}", i + 1),
+        (true, false) => format!("{0}/{0}", i + 1),
+        (false, true) => format!("{0}//{0}", i + 1),
+        (true, true) => format!("{0}/{0}/{0}", i + 1),
+    };
+    for &(i0, i1, i2) in &mesh.indices {
+        writeln!(obj, "f {} {} {}", vertex(i0), vertex(i1), vertex(i2)).unwrap();
+    }
+    obj
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::*;
+    use math::*;
+
+    #[test]
+    fn it_should_write_whichever_attributes_the_mesh_has() {
+        let positions = vec![Point::new(0f64, 0f64, 0f64), Point::new(1f64, 0f64, 0f64), Point::new(0f64, 1.5f64, 0f64)];
+        let uvs = vec![Uv(0f64, 0f64), Uv(1f64, 0f64), Uv(0f64, 1f64)];
+        let mesh = TriangleMeshData::new(positions.clone(), Smoothing::None, Some(uvs), vec![(0, 1, 2)]);
+        assert_eq!(format_obj(&mesh), "v 0 0 0\nv 1 0 0\nv 0 1.5 0\nvt 0 0\nvt 1 0\nvt 0 1\nf 1/1 2/2 3/3\n");
+
+        let mesh = TriangleMeshData::new(positions, Smoothing::Implicit, None, vec![(0, 1, 2)]);
+        let obj = format_obj(&mesh);
+        assert!(obj.contains("vn 0 0 1\n"));
+        assert!(obj.ends_with("f 1//1 2//2 3//3\n"));
+    }
+}
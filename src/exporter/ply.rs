@@ -0,0 +1,68 @@
+use geometry::*;
+
+fn push_f32(bytes: &mut Vec<u8>, value: f64) {
+    bytes.extend_from_slice(&(value as f32).to_le_bytes());
+}
+
+// A binary little-endian PLY of the mesh, with its normals and uvs if it has them. Values are written as
+// single-precision floats, which is what most tools expect.
+pub fn format_ply(mesh: &TriangleMeshData) -> Vec<u8> {
+    let mut header = String::from("ply\nformat binary_little_endian 1.0\n");
+    header += &format!("element vertex {}\n", mesh.positions.len());
+    header += "property float x\nproperty float y\nproperty float z\n";
+    if mesh.normals.is_some() {
+        header += "property float nx\nproperty float ny\nproperty float nz\n";
+    }
+    if mesh.uvs.is_some() {
+        header += "property float u\nproperty float v\n";
+    }
+    header += &format!("element face {}\n", mesh.indices.len());
+    header += "property list uchar int vertex_indices\nend_header\n";
+
+    let mut ply = header.into_bytes();
+    for (i, p) in mesh.positions.iter().enumerate() {
+        push_f32(&mut ply, p.x);
+        push_f32(&mut ply, p.y);
+        push_f32(&mut ply, p.z);
+        if let Some(ref normals) = mesh.normals {
+            push_f32(&mut ply, normals[i].x);
+            push_f32(&mut ply, normals[i].y);
+            push_f32(&mut ply, normals[i].z);
+        }
+        if let Some(ref uvs) = mesh.uvs {
+            push_f32(&mut ply, uvs[i].0);
+            push_f32(&mut ply, uvs[i].1);
+        }
+    }
+    for &(i0, i1, i2) in &mesh.indices {
+        ply.push(3);
+        for &i in &[i0, i1, i2] {
+            ply.extend_from_slice(&(i as i32).to_le_bytes());
+        }
+    }
+    ply
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use math::*;
+
+    #[test]
+    fn it_should_write_a_header_and_binary_body() {
+        let positions = vec![Point::new(0f64, 0f64, 0f64), Point::new(1f64, 0f64, 0f64), Point::new(0f64, 1f64, 0f64)];
+        let mesh = TriangleMeshData::new(positions, Smoothing::Implicit, None, vec![(0, 1, 2)]);
+        let ply = format_ply(&mesh);
+
+        let header = "ply\nformat binary_little_endian 1.0\nelement vertex 3\n\
+            property float x\nproperty float y\nproperty float z\nproperty float nx\nproperty float ny\nproperty float nz\n\
+            element face 1\nproperty list uchar int vertex_indices\nend_header\n";
+        assert!(ply.starts_with(header.as_bytes()));
+        let body = &ply[header.len()..];
+        assert_eq!(body.len(), 3 * 6 * 4 + 1 + 3 * 4);
+        // The second vertex's x, then the first vertex's normal's z.
+        assert_eq!(&body[24..28], &1f32.to_le_bytes());
+        assert_eq!(&body[20..24], &1f32.to_le_bytes());
+        assert_eq!(&body[72..], &[3, 0, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0]);
+    }
+}
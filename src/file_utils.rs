@@ -1,6 +1,6 @@
 use std::path::Path;
-use std::fs::File;
-use std::io::Read;
+use std::fs::{ File, create_dir_all };
+use std::io::{ Read, Write };
 use regex::Regex;
 
 lazy_static! {
@@ -21,3 +21,26 @@ pub fn read_file_contents(path: &Path) -> String {
 
     contents
 }
+
+pub fn read_file_bytes(path: &Path) -> Vec<u8> {
+    let formatted_path = path.to_str().unwrap_or("input file");
+    let mut contents: Vec<u8> = vec![];
+    File::open(path)
+        .expect(&format!("couldn't open {}", formatted_path))
+        .read_to_end(&mut contents)
+        .expect(&format!("couldn't read {} after opening", formatted_path));
+
+    contents
+}
+
+// Creates the file's directory if it has to.
+pub fn write_file_bytes(path: &Path, contents: &[u8]) {
+    let formatted_path = path.to_str().unwrap_or("output file");
+    if let Some(directory) = path.parent() {
+        create_dir_all(directory).expect(&format!("couldn't create the directory for {}", formatted_path));
+    }
+    File::create(path)
+        .expect(&format!("couldn't create {}", formatted_path))
+        .write_all(contents)
+        .expect(&format!("couldn't write {}", formatted_path));
+}
@@ -0,0 +1,204 @@
+use core::*;
+use math::*;
+
+// A bicubic bezier patch, intersected directly by recursive subdivision: pieces of the patch whose control
+// points' bound the ray misses are thrown out, and the rest are halved until they're nearly flat, at which
+// point the ray is tested against the piece's corners and the hit polished with Newton's method.
+#[derive(Debug)]
+pub struct BezierPatch {
+    patch: BicubicBezier,
+    bound: BoundingBox,
+    // How far a piece's control points can stray from its corners' bilinear surface and still be flat.
+    flatness: f64,
+}
+
+const MAX_DEPTH: usize = 20;
+const NEWTON_STEPS: usize = 4;
+
+#[derive(Clone, Copy)]
+struct Hit {
+    t: f64,
+    u: f64,
+    v: f64,
+}
+
+fn control_point_bound(patch: &BicubicBezier) -> BoundingBox {
+    patch.control_points.iter().fold(BoundingBox::empty(), |bound, p| bound.with_point(p))
+}
+
+impl BezierPatch {
+    pub fn new(patch: BicubicBezier) -> BezierPatch {
+        let bound = control_point_bound(&patch);
+        let flatness = (bound.max - bound.min).magnitude() * 1e-4f64;
+        BezierPatch { patch, bound, flatness }
+    }
+
+    fn is_flat(piece: &BicubicBezier, flatness: f64) -> bool {
+        let p = &piece.control_points;
+        let (c00, c10, c01, c11) = (p[0], p[3], p[12], p[15]);
+        (0..16).all(|index| {
+            let (s, t) = ((index % 4) as f64 / 3f64, (index / 4) as f64 / 3f64);
+            let bilinear = c00 * ((1f64 - s) * (1f64 - t)) + c10 * (s * (1f64 - t)) + c01 * ((1f64 - s) * t) + c11 * (s * t);
+            (p[index] - bilinear).magnitude() <= flatness
+        })
+    }
+
+    // Moller-Trumbore, returning (t, b1, b2).
+    fn intersect_triangle(ray: &Ray, p0: Point, p1: Point, p2: Point) -> Option<(f64, f64, f64)> {
+        let (edge1, edge2) = (p1 - p0, p2 - p0);
+        let h = ray.direction.cross(edge2);
+        let determinant = edge1.dot(&h);
+        if determinant.abs() < 1e-20 {
+            return None;
+        }
+        let s = ray.origin - p0;
+        let b1 = s.dot(&h) / determinant;
+        let q = s.cross(edge1);
+        let b2 = ray.direction.dot(&q) / determinant;
+        // A little slack keeps rays from slipping between neighboring pieces.
+        const SLACK: f64 = 1e-6;
+        if b1 < -SLACK || b2 < -SLACK || b1 + b2 > 1f64 + SLACK {
+            return None;
+        }
+        Some((edge2.dot(&q) / determinant, b1, b2))
+    }
+
+    // Solves patch(u, v) = ray(t) starting from a guess, as the guess is only as good as the flat piece.
+    fn refine(&self, ray: &Ray, hit: Hit) -> Hit {
+        let (mut u, mut v, mut t) = (hit.u, hit.v, hit.t);
+        for _ in 0..NEWTON_STEPS {
+            let residual = self.patch.at(u, v) - (ray.origin + ray.direction * t);
+            let (a, b, c) = (self.patch.partial_u(u, v), self.patch.partial_v(u, v), -ray.direction);
+            let determinant = a.dot(&b.cross(c));
+            if determinant.abs() < 1e-20 {
+                break;
+            }
+            // Cramer's rule on [a b c] (du dv dt) = -residual.
+            let r = -residual;
+            u += r.dot(&b.cross(c)) / determinant;
+            v += a.dot(&r.cross(c)) / determinant;
+            t += a.dot(&b.cross(r)) / determinant;
+        }
+        let is_sane = u >= -1e-6 && u <= 1f64 + 1e-6 && v >= -1e-6 && v <= 1f64 + 1e-6 && t.is_finite();
+        if is_sane {
+            Hit { t, u: u.max(0f64).min(1f64), v: v.max(0f64).min(1f64) }
+        } else {
+            hit
+        }
+    }
+
+    fn subdivide(&self, piece: &BicubicBezier, (u0, u1): (f64, f64), (v0, v1): (f64, f64), depth: usize, ray: &Ray, closest: &mut Option<Hit>) {
+        let t_max = closest.as_ref().map_or(ray.t_max, |hit| hit.t);
+        // Neighboring pieces' bounds can round apart from each other, so pad them a bit.
+        let bound = control_point_bound(piece);
+        let padding = Vec3::uniform(self.flatness);
+        let padded = BoundingBox { min: bound.min - padding, max: bound.max + padding };
+        if padded.intersect(&Ray { t_max, ..ray.clone() }).is_none() {
+            return;
+        }
+
+        if depth >= MAX_DEPTH || BezierPatch::is_flat(piece, self.flatness) {
+            let p = &piece.control_points;
+            let (c00, c10, c01, c11) = (p[0], p[3], p[12], p[15]);
+            let (du, dv) = (u1 - u0, v1 - v0);
+            let candidates = [
+                BezierPatch::intersect_triangle(ray, c00, c10, c01).map(|(t, b1, b2)| Hit { t, u: u0 + b1 * du, v: v0 + b2 * dv }),
+                BezierPatch::intersect_triangle(ray, c11, c01, c10).map(|(t, b1, b2)| Hit { t, u: u1 - b1 * du, v: v1 - b2 * dv }),
+            ];
+            for hit in candidates.iter().flat_map(|hit| hit.iter()) {
+                let hit = self.refine(ray, *hit);
+                if hit.t >= ray.t_min && hit.t <= closest.as_ref().map_or(ray.t_max, |closest| closest.t) {
+                    *closest = Some(hit);
+                }
+            }
+            return;
+        }
+
+        let (u_middle, v_middle) = ((u0 + u1) / 2f64, (v0 + v1) / 2f64);
+        let (left, right) = piece.split_u();
+        let (bottom_left, top_left) = left.split_v();
+        let (bottom_right, top_right) = right.split_v();
+        self.subdivide(&bottom_left, (u0, u_middle), (v0, v_middle), depth + 1, ray, closest);
+        self.subdivide(&bottom_right, (u_middle, u1), (v0, v_middle), depth + 1, ray, closest);
+        self.subdivide(&top_left, (u0, u_middle), (v_middle, v1), depth + 1, ray, closest);
+        self.subdivide(&top_right, (u_middle, u1), (v_middle, v1), depth + 1, ray, closest);
+    }
+}
+
+impl Geometry for BezierPatch {
+    fn bound(&self) -> BoundingBox {
+        self.bound.clone()
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+        let mut closest = None;
+        self.subdivide(&self.patch, (0f64, 1f64), (0f64, 1f64), 0, ray, &mut closest);
+        closest.map(|Hit { t, u, v }| {
+            let (u_axis, v_axis) = (self.patch.partial_u(u, v), self.patch.partial_v(u, v));
+            let geometry = if u_axis.cross(v_axis).magnitude2() > 1e-20 {
+                IntersectionGeometry::new(u_axis, v_axis)
+            } else {
+                IntersectionGeometry::from_normal(self.patch.normal(u, v))
+            };
+            Intersection {
+                distance: t,
+                location: ray.origin + ray.direction * t,
+                geometry,
+                shading_geometry: None,
+                uv: Uv(u, v),
+                material: None,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A bump over [0, 1]^2 in the xz-plane, rising to y = 0.75 at the middle, facing +y.
+    fn bump() -> BezierPatch {
+        let mut control_points = [Point::uniform(0f64); 16];
+        for j in 0..4 {
+            for i in 0..4 {
+                let height = if (i == 1 || i == 2) && (j == 1 || j == 2) { 4f64 / 3f64 } else { 0f64 };
+                control_points[4 * j + i] = Point::new(i as f64 / 3f64, height, -(j as f64) / 3f64);
+            }
+        }
+        BezierPatch::new(BicubicBezier { control_points })
+    }
+
+    #[test]
+    fn it_should_intersect_the_middle_of_a_patch() {
+        let r = Ray::half_infinite(Point::new(0.5f64, 5f64, -0.5f64), -Vec3::Y_AXIS);
+        let i = bump().intersect(&r).unwrap();
+        assert!((i.distance - 4.25f64).abs() < 1e-9);
+        assert!((i.uv.0 - 0.5f64).abs() < 1e-9 && (i.uv.1 - 0.5f64).abs() < 1e-9);
+        assert!(i.geometry.normal.as_normalized().y > 1f64 - 1e-9);
+    }
+
+    #[test]
+    fn it_should_agree_with_the_patch_off_center() {
+        let patch = bump();
+        let expected = patch.patch.at(0.2f64, 0.7f64);
+        let r = Ray::half_infinite(Point::new(expected.x, 5f64, expected.z), -Vec3::Y_AXIS);
+        let i = patch.intersect(&r).unwrap();
+        assert!((i.location - expected).magnitude() < 1e-9);
+    }
+
+    #[test]
+    fn it_should_miss_beside_the_patch() {
+        let r = Ray::half_infinite(Point::new(1.5f64, 5f64, -0.5f64), -Vec3::Y_AXIS);
+        assert!(bump().intersect(&r).is_none());
+    }
+
+    #[test]
+    fn it_should_find_the_nearer_of_two_crossings() {
+        // A ray skimming sideways under the bump's peak crosses it twice.
+        let r = Ray::half_infinite(Point::new(-1f64, 0.5f64, -0.5f64), Vec3::X_AXIS);
+        let i = bump().intersect(&r).unwrap();
+        assert!(i.location.x < 0.5f64);
+        assert!((i.location.y - 0.5f64).abs() < 1e-9);
+        assert!(i.geometry.normal.x < 0f64);
+    }
+}
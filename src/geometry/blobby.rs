@@ -0,0 +1,210 @@
+use core::*;
+use math::*;
+
+#[derive(Debug, Clone)]
+pub struct Metaball {
+    pub center: Point,
+    // How far the ball's influence reaches; it contributes nothing beyond this.
+    pub radius: f64,
+    // Negative weights carve blobs away instead.
+    pub weight: f64,
+}
+
+// Wyvill-style soft objects: each ball contributes weight * (1 - (d / radius)^2)^3 to a field, and the
+// surface is wherever the field equals the threshold. The falloff has compact support, so along any ray
+// the field is a sum over only the balls the ray passes through, which is what bounds the root finding.
+#[derive(Debug)]
+pub struct Blobby {
+    balls: Vec<Metaball>,
+    threshold: f64,
+}
+
+// Samples per ball radius when looking for sign changes. Features smaller than this can be missed.
+const SAMPLES_PER_RADIUS: f64 = 16f64;
+const BISECTION_STEPS: usize = 48;
+
+impl Blobby {
+    pub fn new(balls: Vec<Metaball>, threshold: f64) -> Blobby {
+        assert!(threshold > 0f64, "blobby threshold must be positive");
+        assert!(balls.iter().any(|b| b.weight > 0f64), "blobby requires at least one positively-weighted ball");
+        Blobby { balls, threshold }
+    }
+
+    fn field(&self, p: Point) -> f64 {
+        self.balls.iter().map(|b| {
+            let s2 = (p - b.center).magnitude2() / (b.radius * b.radius);
+            if s2 < 1f64 { b.weight * (1f64 - s2).powi(3) } else { 0f64 }
+        }).sum()
+    }
+
+    // Points into the blob, since the field increases towards the centers.
+    fn gradient(&self, p: Point) -> Vec3 {
+        self.balls.iter().fold(Vec3::uniform(0f64), |sum, b| {
+            let offset = p - b.center;
+            let r2 = b.radius * b.radius;
+            let s2 = offset.magnitude2() / r2;
+            if s2 < 1f64 {
+                sum + offset * (-6f64 * b.weight * (1f64 - s2).powi(2) / r2)
+            } else {
+                sum
+            }
+        })
+    }
+
+    // The spans of the ray's line inside each ball's sphere of influence, merged where they overlap.
+    fn spans(&self, ray: &Ray) -> Vec<(f64, f64, f64)> {
+        let mut spans: Vec<(f64, f64, f64)> = self.balls.iter().filter_map(|b| {
+            let offset = ray.origin - b.center;
+            let (a, half_b, c) = (ray.direction.magnitude2(), ray.direction.dot(&offset), offset.magnitude2() - b.radius * b.radius);
+            quadratic(a, 2f64 * half_b, c).map(|(t0, t1)| (t0.max(ray.t_min), t1.min(ray.t_max), b.radius))
+        }).filter(|&(t0, t1, _)| t0 < t1).collect();
+        spans.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        let mut merged: Vec<(f64, f64, f64)> = vec![];
+        for (t0, t1, radius) in spans {
+            match merged.last_mut() {
+                Some(last) if t0 <= last.1 => {
+                    last.1 = last.1.max(t1);
+                    last.2 = last.2.min(radius);
+                }
+                _ => merged.push((t0, t1, radius)),
+            }
+        }
+        merged
+    }
+
+    fn get_intersection(&self, t: f64, ray: &Ray) -> Intersection {
+        let location = ray.at(t);
+        let gradient = self.gradient(location);
+        let normal = if gradient.magnitude2() > 0f64 { -gradient } else { -ray.direction };
+        // Map UVs like a sphere around whichever ball contributes the most here.
+        let strongest = self.balls.iter().max_by(|a, b| {
+            let contribution = |ball: &Metaball| ball.weight * (1f64 - (location - ball.center).magnitude2() / (ball.radius * ball.radius)).max(0f64).powi(3);
+            contribution(a).partial_cmp(&contribution(b)).unwrap()
+        }).unwrap();
+        let from_center = location - strongest.center;
+        let uv = if from_center.magnitude2() > 0f64 {
+            sphere_uv_for_normalized_point(from_center.as_normalized().into_point())
+        } else {
+            Uv(0f64, 0f64)
+        };
+
+        Intersection {
+            distance: t,
+            location,
+            geometry: IntersectionGeometry::from_normal(normal),
+            shading_geometry: None,
+            uv,
+            material: None,
+        }
+    }
+}
+
+impl Geometry for Blobby {
+    fn bound(&self) -> BoundingBox {
+        // Negative balls only ever take away, so they can't extend the surface.
+        self.balls.iter().filter(|b| b.weight > 0f64).fold(BoundingBox::empty(), |bound, b| {
+            bound
+                .with_point(&(b.center - Vec3::uniform(b.radius)))
+                .with_point(&(b.center + Vec3::uniform(b.radius)))
+        })
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+        let speed = ray.direction.magnitude();
+        let value = |t: f64| self.field(ray.origin + ray.direction * t) - self.threshold;
+        // Rays leaving the surface start within rounding error of it and would otherwise find it again.
+        let t_start = ray.t_min + 1e-7 / speed;
+
+        for (t0, t1, radius) in self.spans(ray) {
+            let t0 = t0.max(t_start);
+            if t0 >= t1 {
+                continue;
+            }
+            let steps = (((t1 - t0) * speed / radius) * SAMPLES_PER_RADIUS).ceil().max(1f64) as usize;
+            let dt = (t1 - t0) / steps as f64;
+            let (mut previous_t, mut previous) = (t0, value(t0));
+            for i in 1..(steps + 1) {
+                let t = if i == steps { t1 } else { t0 + dt * i as f64 };
+                let current = value(t);
+                if (previous < 0f64) != (current < 0f64) {
+                    // Bracketed a crossing; bisect it down.
+                    let (mut low, mut high, low_is_negative) = (previous_t, t, previous < 0f64);
+                    for _ in 0..BISECTION_STEPS {
+                        let middle = (low + high) / 2f64;
+                        if (value(middle) < 0f64) == low_is_negative {
+                            low = middle;
+                        } else {
+                            high = middle;
+                        }
+                    }
+                    return Some(self.get_intersection(high, ray));
+                }
+                previous_t = t;
+                previous = current;
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    // With a single ball, the surface is a sphere where (1 - s^2)^3 = threshold.
+    fn single_ball() -> Blobby {
+        Blobby::new(vec![Metaball { center: Point::new(0f64, 0f64, 0f64), radius: 2f64, weight: 1f64 }], 0.125f64)
+    }
+
+    #[test]
+    fn it_should_intersect_a_single_ball_like_a_sphere() {
+        // (1 - s^2)^3 = 1/8 => s^2 = 1/2 => d = sqrt(2).
+        let r = Ray::half_infinite(Point::new(0f64, 0f64, -5f64), Vec3::Z_AXIS);
+        let i = single_ball().intersect(&r).unwrap();
+        assert!((i.distance - (5f64 - 2f64.sqrt())).abs() < 1e-9);
+        assert!(i.geometry.normal.as_normalized().z < -1f64 + 1e-9);
+    }
+
+    #[test]
+    fn it_should_exit_from_inside() {
+        let r = Ray::half_infinite(Point::new(0f64, 0f64, 0f64), Vec3::X_AXIS);
+        let i = single_ball().intersect(&r).unwrap();
+        assert!((i.distance - 2f64.sqrt()).abs() < 1e-9);
+        assert!(i.geometry.normal.as_normalized().x > 1f64 - 1e-9);
+    }
+
+    #[test]
+    fn it_should_join_nearby_balls() {
+        let balls = vec![
+            Metaball { center: Point::new(-0.75f64, 0f64, 0f64), radius: 1f64, weight: 1f64 },
+            Metaball { center: Point::new(0.75f64, 0f64, 0f64), radius: 1f64, weight: 1f64 },
+        ];
+        let blob = Blobby::new(balls, 0.125f64);
+        // Neither ball reaches the threshold at the midpoint on its own, but together they do.
+        let r = Ray::half_infinite(Point::new(0f64, 5f64, 0f64), -Vec3::Y_AXIS);
+        assert!(blob.intersect(&r).is_some());
+    }
+
+    #[test]
+    fn it_should_be_carved_by_negative_balls() {
+        let balls = vec![
+            Metaball { center: Point::new(0f64, 0f64, 0f64), radius: 2f64, weight: 1f64 },
+            Metaball { center: Point::new(0f64, 0f64, -1.5f64), radius: 1f64, weight: -1f64 },
+        ];
+        let blob = Blobby::new(balls, 0.125f64);
+        let r = Ray::half_infinite(Point::new(0f64, 0f64, -5f64), Vec3::Z_AXIS);
+        assert!(blob.intersect(&r).unwrap().distance > 5f64 - 2f64.sqrt() + 0.1f64);
+    }
+
+    #[test]
+    fn it_should_work_inside_a_kd_tree() {
+        let blobs: Vec<Shape> = (0..8)
+            .map(|i| Shape::new(Arc::new(single_ball()), Transform::new(Mat4::create_translation(Vec3::new(i as f64 * 5f64, 0f64, 0f64)))))
+            .collect();
+        let tree = VolumeKdTree::from(blobs);
+        let r = Ray::half_infinite(Point::new(15f64, 0f64, -5f64), Vec3::Z_AXIS);
+        assert!((tree.intersect(&r).unwrap().distance - (5f64 - 2f64.sqrt())).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,121 @@
+use core::*;
+use math::*;
+use super::quadric::*;
+
+// pbrt pg. 143. The base (radius `radius`) sits on y = 0 and the apex at y = height.
+#[derive(Debug)]
+pub struct Cone {
+    radius: f64,
+    height: f64,
+    phi_max: f64,
+}
+
+impl Cone {
+    pub fn new(radius: f64, height: f64, phi_max: f64) -> Cone {
+        Cone {
+            radius,
+            height,
+            phi_max: phi_max.max(0f64).min(TWO_PI),
+        }
+    }
+}
+
+impl Quadric for Cone {
+    fn solve(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let (d, o) = (&ray.direction, &ray.origin);
+        let k = (self.radius / self.height) * (self.radius / self.height);
+        let oy = o.y - self.height;
+        let a = d.x * d.x + d.z * d.z - k * d.y * d.y;
+        let b = 2f64 * (d.x * o.x + d.z * o.z - k * d.y * oy);
+        let c = o.x * o.x + o.z * o.z - k * oy * oy;
+        // Rays parallel to the side cross the double cone once, as the other root has gone off to infinity.
+        // If b is zero too, the ray runs along the surface (or misses it entirely).
+        if a == 0f64 {
+            return if b == 0f64 { None } else { Some((-c / b, f64::INFINITY)) };
+        }
+        quadratic(a, b, c)
+    }
+
+    fn contains(&self, location: &Point) -> bool {
+        // The equation describes a double cone; only keep the nappe between the base and the apex.
+        location.y >= 0f64 && location.y <= self.height && phi_for_point(location) <= self.phi_max
+    }
+
+    fn get_intersection(&self, t: f64, ray: &Ray) -> Intersection {
+        let location = ray.at(t);
+        let phi = phi_for_point(&location);
+        let v = location.y / self.height;
+        // pbrt divides the location by (1 - v) to get the radial part of dp/dv, which blows up at the apex.
+        let v_axis = Vec3::new(-self.radius * phi.cos(), self.height, self.radius * phi.sin());
+        Intersection {
+            distance: t,
+            location,
+            geometry: IntersectionGeometry::new(
+                phi_tangent(phi, (1f64 - v) * self.radius, self.phi_max),
+                v_axis,
+            ),
+            shading_geometry: None,
+            uv: Uv(phi / self.phi_max, v),
+            material: None,
+        }
+    }
+}
+
+impl Geometry for Cone {
+    fn bound(&self) -> BoundingBox {
+        BoundingBox {
+            min: Point::new(-self.radius, 0f64, -self.radius),
+            max: Point::new(self.radius, self.height, self.radius),
+        }
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+        intersect_quadric(self, ray)
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<RayInterval> {
+        quadric_intervals(self, ray)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_cone() -> Cone {
+        Cone::new(1f64, 1f64, TWO_PI)
+    }
+
+    #[test]
+    fn it_should_intersect_the_side_from_outside() {
+        let r = Ray::half_infinite(Point::new(0f64, 0.5f64, -5f64), Vec3::Z_AXIS);
+        let i = unit_cone().intersect(&r).unwrap();
+        assert!((i.distance - 4.5f64).abs() < 1e-9);
+        let normal = i.geometry.normal.as_normalized();
+        assert!(normal.z < 0f64 && normal.y > 0f64);
+        assert!((i.uv.1 - 0.5f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_should_ignore_the_mirrored_nappe_above_the_apex() {
+        let r = Ray::half_infinite(Point::new(0f64, 1.5f64, -5f64), Vec3::Z_AXIS);
+        assert!(unit_cone().intersect(&r).is_none());
+    }
+
+    #[test]
+    fn it_should_intersect_the_apex_along_the_axis() {
+        let r = Ray::half_infinite(Point::new(0f64, 5f64, 0f64), -Vec3::Y_AXIS);
+        let i = unit_cone().intersect(&r).unwrap();
+        assert!((i.distance - 4f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_should_intersect_rays_parallel_to_the_side() {
+        // Parallel to the +x side, so it can only cross the -x side, at (-0.25, 0.75, 0).
+        let r = Ray::half_infinite(Point::new(1.5f64, -1f64, 0f64), Vec3::new(-1f64, 1f64, 0f64).as_normalized());
+        let i = unit_cone().intersect(&r).unwrap();
+        assert!((i.distance - 1.75f64 * 2f64.sqrt()).abs() < 1e-9);
+        assert!((i.location - Point::new(-0.25f64, 0.75f64, 0f64)).magnitude() < 1e-9);
+        assert_eq!(unit_cone().intervals(&r).len(), 1);
+    }
+}
@@ -0,0 +1,470 @@
+use std::sync::Arc;
+use ordered_float::NotNaN;
+
+use core::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    // The first operand, minus all the others.
+    Difference,
+}
+
+#[derive(Debug)]
+pub struct Csg {
+    operation: CsgOperation,
+    operands: Vec<Arc<Geometry>>,
+}
+
+impl Csg {
+    pub fn new(operation: CsgOperation, operands: Vec<Arc<Geometry>>) -> Csg {
+        assert!(operands.len() >= 2, "CSG operations require at least two operands");
+        Csg { operation, operands }
+    }
+
+    pub fn union(operands: Vec<Arc<Geometry>>) -> Csg {
+        Csg::new(CsgOperation::Union, operands)
+    }
+
+    pub fn intersection(operands: Vec<Arc<Geometry>>) -> Csg {
+        Csg::new(CsgOperation::Intersection, operands)
+    }
+
+    pub fn difference(positive: Arc<Geometry>, negatives: Vec<Arc<Geometry>>) -> Csg {
+        let mut operands = vec![positive];
+        operands.extend(negatives);
+        Csg::new(CsgOperation::Difference, operands)
+    }
+
+    fn is_inside(&self, inside_operands: &[bool]) -> bool {
+        match self.operation {
+            CsgOperation::Union => inside_operands.iter().any(|&i| i),
+            CsgOperation::Intersection => inside_operands.iter().all(|&i| i),
+            CsgOperation::Difference => inside_operands[0] && !inside_operands[1..].iter().any(|&i| i),
+        }
+    }
+}
+
+fn invert_intersection_geometry(g: IntersectionGeometry) -> IntersectionGeometry {
+    IntersectionGeometry::new(-g.u_axis, g.v_axis)
+}
+
+pub fn flip_normal(i: Intersection) -> Intersection {
+    Intersection {
+        distance: i.distance,
+        location: i.location,
+        geometry: invert_intersection_geometry(i.geometry),
+        shading_geometry: i.shading_geometry.map(invert_intersection_geometry),
+        uv: i.uv,
+        material: i.material,
+    }
+}
+
+struct Crossing {
+    operand: usize,
+    is_entering: bool,
+    intersection: Intersection,
+}
+
+impl Csg {
+    // Every operand's intervals are merged into a single sorted list of crossings, which we sweep through once
+    // while tracking which operands we're inside of. Crossings that are within EPSILON of each other (e.g.
+    // coplanar faces) are treated as simultaneous, so that touching operands don't leave slivers or seams.
+    fn combine_intervals(&self, operand_intervals: Vec<Vec<RayInterval>>) -> Vec<RayInterval> {
+        const EPSILON: f64 = 1e-9f64;
+
+        let mut inside_operands: Vec<bool> = operand_intervals
+            .iter()
+            .map(|intervals| intervals.first().map_or(false, |i| i.enter.is_none()))
+            .collect();
+
+        let mut crossings: Vec<Crossing> = operand_intervals
+            .into_iter()
+            .enumerate()
+            .flat_map(|(operand, intervals)| {
+                intervals.into_iter().flat_map(move |interval| {
+                    let mut crossings = Vec::with_capacity(2);
+                    if let Some(intersection) = interval.enter {
+                        crossings.push(Crossing { operand, is_entering: true, intersection });
+                    }
+                    if let Some(intersection) = interval.exit {
+                        crossings.push(Crossing { operand, is_entering: false, intersection });
+                    }
+                    crossings.into_iter()
+                })
+            })
+            // Degenerate operands can produce NaN distances, which can't be placed in order.
+            .filter(|crossing: &Crossing| !crossing.intersection.distance.is_nan())
+            .collect();
+        crossings.sort_by_key(|c| NotNaN::new(c.intersection.distance).unwrap());
+
+        let mut intervals = vec![];
+        let mut was_inside = self.is_inside(&inside_operands);
+        let mut open: Option<Option<Intersection>> = if was_inside { Some(None) } else { None };
+
+        let mut remaining = crossings.into_iter().peekable();
+        while let Some(first) = remaining.next() {
+            let group_start = first.intersection.distance;
+            let mut group = vec![first];
+            while remaining.peek().map_or(false, |c| c.intersection.distance - group_start <= EPSILON) {
+                group.push(remaining.next().unwrap());
+            }
+
+            let previous_inside_operands = inside_operands.clone();
+            for crossing in &group {
+                inside_operands[crossing.operand] = crossing.is_entering;
+            }
+            let is_inside = self.is_inside(&inside_operands);
+            if is_inside == was_inside {
+                continue;
+            }
+
+            // Prefer a crossing that would have caused this change on its own, so that e.g. when a subtracted
+            // operand's face is coplanar with the positive operand's, we use the positive operand's face.
+            let responsible_index = group
+                .iter()
+                .position(|crossing| {
+                    let mut alone = previous_inside_operands.clone();
+                    alone[crossing.operand] = crossing.is_entering;
+                    self.is_inside(&alone) == is_inside
+                })
+                .unwrap_or(0);
+            let crossing = group.swap_remove(responsible_index);
+
+            // If the operand's surface faces the opposite way from the combined shape's (e.g. we're entering the
+            // combined shape by leaving a subtracted operand), the normal has to be flipped to match.
+            let boundary = if crossing.is_entering == is_inside {
+                crossing.intersection
+            } else {
+                flip_normal(crossing.intersection)
+            };
+
+            if is_inside {
+                open = Some(Some(boundary));
+            } else {
+                intervals.push(RayInterval { enter: open.take().unwrap(), exit: Some(boundary) });
+            }
+            was_inside = is_inside;
+        }
+
+        if let Some(enter) = open {
+            intervals.push(RayInterval { enter, exit: None });
+        }
+
+        // Tangent rays produce zero-length intervals, which would show up as speckles along silhouettes.
+        intervals.retain(|i| i.end() - i.start() > EPSILON);
+        intervals
+    }
+}
+
+impl Geometry for Csg {
+    fn bound(&self) -> BoundingBox {
+        match self.operation {
+            CsgOperation::Union => self.operands
+                .iter()
+                .fold(BoundingBox::empty(), |bound, operand| BoundingBox::union(&bound, &operand.bound())),
+            CsgOperation::Intersection => self.operands[1..]
+                .iter()
+                .fold(self.operands[0].bound(), |bound, operand| BoundingBox::intersection(&bound, &operand.bound())),
+            CsgOperation::Difference => self.operands[0].bound(),
+        }
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+        self.intervals(ray)
+            .into_iter()
+            .filter(|i| i.end() >= ray.t_min)
+            .next()
+            .and_then(|i| i.first_crossing(ray))
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<RayInterval> {
+        self.combine_intervals(self.operands.iter().map(|operand| operand.intervals(ray)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use math::*;
+    use geometry::{ Sphere, RectPrism, TriangleMeshData, Smoothing };
+
+    fn sphere_at(x: f64) -> Arc<Geometry> {
+        Arc::new(Shape::new(
+            Arc::new(Sphere::new(1f64)),
+            Transform::new(Mat4::create_translation(Vec3::new(x, 0f64, 0f64))),
+        ))
+    }
+
+    fn prism(min: Point, max: Point) -> Arc<Geometry> {
+        Arc::new(RectPrism::new(min, max))
+    }
+
+    fn ray_along_x() -> Ray {
+        Ray::half_infinite(Point::new(-5f64, 0f64, 0f64), Vec3::X_AXIS)
+    }
+
+    fn assert_faces_ray(i: &Intersection, ray: &Ray) {
+        assert!(i.geometry.normal.dot(&ray.direction) < 0f64, "normal {:?} does not face the ray", i.geometry.normal);
+    }
+
+    mod union {
+        use super::*;
+
+        #[test]
+        fn it_should_intersect_the_nearest_operand() {
+            let csg = Csg::union(vec![sphere_at(-0.5f64), sphere_at(0.5f64)]);
+            let i = csg.intersect(&ray_along_x()).unwrap();
+            assert!(i.distance.fuzzy_eq(3.5f64));
+            assert_faces_ray(&i, &ray_along_x());
+        }
+
+        #[test]
+        fn it_should_not_intersect_internal_surfaces_from_inside() {
+            let csg = Csg::union(vec![sphere_at(-0.5f64), sphere_at(0.5f64)]);
+            let r = Ray::half_infinite(Point::new(-0.5f64, 0f64, 0f64), Vec3::X_AXIS);
+            let i = csg.intersect(&r).unwrap();
+            assert!(i.distance.fuzzy_eq(2f64));
+        }
+
+        #[test]
+        fn it_should_respect_t_max_when_starting_inside() {
+            let csg = Csg::union(vec![sphere_at(-0.5f64), sphere_at(0.5f64)]);
+            let r = Ray::finite(Point::new(0f64, 0f64, 0f64), Vec3::X_AXIS, 0f64, 1f64);
+            assert!(csg.intersect(&r).is_none());
+        }
+
+        #[test]
+        fn it_should_merge_operands_with_coplanar_faces() {
+            let csg = Csg::union(vec![
+                prism(Point::new(-1f64, -1f64, -1f64), Point::new(0f64, 1f64, 1f64)),
+                prism(Point::new(0f64, -1f64, -1f64), Point::new(1f64, 1f64, 1f64)),
+            ]);
+            let intervals = csg.intervals(&ray_along_x());
+            assert_eq!(intervals.len(), 1);
+            assert!(intervals[0].start().fuzzy_eq(4f64));
+            assert!(intervals[0].end().fuzzy_eq(6f64));
+        }
+
+        #[test]
+        fn it_should_merge_coincident_operands_despite_floating_point_error() {
+            let cube = Arc::new(RectPrism::new(Point::uniform(-1f64), Point::uniform(1f64)));
+            let rotated_cube = Arc::new(Shape::new(
+                cube.clone(),
+                Transform::new(Mat4::create_rotation(90f64.to_radians(), Vec3::X_AXIS)),
+            ));
+            let csg = Csg::union(vec![cube, rotated_cube]);
+            let r = Ray::half_infinite(Point::new(-5f64, 0.5f64, 0.5f64), Vec3::X_AXIS);
+            assert_eq!(csg.intervals(&r).len(), 1);
+        }
+
+        #[test]
+        fn it_should_ignore_tangent_hits() {
+            let csg = Csg::union(vec![sphere_at(0f64), sphere_at(5f64)]);
+            let r = Ray::half_infinite(Point::new(-5f64, 1f64, 0f64), Vec3::X_AXIS);
+            assert!(csg.intersect(&r).is_none());
+        }
+    }
+
+    mod intersection {
+        use super::*;
+
+        #[test]
+        fn it_should_have_an_empty_bound_for_disjoint_operands() {
+            let csg = Csg::intersection(vec![sphere_at(-2f64), sphere_at(2f64)]);
+            assert!(csg.bound().is_empty());
+            assert!(march_intervals(&csg, &ray_along_x()).is_empty());
+        }
+
+        #[test]
+        fn it_should_intersect_only_the_overlap() {
+            let csg = Csg::intersection(vec![sphere_at(-0.5f64), sphere_at(0.5f64)]);
+            let i = csg.intersect(&ray_along_x()).unwrap();
+            assert!(i.distance.fuzzy_eq(4.5f64));
+            assert_faces_ray(&i, &ray_along_x());
+        }
+
+        #[test]
+        fn it_should_not_intersect_disjoint_operands() {
+            let csg = Csg::intersection(vec![sphere_at(-2f64), sphere_at(2f64)]);
+            assert!(csg.intersect(&ray_along_x()).is_none());
+        }
+
+        #[test]
+        fn it_should_not_intersect_operands_that_only_touch() {
+            let csg = Csg::intersection(vec![sphere_at(-1f64), sphere_at(1f64)]);
+            assert!(csg.intersect(&ray_along_x()).is_none());
+        }
+
+        #[test]
+        fn it_should_keep_coplanar_faces() {
+            let csg = Csg::intersection(vec![
+                prism(Point::uniform(-1f64), Point::uniform(1f64)),
+                prism(Point::new(-1f64, -1f64, 0f64), Point::new(1f64, 1f64, 2f64)),
+            ]);
+            let r = Ray::half_infinite(Point::new(-5f64, 0f64, 0.5f64), Vec3::X_AXIS);
+            let i = csg.intersect(&r).unwrap();
+            assert!(i.distance.fuzzy_eq(4f64));
+            assert_faces_ray(&i, &r);
+
+            let r = Ray::half_infinite(Point::new(0f64, 0f64, -5f64), Vec3::Z_AXIS);
+            let i = csg.intersect(&r).unwrap();
+            assert!(i.distance.fuzzy_eq(5f64));
+            assert_faces_ray(&i, &r);
+        }
+    }
+
+    mod difference {
+        use super::*;
+
+        #[test]
+        fn it_should_flip_the_normal_of_a_subtracted_operand() {
+            let csg = Csg::difference(sphere_at(0f64), vec![sphere_at(-1f64)]);
+            let i = csg.intersect(&ray_along_x()).unwrap();
+            assert!(i.distance.fuzzy_eq(5f64));
+            assert_faces_ray(&i, &ray_along_x());
+        }
+
+        #[test]
+        fn it_should_subtract_every_negative_operand() {
+            let csg = Csg::difference(sphere_at(0f64), vec![sphere_at(-0.9f64), sphere_at(0.9f64)]);
+            assert!(csg.intersect(&ray_along_x()).is_none());
+        }
+
+        #[test]
+        fn it_should_not_leave_a_gap_between_coincident_negative_operands() {
+            let csg = Csg::difference(sphere_at(0f64), vec![sphere_at(-1f64), sphere_at(1f64)]);
+            assert!(csg.intersect(&ray_along_x()).is_none());
+        }
+
+        #[test]
+        fn it_should_not_leave_slivers_on_coplanar_faces() {
+            let csg = Csg::difference(
+                prism(Point::uniform(-1f64), Point::uniform(1f64)),
+                vec![prism(Point::new(0f64, -1f64, -1f64), Point::uniform(1f64))],
+            );
+
+            let r = Ray::half_infinite(Point::new(5f64, 0f64, 0f64), -Vec3::X_AXIS);
+            let i = csg.intersect(&r).unwrap();
+            assert!(i.distance.fuzzy_eq(5f64));
+            assert_faces_ray(&i, &r);
+
+            let r = Ray::half_infinite(Point::new(0.5f64, -5f64, 1f64), Vec3::Y_AXIS);
+            assert!(csg.intersect(&r).is_none());
+        }
+
+        #[test]
+        fn it_should_intersect_from_inside() {
+            let csg = Csg::difference(sphere_at(0f64), vec![sphere_at(1.5f64)]);
+            let r = Ray::half_infinite(Point::uniform(0f64), Vec3::X_AXIS);
+            let i = csg.intersect(&r).unwrap();
+            assert!(i.distance.fuzzy_eq(0.5f64));
+            assert!(i.geometry.normal.dot(&r.direction) > 0f64);
+        }
+
+        #[test]
+        fn it_should_treat_an_open_surface_as_a_half_space() {
+            // A single triangle facing +x, which should cut away everything behind it (x < 0).
+            let sheet = TriangleMeshData::new(
+                vec![Point::new(0f64, -10f64, -10f64), Point::new(0f64, 10f64, -10f64), Point::new(0f64, 0f64, 10f64)],
+                Smoothing::None,
+                None,
+                vec![(0, 1, 2)],
+            ).into_triangle_mesh();
+            let csg = Csg::difference(sphere_at(0f64), vec![Arc::new(sheet)]);
+
+            let i = csg.intersect(&ray_along_x()).unwrap();
+            assert!(i.distance.fuzzy_eq(5f64));
+            assert_faces_ray(&i, &ray_along_x());
+
+            let r = Ray::half_infinite(Point::new(5f64, 0f64, 0f64), -Vec3::X_AXIS);
+            let intervals = csg.intervals(&r);
+            assert_eq!(intervals.len(), 1);
+            assert!(intervals[0].start().fuzzy_eq(4f64));
+            assert!(intervals[0].end().fuzzy_eq(5f64));
+        }
+    }
+
+    mod nested {
+        use super::*;
+
+        // Two overlapping spheres spanning -1.5 to 1.5 along the x axis, minus a slab from -0.2 to 0.2.
+        fn nested_csg() -> Csg {
+            let slab = Csg::intersection(vec![
+                prism(Point::new(-0.2f64, -2f64, -2f64), Point::uniform(5f64)),
+                prism(Point::uniform(-5f64), Point::new(0.2f64, 2f64, 2f64)),
+            ]);
+            Csg::difference(
+                Arc::new(Csg::union(vec![sphere_at(-0.5f64), sphere_at(0.5f64)])),
+                vec![Arc::new(slab)],
+            )
+        }
+
+        #[test]
+        fn it_should_produce_every_interval() {
+            let intervals = nested_csg().intervals(&ray_along_x());
+            assert_eq!(intervals.len(), 2);
+            assert!(intervals[0].start().fuzzy_eq(3.5f64));
+            assert!(intervals[0].end().fuzzy_eq(4.8f64));
+            assert!(intervals[1].start().fuzzy_eq(5.2f64));
+            assert!(intervals[1].end().fuzzy_eq(6.5f64));
+        }
+
+        #[test]
+        fn it_should_intersect_the_far_side_of_a_hole() {
+            let r = Ray::half_infinite(Point::new(-0.1f64, 0f64, 0f64), Vec3::X_AXIS);
+            let i = nested_csg().intersect(&r).unwrap();
+            assert!(i.distance.fuzzy_eq(0.3f64));
+            assert_faces_ray(&i, &r);
+        }
+
+        #[test]
+        fn it_should_match_intervals_found_by_marching() {
+            let csg = nested_csg();
+            let r = Ray::half_infinite(Point::new(-5f64, 0.3f64, 0.1f64), Vec3::new(1f64, 0.05f64, 0f64).into_normalized());
+            let analytic = csg.intervals(&r);
+            let marched = march_intervals(&csg, &r);
+            assert_eq!(analytic.len(), marched.len());
+            for (a, m) in analytic.iter().zip(marched.iter()) {
+                assert!((a.start() - m.start()).abs() < 1e-6f64);
+                assert!((a.end() - m.end()).abs() < 1e-6f64);
+            }
+        }
+    }
+
+    // Stands in for a degenerate operand, with one crossing at a NaN distance.
+    #[derive(Debug)]
+    struct NanCrossing;
+
+    impl Geometry for NanCrossing {
+        fn bound(&self) -> BoundingBox {
+            BoundingBox { min: Point::uniform(-1f64), max: Point::uniform(1f64) }
+        }
+
+        fn intersect(&self, _ray: &Ray) -> Option<Intersection> {
+            None
+        }
+
+        fn intervals(&self, _ray: &Ray) -> Vec<RayInterval> {
+            vec![RayInterval {
+                enter: Some(Intersection {
+                    distance: f64::NAN,
+                    location: Point::uniform(0f64),
+                    geometry: IntersectionGeometry::new(Vec3::X_AXIS, Vec3::Y_AXIS),
+                    shading_geometry: None,
+                    uv: Uv(0f64, 0f64),
+                    material: None,
+                }),
+                exit: None,
+            }]
+        }
+    }
+
+    #[test]
+    fn it_should_ignore_nan_crossings() {
+        let csg = Csg::union(vec![sphere_at(0f64), Arc::new(NanCrossing)]);
+        let i = csg.intersect(&ray_along_x()).unwrap();
+        assert!(i.distance.fuzzy_eq(4f64));
+    }
+}
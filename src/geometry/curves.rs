@@ -0,0 +1,297 @@
+use std::sync::Arc;
+use core::*;
+use math::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CurveType {
+    // A ribbon that always faces the ray, like a blade of grass seen from wherever you're looking.
+    Flat,
+    // Also a ray-facing ribbon, but shaded with normals that curve around as if it were a tube, like hair.
+    Cylinder,
+}
+
+#[derive(Debug)]
+struct Strand {
+    curves: Vec<CubicBezier>,
+    // One per joint between curves, so there's one more than there are curves.
+    widths: Vec<f64>,
+    curve_type: CurveType,
+}
+
+// A piece of one of a strand's beziers. Strands are split up this way so their pieces can have tight bounds
+// in the kd-tree, rather than one loose box around a whole hair.
+//
+// This is pbrt's curve (pg. 167): in a coordinate system where the ray runs down +z from the origin, the
+// piece is halved until its halves are nearly straight, and then the ray hits if it passes within the
+// curve's width of the straight line between the ends.
+#[derive(Debug)]
+pub struct CurveSegment {
+    strand: Arc<Strand>,
+    index: usize,
+    piece: CubicBezier,
+    // Where the piece is along its bezier.
+    t_range: (f64, f64),
+}
+
+pub type Curves = VolumeKdTree<CurveSegment>;
+
+const MAX_DEPTH: usize = 10;
+
+// How far a piece's chords may stray from it, relative to its width.
+const CHORD_TOLERANCE: f64 = 0.05f64;
+
+// Splits a strand of 3n + 1 bezier control points, with n + 1 widths at its joints, into 2^split_depth pieces
+// per bezier.
+pub fn create_curve_segments(points: &[Point], widths: Vec<f64>, curve_type: CurveType, split_depth: usize) -> Vec<CurveSegment> {
+    let curves = CubicBezier::chain_from_points(points);
+    assert_eq!(widths.len(), curves.len() + 1, "curve strands need a width at every joint");
+    assert!(widths.iter().all(|&width| width > 0f64), "curve widths must be positive");
+    let strand = Arc::new(Strand { curves, widths, curve_type });
+
+    let mut segments = vec![];
+    for (index, curve) in strand.curves.iter().enumerate() {
+        let mut pieces = vec![(*curve, (0f64, 1f64))];
+        for _ in 0..split_depth {
+            pieces = pieces
+                .into_iter()
+                .flat_map(|(piece, (t0, t1))| {
+                    let (first, second) = piece.split();
+                    let middle = (t0 + t1) / 2f64;
+                    vec![(first, (t0, middle)), (second, (middle, t1))]
+                })
+                .collect();
+        }
+        segments.extend(pieces.into_iter().map(|(piece, t_range)| CurveSegment {
+            strand: Arc::clone(&strand),
+            index,
+            piece,
+            t_range,
+        }));
+    }
+    segments
+}
+
+#[derive(Clone, Copy)]
+struct Hit {
+    distance: f64,
+    t: f64,
+    // From the curve to the ray, in ray space.
+    offset: (f64, f64),
+    width: f64,
+}
+
+fn bezier_points(curve: &CubicBezier) -> [Point; 4] {
+    [curve.p0, curve.p1, curve.p2, curve.p3]
+}
+
+impl CurveSegment {
+    fn width_at(&self, t: f64) -> f64 {
+        let (w0, w1) = (self.strand.widths[self.index], self.strand.widths[self.index + 1]);
+        w0 + (w1 - w0) * t
+    }
+
+    fn max_width(&self) -> f64 {
+        self.width_at(self.t_range.0).max(self.width_at(self.t_range.1))
+    }
+
+    // z is in ray space, where it's the distance along the normalized ray.
+    fn recursive_intersect(&self, piece: &CubicBezier, (t0, t1): (f64, f64), depth: usize, (z_min, z_max): (f64, f64), closest: &mut Option<Hit>) {
+        let z_max = closest.as_ref().map_or(z_max, |hit| hit.distance);
+        let half_width = self.width_at(t0).max(self.width_at(t1)) / 2f64;
+        let bound = bezier_points(piece).iter().fold(BoundingBox::empty(), |bound, p| bound.with_point(p));
+        if bound.min.x - half_width > 0f64 || bound.max.x + half_width < 0f64
+            || bound.min.y - half_width > 0f64 || bound.max.y + half_width < 0f64
+            || bound.min.z - half_width > z_max || bound.max.z + half_width < z_min {
+            return;
+        }
+
+        if depth > 0 {
+            let (first, second) = piece.split();
+            let middle = (t0 + t1) / 2f64;
+            self.recursive_intersect(&first, (t0, middle), depth - 1, (z_min, z_max), closest);
+            self.recursive_intersect(&second, (middle, t1), depth - 1, (z_min, z_max), closest);
+            return;
+        }
+
+        let [p0, p1, p2, p3] = bezier_points(piece);
+        // The ray has to pass between the planes perpendicular to the curve at either end...
+        if (p1.y - p0.y) * -p0.y + p0.x * (p0.x - p1.x) < 0f64 || (p2.y - p3.y) * -p3.y + p3.x * (p3.x - p2.x) < 0f64 {
+            return;
+        }
+        // ...and close enough to the line between them.
+        let (dx, dy) = (p3.x - p0.x, p3.y - p0.y);
+        let denominator = dx * dx + dy * dy;
+        if denominator == 0f64 {
+            return;
+        }
+        let w = ((-p0.x * dx - p0.y * dy) / denominator).max(0f64).min(1f64);
+        let t = t0 + (t1 - t0) * w;
+        let width = self.width_at(t);
+        let center = piece.at(w);
+        let distance2 = center.x * center.x + center.y * center.y;
+        if distance2 > width * width / 4f64 || center.z < z_min || center.z > z_max {
+            return;
+        }
+        // Rays that start inside the curve are leaving it, like shadow rays cast from it, so they can't hit
+        // it. The origin is measured against the chord, so there's room for the 5% of the width that this
+        // ray's and the earlier ray's chords can each stray from the curve.
+        let chord = p3 - p0;
+        let along = ((-p0.into_vector()).dot(&chord) / chord.magnitude2()).max(0f64).min(1f64);
+        let origin_distance = (p0 + chord * along).into_vector().magnitude();
+        if origin_distance <= width * (0.5f64 + 2f64 * CHORD_TOLERANCE) {
+            return;
+        }
+        *closest = Some(Hit { distance: center.z, t, offset: (-center.x, -center.y), width });
+    }
+}
+
+// Any two directions perpendicular to each other and a normalized vector (pbrt pg. 67).
+fn coordinate_system(v: &Vec3) -> (Vec3, Vec3) {
+    let a = if v.x.abs() > v.y.abs() {
+        Vec3::new(-v.z, 0f64, v.x) / (v.x * v.x + v.z * v.z).sqrt()
+    } else {
+        Vec3::new(0f64, v.z, -v.y) / (v.y * v.y + v.z * v.z).sqrt()
+    };
+    let b = v.cross(a);
+    (a, b)
+}
+
+impl Geometry for CurveSegment {
+    fn bound(&self) -> BoundingBox {
+        let half_width = Vec3::uniform(self.max_width() / 2f64);
+        let bound = bezier_points(&self.piece).iter().fold(BoundingBox::empty(), |bound, p| bound.with_point(p));
+        BoundingBox { min: bound.min - half_width, max: bound.max + half_width }
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+        let length = ray.direction.magnitude();
+        let direction = ray.direction / length;
+        let (a, b) = coordinate_system(&direction);
+        let to_ray_space = |p: Point| {
+            let p = p - ray.origin;
+            Point::new(p.dot(&a), p.dot(&b), p.dot(&direction))
+        };
+        let piece = CubicBezier {
+            p0: to_ray_space(self.piece.p0),
+            p1: to_ray_space(self.piece.p1),
+            p2: to_ray_space(self.piece.p2),
+            p3: to_ray_space(self.piece.p3),
+        };
+
+        // Enough halvings to make the piece's chords stray less than 5% of its width from it (pbrt pg. 172).
+        let [p0, p1, p2, p3] = bezier_points(&piece);
+        let second_difference = |a: Point, b: Point, c: Point| {
+            let d = a - b * 2f64 + c.into_vector();
+            d.x.abs().max(d.y.abs()).max(d.z.abs())
+        };
+        let l0 = second_difference(p0, p1, p2).max(second_difference(p1, p2, p3));
+        let epsilon = self.max_width() * CHORD_TOLERANCE;
+        let depth = if l0 > 0f64 {
+            ((2f64.sqrt() * 6f64 * l0 / (8f64 * epsilon)).log2() / 2f64).round().max(0f64).min(MAX_DEPTH as f64) as usize
+        } else {
+            0
+        };
+
+        let mut closest = None;
+        self.recursive_intersect(&piece, self.t_range, depth, (ray.t_min * length, ray.t_max * length), &mut closest);
+        closest.map(|Hit { distance, t, offset, width }| {
+            let curve = &self.strand.curves[self.index];
+            let count = self.strand.curves.len() as f64;
+            let tangent = {
+                let derivative = curve.derivative(t);
+                if derivative.magnitude2() > 0f64 { derivative } else { curve.p3 - curve.p0 }
+            };
+            let u_axis = tangent * count;
+
+            // Across the ribbon, so that u cross v faces back along the ray.
+            let across = {
+                let across = tangent.cross(direction);
+                if across.magnitude2() > 0f64 { across.into_normalized() } else { a }
+            };
+            let signed_offset = (a * offset.0 + b * offset.1).dot(&across);
+            let v = (0.5f64 + signed_offset / width).max(0f64).min(1f64);
+            let v_axis = match self.strand.curve_type {
+                CurveType::Flat => across * width,
+                CurveType::Cylinder => {
+                    // Tip v around the tangent so the normal turns from facing the ray in the middle to
+                    // pointing sideways at the edges.
+                    let facing = tangent.cross(across).into_normalized();
+                    let sin = 2f64 * v - 1f64;
+                    let cos = (1f64 - sin * sin).max(0f64).sqrt();
+                    (across * cos - facing * sin) * width
+                }
+            };
+
+            Intersection {
+                distance: distance / length,
+                location: ray.origin + ray.direction * (distance / length),
+                geometry: IntersectionGeometry::new(u_axis, v_axis),
+                shading_geometry: None,
+                uv: Uv((self.index as f64 + t) / count, v),
+                material: None,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A straight strand along +x from the origin, thinning from 0.2 to 0.1 wide.
+    fn strand(curve_type: CurveType) -> Curves {
+        let points = [Point::new(0f64, 0f64, 0f64), Point::new(1f64, 0f64, 0f64), Point::new(2f64, 0f64, 0f64), Point::new(3f64, 0f64, 0f64)];
+        VolumeKdTree::from(create_curve_segments(&points, vec![0.2f64, 0.1f64], curve_type, 2))
+    }
+
+    #[test]
+    fn it_should_hit_within_the_width() {
+        let curves = strand(CurveType::Flat);
+        let r = Ray::half_infinite(Point::new(1.5f64, 0.07f64, 5f64), -Vec3::Z_AXIS);
+        let i = curves.intersect(&r).unwrap();
+        assert!((i.distance - 5f64).abs() < 1e-9);
+        assert!((i.uv.0 - 0.5f64).abs() < 1e-9);
+        assert!((i.uv.1 - (0.5f64 + 0.07f64 / 0.15f64)).abs() < 1e-9);
+        // Flat curves face the ray.
+        assert!(i.geometry.normal.as_normalized().z > 1f64 - 1e-9);
+
+        let r = Ray::half_infinite(Point::new(1.5f64, 0.08f64, 5f64), -Vec3::Z_AXIS);
+        assert!(curves.intersect(&r).is_none());
+    }
+
+    #[test]
+    fn it_should_face_any_ray() {
+        let curves = strand(CurveType::Flat);
+        let direction = Vec3::new(0f64, -1f64, -1f64).as_normalized();
+        let r = Ray::half_infinite(Point::new(0.5f64, 4f64, 4f64), direction);
+        let i = curves.intersect(&r).unwrap();
+        assert!((i.location - Point::new(0.5f64, 0f64, 0f64)).magnitude() < 1e-9);
+        assert!(i.geometry.normal.as_normalized().dot(&-direction) > 1f64 - 1e-9);
+    }
+
+    #[test]
+    fn it_should_curve_cylinder_normals_around() {
+        let curves = strand(CurveType::Cylinder);
+        let r = Ray::half_infinite(Point::new(1.5f64, 0f64, 5f64), -Vec3::Z_AXIS);
+        assert!(curves.intersect(&r).unwrap().geometry.normal.as_normalized().z > 1f64 - 1e-9);
+        let r = Ray::half_infinite(Point::new(1.5f64, -0.07f64, 5f64), -Vec3::Z_AXIS);
+        let normal = curves.intersect(&r).unwrap().geometry.normal.as_normalized();
+        assert!(normal.y < -0.5f64 && normal.z > 0f64);
+    }
+
+    #[test]
+    fn it_should_not_hit_itself() {
+        let curves = strand(CurveType::Flat);
+        let r = Ray::half_infinite(Point::new(1.5f64, 0.01f64, 0f64), Vec3::new(0f64, 1f64, 1f64).as_normalized()).with_min(EPSILON);
+        assert!(curves.intersect(&r).is_none());
+    }
+
+    #[test]
+    fn it_should_be_hit_from_just_outside() {
+        // Closer than the strand is wide, like a shadow ray from the surface it's growing out of.
+        let curves = strand(CurveType::Flat);
+        let r = Ray::half_infinite(Point::new(1.5f64, 0f64, -0.1f64), Vec3::Z_AXIS).with_min(EPSILON);
+        let i = curves.intersect(&r).unwrap();
+        assert!((i.distance - 0.1f64).abs() < 1e-9);
+    }
+}
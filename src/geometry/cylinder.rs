@@ -0,0 +1,119 @@
+use core::*;
+use math::*;
+use super::quadric::*;
+
+// pbrt pg. 142
+#[derive(Debug)]
+pub struct Cylinder {
+    radius: f64,
+    y_min: f64,
+    y_max: f64,
+    phi_max: f64,
+}
+
+impl Cylinder {
+    pub fn new(radius: f64, y_min: f64, y_max: f64, phi_max: f64) -> Cylinder {
+        Cylinder {
+            radius,
+            y_min: y_min.min(y_max),
+            y_max: y_min.max(y_max),
+            phi_max: phi_max.max(0f64).min(TWO_PI),
+        }
+    }
+}
+
+impl Quadric for Cylinder {
+    fn solve(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let (d, o) = (&ray.direction, &ray.origin);
+        let a = d.x * d.x + d.z * d.z;
+        // Rays parallel to the axis never cross the walls.
+        if a == 0f64 {
+            return None;
+        }
+        let b = 2f64 * (d.x * o.x + d.z * o.z);
+        let c = o.x * o.x + o.z * o.z - self.radius * self.radius;
+        quadratic(a, b, c)
+    }
+
+    fn contains(&self, location: &Point) -> bool {
+        location.y >= self.y_min && location.y <= self.y_max && phi_for_point(location) <= self.phi_max
+    }
+
+    fn get_intersection(&self, t: f64, ray: &Ray) -> Intersection {
+        let location = ray.at(t);
+        let phi = phi_for_point(&location);
+        let height = self.y_max - self.y_min;
+        Intersection {
+            distance: t,
+            location,
+            geometry: IntersectionGeometry::new(
+                phi_tangent(phi, self.radius, self.phi_max),
+                Vec3::new(0f64, height, 0f64),
+            ),
+            shading_geometry: None,
+            uv: Uv(phi / self.phi_max, (location.y - self.y_min) / height),
+            material: None,
+        }
+    }
+}
+
+impl Geometry for Cylinder {
+    fn bound(&self) -> BoundingBox {
+        BoundingBox {
+            min: Point::new(-self.radius, self.y_min, -self.radius),
+            max: Point::new(self.radius, self.y_max, self.radius),
+        }
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+        intersect_quadric(self, ray)
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<RayInterval> {
+        quadric_intervals(self, ray)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_cylinder() -> Cylinder {
+        Cylinder::new(1f64, -1f64, 1f64, TWO_PI)
+    }
+
+    #[test]
+    fn it_should_intersect_the_wall_from_outside() {
+        let r = Ray::half_infinite(Point::new(0f64, 0.5f64, -5f64), Vec3::Z_AXIS);
+        let i = unit_cylinder().intersect(&r).unwrap();
+        assert_eq!(i.distance, 4f64);
+        assert!(i.geometry.normal.as_normalized().dot(&Vec3::new(0f64, 0f64, -1f64)) > 1f64 - 1e-9);
+        assert!((i.uv.1 - 0.75f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_should_intersect_the_far_wall_from_inside() {
+        let r = Ray::half_infinite(Point::new(0f64, 0f64, 0f64), Vec3::Z_AXIS);
+        assert_eq!(unit_cylinder().intersect(&r).unwrap().distance, 1f64);
+    }
+
+    #[test]
+    fn it_should_not_intersect_beyond_the_y_range() {
+        let r = Ray::half_infinite(Point::new(0f64, 2f64, -5f64), Vec3::Z_AXIS);
+        assert!(unit_cylinder().intersect(&r).is_none());
+    }
+
+    #[test]
+    fn it_should_see_through_the_open_ends() {
+        let r = Ray::half_infinite(Point::new(0f64, 5f64, 0f64), -Vec3::Y_AXIS);
+        assert!(unit_cylinder().intersect(&r).is_none());
+    }
+
+    #[test]
+    fn it_should_hit_the_far_wall_past_the_swept_angle() {
+        // Half a cylinder spans phi in [0, PI], i.e. the -z side; the +z side is missing.
+        let half = Cylinder::new(1f64, -1f64, 1f64, TWO_PI / 2f64);
+        let r = Ray::half_infinite(Point::new(0f64, 0f64, 5f64), -Vec3::Z_AXIS);
+        assert_eq!(half.intersect(&r).unwrap().distance, 6f64);
+    }
+}
@@ -0,0 +1,96 @@
+use core::*;
+use math::*;
+use super::quadric::*;
+
+// pbrt pg. 146. A disk (or, with an inner radius, an annulus) in the plane y = height, facing +y.
+#[derive(Debug)]
+pub struct Disk {
+    height: f64,
+    radius: f64,
+    inner_radius: f64,
+    phi_max: f64,
+}
+
+impl Disk {
+    pub fn new(height: f64, radius: f64, inner_radius: f64, phi_max: f64) -> Disk {
+        Disk {
+            height,
+            radius,
+            inner_radius: inner_radius.max(0f64).min(radius),
+            phi_max: phi_max.max(0f64).min(TWO_PI),
+        }
+    }
+}
+
+impl Geometry for Disk {
+    fn bound(&self) -> BoundingBox {
+        BoundingBox {
+            min: Point::new(-self.radius, self.height, -self.radius),
+            max: Point::new(self.radius, self.height, self.radius),
+        }
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+        if ray.direction.y == 0f64 {
+            return None;
+        }
+        let t = (self.height - ray.origin.y) / ray.direction.y;
+        if t < ray.t_min || t > ray.t_max {
+            return None;
+        }
+
+        let location = ray.at(t);
+        let distance2 = location.x * location.x + location.z * location.z;
+        if distance2 > self.radius * self.radius || distance2 < self.inner_radius * self.inner_radius {
+            return None;
+        }
+        let phi = phi_for_point(&location);
+        if phi > self.phi_max {
+            return None;
+        }
+
+        let distance = distance2.sqrt();
+        // Like pbrt, v runs inward from the outer edge. Both axes are written in terms of phi so that
+        // they're still meaningful at the center.
+        Some(Intersection {
+            distance: t,
+            location,
+            geometry: IntersectionGeometry::new(
+                phi_tangent(phi, distance, self.phi_max),
+                Vec3::new(phi.cos(), 0f64, -phi.sin()) * (self.inner_radius - self.radius),
+            ),
+            shading_geometry: None,
+            uv: Uv(phi / self.phi_max, (self.radius - distance) / (self.radius - self.inner_radius)),
+            material: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_intersect_from_above_facing_up() {
+        let d = Disk::new(1f64, 2f64, 0f64, TWO_PI);
+        let r = Ray::half_infinite(Point::new(0.5f64, 5f64, 0f64), -Vec3::Y_AXIS);
+        let i = d.intersect(&r).unwrap();
+        assert_eq!(i.distance, 4f64);
+        assert!(i.geometry.normal.as_normalized().y > 1f64 - 1e-9);
+        assert!((i.uv.1 - 0.75f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_should_have_a_usable_normal_at_the_center() {
+        let d = Disk::new(0f64, 1f64, 0f64, TWO_PI);
+        let r = Ray::half_infinite(Point::new(0f64, 5f64, 0f64), -Vec3::Y_AXIS);
+        assert!(d.intersect(&r).unwrap().geometry.normal.as_normalized().y > 1f64 - 1e-9);
+    }
+
+    #[test]
+    fn it_should_not_intersect_inside_the_inner_radius() {
+        let d = Disk::new(0f64, 2f64, 1f64, TWO_PI);
+        let r = Ray::half_infinite(Point::new(0.5f64, 5f64, 0f64), -Vec3::Y_AXIS);
+        assert!(d.intersect(&r).is_none());
+    }
+}
@@ -0,0 +1,255 @@
+use core::*;
+use math::*;
+
+// A grid of heights, like a terrain from a DEM image, centered over the origin and rising along +y. Rows
+// run from -z to +z and columns from -x to +x, so an image's top row ends up furthest away when seen from
+// +z, and UVs line up with the same image used as a texture. Each cell is split into two triangles.
+//
+// Rays walk the grid cell by cell with a 2D DDA (Amanatides and Woo 1987), skipping cells they pass over
+// or under without testing any triangles.
+#[derive(Debug)]
+pub struct Heightfield {
+    heights: Vec<f64>,
+    columns: usize,
+    rows: usize,
+    size: Vec3,
+    normals: Vec<Vec3>,
+    bound: BoundingBox,
+}
+
+impl Heightfield {
+    // Heights are fractions of size.y, by row.
+    pub fn new(heights: Vec<Vec<f64>>, size: Vec3) -> Heightfield {
+        let rows = heights.len();
+        let columns = heights.first().map_or(0, |row| row.len());
+        assert!(rows >= 2 && columns >= 2, "heightfields need at least 2 by 2 heights");
+        assert!(heights.iter().all(|row| row.len() == columns), "heightfield rows must all be the same length");
+        let heights: Vec<f64> = heights.into_iter().flat_map(|row| row.into_iter()).map(|h| h * size.y).collect();
+
+        let (y_min, y_max) = heights.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &h| (min.min(h), max.max(h)));
+        let bound = BoundingBox {
+            min: Point::new(-size.x / 2f64, y_min, -size.z / 2f64),
+            max: Point::new(size.x / 2f64, y_max, size.z / 2f64),
+        };
+
+        let mut field = Heightfield { heights, columns, rows, size, normals: vec![], bound };
+        field.normals = (0..rows)
+            .flat_map(|j| (0..columns).map(move |i| (i, j)))
+            .map(|(i, j)| field.slope_normal(i, j))
+            .collect();
+        field
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    fn cell_size(&self) -> (f64, f64) {
+        (self.size.x / (self.columns - 1) as f64, self.size.z / (self.rows - 1) as f64)
+    }
+
+    fn height(&self, i: usize, j: usize) -> f64 {
+        self.heights[j * self.columns + i]
+    }
+
+    pub fn position(&self, i: usize, j: usize) -> Point {
+        let (dx, dz) = self.cell_size();
+        Point::new(-self.size.x / 2f64 + i as f64 * dx, self.height(i, j), -self.size.z / 2f64 + j as f64 * dz)
+    }
+
+    pub fn uv(&self, i: usize, j: usize) -> Uv {
+        Uv(i as f64 / (self.columns - 1) as f64, 1f64 - j as f64 / (self.rows - 1) as f64)
+    }
+
+    // The normal of the smooth surface through the heights, by central differences (one-sided at the edges).
+    pub fn normal(&self, i: usize, j: usize) -> Vec3 {
+        self.normals[j * self.columns + i]
+    }
+
+    fn slope_normal(&self, i: usize, j: usize) -> Vec3 {
+        let (dx, dz) = self.cell_size();
+        let (i0, i1) = (i.saturating_sub(1), (i + 1).min(self.columns - 1));
+        let (j0, j1) = (j.saturating_sub(1), (j + 1).min(self.rows - 1));
+        let slope_x = (self.height(i1, j) - self.height(i0, j)) / ((i1 - i0) as f64 * dx);
+        let slope_z = (self.height(i, j1) - self.height(i, j0)) / ((j1 - j0) as f64 * dz);
+        Vec3::new(-slope_x, 1f64, -slope_z).into_normalized()
+    }
+
+    // The vertices of the two triangles in cell (i, j), as grid coordinates. Both face up.
+    pub fn cell_triangles(i: usize, j: usize) -> [[(usize, usize); 3]; 2] {
+        [
+            [(i, j), (i, j + 1), (i + 1, j)],
+            [(i + 1, j), (i, j + 1), (i + 1, j + 1)],
+        ]
+    }
+
+    // u runs along +x and v along -z, so these are the surface's tangents along x and z, lifted onto the
+    // plane with the given normal (which always faces somewhat up).
+    fn axes_for_normal(&self, normal: Vec3) -> IntersectionGeometry {
+        IntersectionGeometry::new(
+            Vec3::new(self.size.x, -self.size.x * normal.x / normal.y, 0f64),
+            Vec3::new(0f64, self.size.z * normal.z / normal.y, -self.size.z),
+        )
+    }
+
+    // Both triangles are tested, since a ray can cross both in a folded cell and either may be nearer.
+    fn intersect_cell(&self, i: usize, j: usize, ray: &Ray) -> Option<Intersection> {
+        let mut t_max = ray.t_max;
+        let mut closest = None;
+        for triangle in Heightfield::cell_triangles(i, j).iter() {
+            let (p0, p1, p2) = (self.position(triangle[0].0, triangle[0].1), self.position(triangle[1].0, triangle[1].1), self.position(triangle[2].0, triangle[2].1));
+            // pbrt pg. 141, as in Triangle.
+            let (e1, e2) = (p1 - p0, p2 - p0);
+            let s1 = ray.direction.cross(e2);
+            let divisor = s1.dot(&e1);
+            if divisor == 0f64 {
+                continue;
+            }
+            let d = ray.origin - p0;
+            let b1 = d.dot(&s1) / divisor;
+            let s2 = d.cross(e1);
+            let b2 = ray.direction.dot(&s2) / divisor;
+            if b1 < 0f64 || b2 < 0f64 || b1 + b2 > 1f64 {
+                continue;
+            }
+            let t = e2.dot(&s2) / divisor;
+            if t < ray.t_min || t > t_max {
+                continue;
+            }
+
+            let b0 = 1f64 - b1 - b2;
+            let corner = |k: usize| triangle[k];
+            let (uv0, uv1, uv2) = (self.uv(corner(0).0, corner(0).1), self.uv(corner(1).0, corner(1).1), self.uv(corner(2).0, corner(2).1));
+            let (n0, n1, n2) = (self.normal(corner(0).0, corner(0).1), self.normal(corner(1).0, corner(1).1), self.normal(corner(2).0, corner(2).1));
+            let shading_normal = n0 * b0 + n1 * b1 + n2 * b2;
+            t_max = t;
+            closest = Some(Intersection {
+                distance: t,
+                location: ray.at(t),
+                geometry: self.axes_for_normal(e1.cross(e2)),
+                shading_geometry: Some(self.axes_for_normal(shading_normal)),
+                uv: uv0 * b0 + uv1 * b1 + uv2 * b2,
+                material: None,
+            });
+        }
+        closest
+    }
+}
+
+impl Geometry for Heightfield {
+    fn bound(&self) -> BoundingBox {
+        self.bound.clone()
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+        let (t0, t1) = self.bound.intersect(ray)?;
+        let (dx, dz) = self.cell_size();
+        let (d, start) = (&ray.direction, ray.origin + ray.direction * t0);
+        let cell = |x: f64, size: f64, count: usize| (((x / size).floor().max(0f64)) as usize).min(count - 2);
+        let (mut i, mut j) = (cell(start.x + self.size.x / 2f64, dx, self.columns), cell(start.z + self.size.z / 2f64, dz, self.rows));
+
+        // When the ray next crosses a column and a row boundary, and how far apart those crossings are.
+        let setup = |index: usize, origin: f64, direction: f64, size: f64, offset: f64| {
+            if direction > 0f64 {
+                (((index + 1) as f64 * size - offset - origin) / direction, size / direction)
+            } else if direction < 0f64 {
+                ((index as f64 * size - offset - origin) / direction, -size / direction)
+            } else {
+                (f64::INFINITY, f64::INFINITY)
+            }
+        };
+        let (mut next_x, delta_x) = setup(i, ray.origin.x, d.x, dx, self.size.x / 2f64);
+        let (mut next_z, delta_z) = setup(j, ray.origin.z, d.z, dz, self.size.z / 2f64);
+
+        let mut t_enter = t0;
+        loop {
+            let t_exit = next_x.min(next_z).min(t1);
+            // Only look at the triangles if the ray's height over the cell overlaps the cell's heights.
+            let (y_enter, y_exit) = (ray.origin.y + d.y * t_enter, ray.origin.y + d.y * t_exit);
+            let corners = [self.height(i, j), self.height(i + 1, j), self.height(i, j + 1), self.height(i + 1, j + 1)];
+            let (cell_min, cell_max) = corners.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &h| (min.min(h), max.max(h)));
+            if y_enter.min(y_exit) <= cell_max && y_enter.max(y_exit) >= cell_min {
+                if let Some(intersection) = self.intersect_cell(i, j, ray) {
+                    return Some(intersection);
+                }
+            }
+
+            if t_exit >= t1 {
+                return None;
+            }
+            if next_x < next_z {
+                if (d.x > 0f64 && i + 2 >= self.columns) || (d.x < 0f64 && i == 0) {
+                    return None;
+                }
+                i = if d.x > 0f64 { i + 1 } else { i - 1 };
+                next_x += delta_x;
+            } else {
+                if (d.z > 0f64 && j + 2 >= self.rows) || (d.z < 0f64 && j == 0) {
+                    return None;
+                }
+                j = if d.z > 0f64 { j + 1 } else { j - 1 };
+                next_z += delta_z;
+            }
+            t_enter = t_exit;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A ramp rising along +x from 0 to 1 over a 2 by 2 square.
+    fn ramp() -> Heightfield {
+        let row = vec![0f64, 0.25f64, 0.5f64, 0.75f64, 1f64];
+        Heightfield::new(vec![row.clone(), row.clone(), row.clone(), row], Vec3::new(2f64, 1f64, 2f64))
+    }
+
+    #[test]
+    fn it_should_intersect_straight_down() {
+        let r = Ray::half_infinite(Point::new(0.5f64, 5f64, 0.3f64), -Vec3::Y_AXIS);
+        let i = ramp().intersect(&r).unwrap();
+        assert!((i.distance - 4.25f64).abs() < 1e-9);
+        assert!((i.uv.0 - 0.75f64).abs() < 1e-9);
+        let expected = Vec3::new(-1f64, 2f64, 0f64).as_normalized();
+        assert!(i.geometry.normal.as_normalized().dot(&expected) > 1f64 - 1e-9);
+    }
+
+    #[test]
+    fn it_should_walk_across_cells_to_a_hit() {
+        // Skimming along -x at height 0.6 flies over the low end and hits where the ramp climbs past it.
+        let r = Ray::half_infinite(Point::new(-5f64, 0.6f64, -0.7f64), Vec3::X_AXIS);
+        let i = ramp().intersect(&r).unwrap();
+        assert!((i.location.x - 0.2f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_should_miss_over_the_top() {
+        let r = Ray::half_infinite(Point::new(-5f64, 1.5f64, 0f64), Vec3::X_AXIS);
+        assert!(ramp().intersect(&r).is_none());
+    }
+
+    #[test]
+    fn it_should_put_the_first_row_at_the_back() {
+        let field = Heightfield::new(vec![vec![1f64, 1f64], vec![0f64, 0f64]], Vec3::new(2f64, 1f64, 2f64));
+        assert_eq!(field.position(0, 0).z, -1f64);
+        assert_eq!(field.uv(0, 0).1, 1f64);
+        let r = Ray::half_infinite(Point::new(0f64, 5f64, -0.5f64), -Vec3::Y_AXIS);
+        assert!((field.intersect(&r).unwrap().location.y - 0.75f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_should_hit_the_nearer_triangle_in_a_folded_cell() {
+        // One cell, high at two opposite corners and low along the diagonal between its triangles, so a ray
+        // from underneath can come up through the second triangle before going back down into the first.
+        let valley = Heightfield::new(vec![vec![1f64, 0f64], vec![0f64, 1f64]], Vec3::new(2f64, 1f64, 2f64));
+        let r = Ray::half_infinite(Point::new(2f64, 0.5f64, 2f64), Vec3::new(-1f64, 0f64, -1f64).as_normalized());
+        let i = valley.intersect(&r).unwrap();
+        assert!((i.distance - 1.5f64 * 2f64.sqrt()).abs() < 1e-9);
+        assert!((i.location - Point::new(0.5f64, 0.5f64, 0.5f64)).magnitude() < 1e-9);
+    }
+}
@@ -0,0 +1,161 @@
+use std::mem::swap;
+use core::*;
+use math::*;
+use super::quadric::*;
+
+// pbrt pg. 144. The surface swept out by revolving the line segment from point_1 to point_2 around +y.
+// Depending on the segment, that's a hyperboloid of one sheet, a cylinder or a cone. As in pbrt, the
+// implicit form puts the waist at y = 0, so the segment should straddle it symmetrically.
+#[derive(Debug)]
+pub struct Hyperboloid {
+    // Ordered so that point_1 is the lower one, which keeps the normals pointing outward.
+    point_1: Point,
+    point_2: Point,
+    radius: f64,
+    phi_max: f64,
+    // Coefficients of the implicit form, a_h (x^2 + z^2) - c_h y^2 = 1.
+    a_h: f64,
+    c_h: f64,
+}
+
+impl Hyperboloid {
+    pub fn new(point_1: Point, point_2: Point, phi_max: f64) -> Hyperboloid {
+        assert!(point_1.y != point_2.y, "hyperboloid endpoints must be at different heights");
+        let (point_1, point_2) = if point_1.y < point_2.y { (point_1, point_2) } else { (point_2, point_1) };
+        let radial2 = |p: &Point| p.x * p.x + p.z * p.z;
+        // A segment along the axis sweeps out nothing, and the walk below would never find a solution.
+        assert!(radial2(&point_1) > 0f64 || radial2(&point_2) > 0f64, "hyperboloid endpoints must not both be on the axis");
+
+        let (mut p1, mut p2) = (point_1, point_2);
+        if p2.y == 0f64 {
+            swap(&mut p1, &mut p2);
+        }
+        let mut pp = p1;
+        let (mut a_h, mut c_h);
+        // Walk along the line until the point is far enough away from p2 that the system is well-conditioned.
+        loop {
+            pp = pp + (p2 - p1) * 2f64;
+            let (xz1, xz2) = (radial2(&pp), radial2(&p2));
+            let ratio = (pp.y * pp.y) / (xz1 * p2.y * p2.y);
+            a_h = (1f64 / xz1 - ratio) / (1f64 - xz2 * ratio);
+            c_h = (a_h * xz2 - 1f64) / (p2.y * p2.y);
+            if a_h.is_finite() && c_h.is_finite() {
+                break;
+            }
+        }
+
+        Hyperboloid {
+            point_1,
+            point_2,
+            radius: radial2(&point_1).max(radial2(&point_2)).sqrt(),
+            phi_max: phi_max.max(0f64).min(TWO_PI),
+            a_h,
+            c_h,
+        }
+    }
+
+    fn v_for_y(&self, y: f64) -> f64 {
+        (y - self.point_1.y) / (self.point_2.y - self.point_1.y)
+    }
+
+    // The angle of the location around +y, relative to where the generating line is at its height.
+    fn relative_phi(&self, location: &Point) -> f64 {
+        let v = self.v_for_y(location.y);
+        let line_point = self.point_1 * (1f64 - v) + self.point_2 * v;
+        let phi = phi_for_point(location) - phi_for_point(&line_point);
+        if phi < 0f64 { phi + TWO_PI } else { phi }
+    }
+}
+
+impl Quadric for Hyperboloid {
+    fn solve(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let (d, o) = (&ray.direction, &ray.origin);
+        let a = self.a_h * (d.x * d.x + d.z * d.z) - self.c_h * d.y * d.y;
+        if a == 0f64 {
+            return None;
+        }
+        let b = 2f64 * (self.a_h * (d.x * o.x + d.z * o.z) - self.c_h * d.y * o.y);
+        let c = self.a_h * (o.x * o.x + o.z * o.z) - self.c_h * o.y * o.y - 1f64;
+        quadratic(a, b, c)
+    }
+
+    fn contains(&self, location: &Point) -> bool {
+        location.y >= self.point_1.y && location.y <= self.point_2.y && self.relative_phi(location) <= self.phi_max
+    }
+
+    fn get_intersection(&self, t: f64, ray: &Ray) -> Intersection {
+        let location = ray.at(t);
+        let phi = self.relative_phi(&location);
+        let radius = (location.x * location.x + location.z * location.z).sqrt();
+        // The generating line, rotated around +y to wherever the location is.
+        let line = self.point_2 - self.point_1;
+        let (sin, cos) = phi.sin_cos();
+        let v_axis = Vec3::new(line.x * cos + line.z * sin, line.y, -line.x * sin + line.z * cos);
+        Intersection {
+            distance: t,
+            location,
+            geometry: IntersectionGeometry::new(
+                phi_tangent(phi_for_point(&location), radius, self.phi_max),
+                v_axis,
+            ),
+            shading_geometry: None,
+            uv: Uv(phi / self.phi_max, self.v_for_y(location.y)),
+            material: None,
+        }
+    }
+}
+
+impl Geometry for Hyperboloid {
+    fn bound(&self) -> BoundingBox {
+        BoundingBox {
+            min: Point::new(-self.radius, self.point_1.y, -self.radius),
+            max: Point::new(self.radius, self.point_2.y, self.radius),
+        }
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+        intersect_quadric(self, ray)
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<RayInterval> {
+        quadric_intervals(self, ray)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_intersect_a_vertical_segment_like_a_cylinder() {
+        let h = Hyperboloid::new(Point::new(1f64, -1f64, 0f64), Point::new(1f64, 1f64, 0f64), TWO_PI);
+        let r = Ray::half_infinite(Point::new(0f64, 0.5f64, -5f64), Vec3::Z_AXIS);
+        let i = h.intersect(&r).unwrap();
+        assert!((i.distance - 4f64).abs() < 1e-9);
+        assert!(i.geometry.normal.as_normalized().dot(&Vec3::new(0f64, 0f64, -1f64)) > 1f64 - 1e-9);
+    }
+
+    #[test]
+    fn it_should_narrow_at_the_waist() {
+        // A skewed segment sweeps out a hyperboloid of one sheet, whose waist (at y = 0) has radius 1.
+        let h = Hyperboloid::new(Point::new(1f64, -1f64, -1f64), Point::new(1f64, 1f64, 1f64), TWO_PI);
+        let waist = Ray::half_infinite(Point::new(0f64, 0f64, -5f64), Vec3::Z_AXIS);
+        assert!((h.intersect(&waist).unwrap().distance - 4f64).abs() < 1e-9);
+        let rim = Ray::half_infinite(Point::new(0f64, 1f64, -5f64), Vec3::Z_AXIS);
+        assert!((h.intersect(&rim).unwrap().distance - (5f64 - 2f64.sqrt())).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_should_point_normals_outward_regardless_of_endpoint_order() {
+        let h = Hyperboloid::new(Point::new(1f64, 1f64, 1f64), Point::new(1f64, -1f64, -1f64), TWO_PI);
+        let r = Ray::half_infinite(Point::new(-5f64, 0f64, 0f64), Vec3::X_AXIS);
+        let i = h.intersect(&r).unwrap();
+        assert!(i.geometry.normal.dot(&Vec3::new(-1f64, 0f64, 0f64)) > 0f64);
+    }
+
+    #[test]
+    #[should_panic(expected = "must not both be on the axis")]
+    fn it_should_not_allow_a_segment_on_the_axis() {
+        Hyperboloid::new(Point::new(0f64, -1f64, 0f64), Point::new(0f64, 1f64, 0f64), TWO_PI);
+    }
+}
@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use core::*;
+use math::*;
+use super::triangle_mesh::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NormalWeighting {
+    // Each face counts by its area, like implicit smoothing.
+    Area,
+    // Each face counts by its angle at the vertex, so how finely the surface around it is split up doesn't
+    // sway the normal.
+    Angle,
+}
+
+// Clean-up for a mesh after it's built or loaded. Steps happen in the order of the fields.
+#[derive(Debug, Clone, Default)]
+pub struct MeshProcessing {
+    pub weld_epsilon: Option<f64>,
+    // Recomputes the normals with this crease angle, in radians.
+    pub crease_angle: Option<f64>,
+    // Recomputes the normals with this weighting.
+    pub normal_weighting: Option<NormalWeighting>,
+    pub generate_tangents: bool,
+}
+
+impl MeshProcessing {
+    pub fn apply(&self, mesh: TriangleMeshData) -> TriangleMeshData {
+        let recompute_normals = self.crease_angle.is_some() || self.normal_weighting.is_some();
+        let mut mesh = mesh;
+        // Normals that are about to be replaced shouldn't keep vertices from welding.
+        if recompute_normals {
+            mesh.normals = None;
+        }
+        if let Some(epsilon) = self.weld_epsilon {
+            mesh = weld_vertices(mesh, epsilon);
+        }
+        if recompute_normals {
+            mesh = compute_normals(mesh, self.normal_weighting.unwrap_or(NormalWeighting::Area), self.crease_angle.unwrap_or(PI));
+        }
+        if self.generate_tangents {
+            if mesh.uvs.is_some() {
+                mesh = generate_tangents(mesh);
+            } else {
+                eprintln!("warning: can't generate tangents for a mesh without uvs; skipping them");
+            }
+        }
+        mesh
+    }
+}
+
+// The angle of the triangle at a, between b and c.
+fn corner_angle(positions: &[Point], a: usize, b: usize, c: usize) -> f64 {
+    let (ab, ac) = (positions[b] - positions[a], positions[c] - positions[a]);
+    if ab.magnitude2() == 0f64 || ac.magnitude2() == 0f64 {
+        return 0f64;
+    }
+    ab.as_normalized().dot(&ac.as_normalized()).max(-1f64).min(1f64).acos()
+}
+
+fn face_weights(positions: &[Point], (a, b, c): TriangleIndices, face_normal: &Vec3, weighting: NormalWeighting) -> [f64; 3] {
+    match weighting {
+        NormalWeighting::Area => [face_normal.magnitude(); 3],
+        NormalWeighting::Angle => [corner_angle(positions, a, b, c), corner_angle(positions, b, c, a), corner_angle(positions, c, a, b)],
+    }
+}
+
+// Unnormalized, so its length is twice the face's area.
+fn face_normal(positions: &[Point], (a, b, c): TriangleIndices) -> Vec3 {
+    (positions[b] - positions[a]).cross(positions[c] - positions[a])
+}
+
+// Merges vertices that are within epsilon of each other, as long as their normals and uvs are that close
+// too, so seams in either are kept. Triangles that collapse are dropped.
+pub fn weld_vertices(mesh: TriangleMeshData, epsilon: f64) -> TriangleMeshData {
+    assert!(epsilon > 0f64, "weld epsilon must be positive");
+    let is_match = |a: usize, b: usize| {
+        (mesh.positions[a] - mesh.positions[b]).magnitude() <= epsilon
+            && mesh.normals.as_ref().map_or(true, |normals| (normals[a] - normals[b]).into_vector().magnitude() <= epsilon)
+            && mesh.uvs.as_ref().map_or(true, |uvs| (uvs[a].0 - uvs[b].0).abs() <= epsilon && (uvs[a].1 - uvs[b].1).abs() <= epsilon)
+    };
+    let cell_of = |p: &Point| ((p.x / epsilon).floor() as i64, (p.y / epsilon).floor() as i64, (p.z / epsilon).floor() as i64);
+
+    // Vertices that are kept, bucketed by the epsilon-sized cell they're in, so matches are in neighboring cells.
+    let mut cells = HashMap::<(i64, i64, i64), Vec<usize>>::new();
+    let mut kept = Vec::<usize>::new();
+    let mut remapped = Vec::<usize>::with_capacity(mesh.positions.len());
+    for i in 0..mesh.positions.len() {
+        let (x, y, z) = cell_of(&mesh.positions[i]);
+        let mut neighbors = (-1i64..=1).flat_map(|dx| (-1i64..=1).flat_map(move |dy| (-1i64..=1).map(move |dz| (x + dx, y + dy, z + dz))));
+        let existing = neighbors.find_map(|cell| cells.get(&cell).and_then(|vertices| vertices.iter().cloned().find(|&j| is_match(i, j))));
+        match existing {
+            Some(j) => {
+                let index = remapped[j];
+                remapped.push(index);
+            }
+            None => {
+                remapped.push(kept.len());
+                kept.push(i);
+                cells.entry((x, y, z)).or_insert_with(Vec::new).push(i);
+            }
+        }
+    }
+
+    let indices = mesh.indices
+        .iter()
+        .map(|&(a, b, c)| (remapped[a], remapped[b], remapped[c]))
+        .filter(|&(a, b, c)| a != b && b != c && c != a)
+        .collect();
+    let positions = kept.iter().map(|&i| mesh.positions[i]).collect();
+    let smoothing = match mesh.normals {
+        Some(ref normals) => Smoothing::Explicit(kept.iter().map(|&i| normals[i]).collect()),
+        None => Smoothing::None,
+    };
+    let uvs = mesh.uvs.as_ref().map(|uvs| kept.iter().map(|&i| uvs[i]).collect());
+    TriangleMeshData::new(positions, smoothing, uvs, indices)
+}
+
+// Recomputes the normals from the faces. Faces that meet at more than the crease angle don't smooth into each
+// other, so vertices on creases are split, one per side. Faces without any area are dropped.
+pub fn compute_normals(mesh: TriangleMeshData, weighting: NormalWeighting, crease_angle: f64) -> TriangleMeshData {
+    let TriangleMeshData { positions, indices, uvs, .. } = mesh;
+    let indices: Vec<TriangleIndices> = indices.into_iter().filter(|&t| face_normal(&positions, t).magnitude2() > 0f64).collect();
+    let face_normals: Vec<Vec3> = indices.iter().map(|&t| face_normal(&positions, t)).collect();
+    let weights: Vec<[f64; 3]> = indices.iter().zip(&face_normals).map(|(&t, n)| face_weights(&positions, t, n, weighting)).collect();
+    let face_normals: Vec<Vec3> = face_normals.into_iter().map(|n| n.into_normalized()).collect();
+
+    let mut corners = vec![vec![]; positions.len()];
+    for (face, &(a, b, c)) in indices.iter().enumerate() {
+        corners[a].push((face, 0));
+        corners[b].push((face, 1));
+        corners[c].push((face, 2));
+    }
+
+    let cos_crease = crease_angle.cos();
+    let (mut new_positions, mut new_normals, mut new_uvs) = (vec![], vec![], vec![]);
+    let mut new_indices = vec![[0usize; 3]; indices.len()];
+    for (vertex, corners) in corners.iter().enumerate() {
+        // Corners that smooth with the same faces get the same normal, and so share a vertex.
+        let mut splits: Vec<(Vec3, usize)> = vec![];
+        for &(face, corner) in corners {
+            let own = face_normals[face];
+            let normal = corners
+                .iter()
+                .filter(|&&(other, _)| own.dot(&face_normals[other]) >= cos_crease)
+                .fold(Vec3::uniform(0f64), |sum, &(other, other_corner)| sum + face_normals[other] * weights[other][other_corner]);
+            let normal = if normal.magnitude2() > 0f64 { normal.into_normalized() } else { own };
+            let index = match splits.iter().find(|&&(split, _)| split.x == normal.x && split.y == normal.y && split.z == normal.z) {
+                Some(&(_, index)) => index,
+                None => {
+                    new_positions.push(positions[vertex]);
+                    new_normals.push(normal.into_normal());
+                    if let Some(ref uvs) = uvs {
+                        new_uvs.push(uvs[vertex]);
+                    }
+                    splits.push((normal, new_positions.len() - 1));
+                    new_positions.len() - 1
+                }
+            };
+            new_indices[face][corner] = index;
+        }
+    }
+
+    let new_indices = new_indices.into_iter().map(|t| (t[0], t[1], t[2])).collect();
+    TriangleMeshData::new(new_positions, Smoothing::Explicit(new_normals), uvs.map(|_| new_uvs), new_indices)
+}
+
+// MikkTSpace-style tangents: each face's direction of increasing u, flattened onto the vertex's normal and
+// weighted by the face's angle there, averaged. Whether v follows normal cross tangent or goes against it is
+// decided the same way. Meshes without normals use area-weighted face normals.
+pub fn generate_tangents(mesh: TriangleMeshData) -> TriangleMeshData {
+    let tangents = {
+        let uvs = mesh.uvs.as_ref().expect("tangents can only be generated for meshes with uvs");
+        let positions = &mesh.positions;
+        let normals: Vec<Vec3> = match mesh.normals {
+            Some(ref normals) => normals.iter().map(|n| n.into_vector().into_normalized()).collect(),
+            None => {
+                let mut normals = vec![Vec3::uniform(0f64); positions.len()];
+                for &t in &mesh.indices {
+                    let normal = face_normal(positions, t);
+                    for &i in &[t.0, t.1, t.2] {
+                        normals[i] = normals[i] + normal;
+                    }
+                }
+                normals.into_iter().map(|n| if n.magnitude2() > 0f64 { n.into_normalized() } else { Vec3::Z_AXIS }).collect()
+            }
+        };
+        let flatten = |v: Vec3, n: Vec3| v - n * n.dot(&v);
+
+        let mut u_sums = vec![Vec3::uniform(0f64); positions.len()];
+        let mut v_sums = vec![Vec3::uniform(0f64); positions.len()];
+        for &(a, b, c) in &mesh.indices {
+            // The same derivatives that triangles use for their u and v axes.
+            let (du0, du1, dv0, dv1) = (uvs[a].0 - uvs[c].0, uvs[b].0 - uvs[c].0, uvs[a].1 - uvs[c].1, uvs[b].1 - uvs[c].1);
+            let d = du0 * dv1 - dv0 * du1;
+            if d == 0f64 {
+                continue;
+            }
+            let (dp0, dp1) = (positions[a] - positions[c], positions[b] - positions[c]);
+            let (u_axis, v_axis) = ((dp0 * dv1 - dp1 * dv0) / d, (dp0 * -du1 + dp1 * du0) / d);
+            for &(i, j, k) in &[(a, b, c), (b, c, a), (c, a, b)] {
+                let weight = corner_angle(positions, i, j, k);
+                let (u, v) = (flatten(u_axis, normals[i]), flatten(v_axis, normals[i]));
+                if u.magnitude2() > 0f64 {
+                    u_sums[i] = u_sums[i] + u.into_normalized() * weight;
+                }
+                if v.magnitude2() > 0f64 {
+                    v_sums[i] = v_sums[i] + v.into_normalized() * weight;
+                }
+            }
+        }
+
+        (0..positions.len())
+            .map(|i| {
+                let normal = normals[i];
+                let u = flatten(u_sums[i], normal);
+                let direction = if u.magnitude2() > 0f64 { u.into_normalized() } else { IntersectionGeometry::from_normal(normal).u_axis };
+                let sign = if normal.cross(direction).dot(&v_sums[i]) < 0f64 { -1f64 } else { 1f64 };
+                Tangent { direction, sign }
+            })
+            .collect()
+    };
+    TriangleMeshData { tangents: Some(tangents), ..mesh }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A unit cube with one vertex per corner, wound outward.
+    fn cube() -> TriangleMeshData {
+        let positions = (0..8).map(|i| Point::new((i & 1) as f64, (i >> 1 & 1) as f64, (i >> 2 & 1) as f64)).collect();
+        let indices = vec![
+            (0, 2, 1), (1, 2, 3), (4, 5, 6), (5, 7, 6),
+            (0, 1, 4), (1, 5, 4), (2, 6, 3), (3, 6, 7),
+            (0, 4, 2), (2, 4, 6), (1, 3, 5), (3, 7, 5),
+        ];
+        TriangleMeshData::new(positions, Smoothing::None, None, indices)
+    }
+
+    #[test]
+    fn it_should_weld_close_vertices_but_keep_uv_seams() {
+        let positions = vec![
+            Point::new(0f64, 0f64, 0f64), Point::new(1f64, 0f64, 0f64), Point::new(0f64, 1f64, 0f64),
+            Point::new(1f64, 1e-7f64, 0f64), Point::new(1f64, 1f64, 0f64), Point::new(0f64, 1f64, 1e-7f64),
+        ];
+        let indices = vec![(0, 1, 2), (3, 4, 5)];
+        let mesh = weld_vertices(TriangleMeshData::new(positions.clone(), Smoothing::None, None, indices.clone()), 1e-5f64);
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.indices, vec![(0, 1, 2), (1, 3, 2)]);
+
+        let uvs = vec![Uv(0f64, 0f64), Uv(1f64, 0f64), Uv(0f64, 1f64), Uv(0.5f64, 0f64), Uv(1f64, 1f64), Uv(0f64, 1f64)];
+        let mesh = weld_vertices(TriangleMeshData::new(positions, Smoothing::None, Some(uvs), indices), 1e-5f64);
+        assert_eq!(mesh.positions.len(), 5);
+    }
+
+    #[test]
+    fn it_should_split_normals_at_creases() {
+        let smooth = compute_normals(cube(), NormalWeighting::Angle, PI);
+        assert_eq!(smooth.positions.len(), 8);
+        let corner = smooth.normals.as_ref().unwrap()[smooth.indices[0].0];
+        assert!((corner.into_vector() - Vec3::uniform(-1f64 / 3f64.sqrt())).magnitude() < 1e-9);
+
+        let creased = compute_normals(cube(), NormalWeighting::Area, 30f64.to_radians());
+        assert_eq!(creased.positions.len(), 24);
+        for (&(a, b, c), i) in creased.indices.iter().zip(0..) {
+            let normals = creased.normals.as_ref().unwrap();
+            let face = face_normal(&creased.positions, (a, b, c)).into_normalized();
+            for &v in &[a, b, c] {
+                assert!((normals[v].into_vector() - face).magnitude() < 1e-9, "face {} is not flat", i);
+            }
+        }
+    }
+
+    #[test]
+    fn it_should_weight_normals_by_angle_or_area() {
+        // At the origin, a big face in the xy plane and a small one in the xz plane, both with a right angle there.
+        let positions = vec![
+            Point::new(0f64, 0f64, 0f64), Point::new(4f64, 0f64, 0f64), Point::new(0f64, 4f64, 0f64),
+            Point::new(0f64, 0f64, 1f64),
+        ];
+        let mesh = || TriangleMeshData::new(positions.clone(), Smoothing::None, None, vec![(0, 1, 2), (0, 3, 1)]);
+        let normal_at_origin = |mesh: TriangleMeshData| {
+            let corner = mesh.indices[0].0;
+            mesh.normals.unwrap()[corner].into_vector()
+        };
+        let by_angle = normal_at_origin(compute_normals(mesh(), NormalWeighting::Angle, PI));
+        assert!((by_angle.y - by_angle.z).abs() < 1e-9);
+        let by_area = normal_at_origin(compute_normals(mesh(), NormalWeighting::Area, PI));
+        assert!((by_area.z - 4f64 * by_area.y).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_should_generate_tangents_along_u() {
+        let positions = vec![Point::new(0f64, 0f64, 0f64), Point::new(1f64, 0f64, 0f64), Point::new(1f64, 1f64, 0f64), Point::new(0f64, 1f64, 0f64)];
+        let quad = |uvs: Vec<Uv>| TriangleMeshData::new(positions.clone(), Smoothing::Implicit, Some(uvs), vec![(0, 1, 2), (0, 2, 3)]);
+
+        let mesh = generate_tangents(quad(vec![Uv(0f64, 0f64), Uv(1f64, 0f64), Uv(1f64, 1f64), Uv(0f64, 1f64)]));
+        for tangent in mesh.tangents.unwrap() {
+            assert!((tangent.direction - Vec3::X_AXIS).magnitude() < 1e-9);
+            assert_eq!(tangent.sign, 1f64);
+        }
+
+        // Mirrored in v.
+        let mesh = generate_tangents(quad(vec![Uv(0f64, 1f64), Uv(1f64, 1f64), Uv(1f64, 0f64), Uv(0f64, 0f64)]));
+        for tangent in mesh.tangents.unwrap() {
+            assert!((tangent.direction - Vec3::X_AXIS).magnitude() < 1e-9);
+            assert_eq!(tangent.sign, -1f64);
+        }
+    }
+
+    #[test]
+    fn it_should_skip_tangents_for_meshes_without_uvs() {
+        let processing = MeshProcessing { generate_tangents: true, ..Default::default() };
+        let mesh = processing.apply(cube());
+        assert!(mesh.tangents.is_none());
+        assert_eq!(mesh.positions.len(), 8);
+    }
+}
@@ -1,9 +1,41 @@
-mod difference;
+mod bezier_patch;
+mod blobby;
+mod cone;
+mod csg;
+mod curves;
+mod cylinder;
+mod disk;
+mod heightfield;
+mod hyperboloid;
+mod mesh_processing;
+mod parallelogram;
+mod paraboloid;
+mod plane;
+mod quadric;
 mod rect_prism;
+mod revolution;
+mod sdf;
 mod sphere;
+mod torus;
 mod triangle_mesh;
 
-pub use self::difference::*;
+pub use self::bezier_patch::*;
+pub use self::blobby::*;
+pub use self::cone::*;
+pub use self::csg::*;
+pub use self::curves::*;
+pub use self::cylinder::*;
+pub use self::disk::*;
+pub use self::heightfield::*;
+pub use self::hyperboloid::*;
+pub use self::mesh_processing::*;
+pub use self::parallelogram::*;
+pub use self::paraboloid::*;
+pub use self::plane::*;
+pub use self::quadric::*;
 pub use self::rect_prism::*;
+pub use self::revolution::*;
+pub use self::sdf::*;
 pub use self::sphere::*;
+pub use self::torus::*;
 pub use self::triangle_mesh::*;
@@ -0,0 +1,117 @@
+use core::*;
+use math::*;
+use super::quadric::*;
+
+// pbrt pg. 144. The paraboloid opens upward from its vertex at the origin and has the given radius at
+// y_max.
+#[derive(Debug)]
+pub struct Paraboloid {
+    radius: f64,
+    y_min: f64,
+    y_max: f64,
+    phi_max: f64,
+}
+
+impl Paraboloid {
+    pub fn new(radius: f64, y_min: f64, y_max: f64, phi_max: f64) -> Paraboloid {
+        let y_max = y_min.max(y_max);
+        Paraboloid {
+            radius,
+            y_min: y_min.min(y_max).max(0f64),
+            y_max,
+            phi_max: phi_max.max(0f64).min(TWO_PI),
+        }
+    }
+
+    fn k(&self) -> f64 {
+        self.y_max / (self.radius * self.radius)
+    }
+}
+
+impl Quadric for Paraboloid {
+    fn solve(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let (d, o) = (&ray.direction, &ray.origin);
+        let k = self.k();
+        let a = k * (d.x * d.x + d.z * d.z);
+        let b = 2f64 * k * (d.x * o.x + d.z * o.z) - d.y;
+        let c = k * (o.x * o.x + o.z * o.z) - o.y;
+        if a == 0f64 {
+            // Rays parallel to the axis cross exactly once.
+            let t = -c / b;
+            return if t.is_finite() { Some((t, t)) } else { None };
+        }
+        quadratic(a, b, c)
+    }
+
+    fn contains(&self, location: &Point) -> bool {
+        location.y >= self.y_min && location.y <= self.y_max && phi_for_point(location) <= self.phi_max
+    }
+
+    fn get_intersection(&self, t: f64, ray: &Ray) -> Intersection {
+        let location = ray.at(t);
+        let phi = phi_for_point(&location);
+        let height = self.y_max - self.y_min;
+        let radius = (location.x * location.x + location.z * location.z).sqrt();
+        // pbrt's (x / 2z, y / 2z, 1), in terms of phi; the radial part is vertical at the vertex.
+        let slope = 1f64 / (2f64 * self.k() * radius.max(EPSILON));
+        let v_axis = Vec3::new(slope * phi.cos(), 1f64, -slope * phi.sin()) * height;
+        Intersection {
+            distance: t,
+            location,
+            geometry: IntersectionGeometry::new(phi_tangent(phi, radius, self.phi_max), v_axis),
+            shading_geometry: None,
+            uv: Uv(phi / self.phi_max, (location.y - self.y_min) / height),
+            material: None,
+        }
+    }
+}
+
+impl Geometry for Paraboloid {
+    fn bound(&self) -> BoundingBox {
+        BoundingBox {
+            min: Point::new(-self.radius, self.y_min, -self.radius),
+            max: Point::new(self.radius, self.y_max, self.radius),
+        }
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+        intersect_quadric(self, ray)
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<RayInterval> {
+        quadric_intervals(self, ray)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_paraboloid() -> Paraboloid {
+        Paraboloid::new(1f64, 0f64, 1f64, TWO_PI)
+    }
+
+    #[test]
+    fn it_should_intersect_the_side_from_outside() {
+        let r = Ray::half_infinite(Point::new(0f64, 0.25f64, -5f64), Vec3::Z_AXIS);
+        let i = unit_paraboloid().intersect(&r).unwrap();
+        assert!((i.distance - 4.5f64).abs() < 1e-9);
+        let normal = i.geometry.normal.as_normalized();
+        assert!(normal.z < 0f64 && normal.y < 0f64);
+    }
+
+    #[test]
+    fn it_should_intersect_the_vertex_along_the_axis() {
+        let r = Ray::half_infinite(Point::new(0f64, 5f64, 0f64), -Vec3::Y_AXIS);
+        let i = unit_paraboloid().intersect(&r).unwrap();
+        assert!((i.distance - 5f64).abs() < 1e-9);
+        assert!(i.geometry.normal.as_normalized().y < -1f64 + 1e-9);
+    }
+
+    #[test]
+    fn it_should_not_intersect_below_y_min() {
+        let bowl = Paraboloid::new(1f64, 0.5f64, 1f64, TWO_PI);
+        let r = Ray::half_infinite(Point::new(0f64, 0.25f64, -5f64), Vec3::Z_AXIS);
+        assert!(bowl.intersect(&r).is_none());
+    }
+}
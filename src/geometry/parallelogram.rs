@@ -0,0 +1,94 @@
+use core::*;
+use math::*;
+
+// The parallelogram spanned by edge_u and edge_v from origin, facing along edge_u x edge_v. uv_scale is
+// how many times the texture repeats along each edge.
+#[derive(Debug)]
+pub struct Parallelogram {
+    origin: Point,
+    edge_u: Vec3,
+    edge_v: Vec3,
+    normal: Vec3,
+    uv_scale: Uv,
+}
+
+impl Parallelogram {
+    pub fn new(origin: Point, edge_u: Vec3, edge_v: Vec3, uv_scale: Uv) -> Parallelogram {
+        let normal = edge_u.cross(edge_v);
+        assert!(normal.magnitude2() > 0f64, "parallelogram edges must not be parallel");
+        Parallelogram { origin, edge_u, edge_v, normal, uv_scale }
+    }
+}
+
+impl Geometry for Parallelogram {
+    fn bound(&self) -> BoundingBox {
+        BoundingBox::empty()
+            .with_point(&self.origin)
+            .with_point(&(self.origin + self.edge_u))
+            .with_point(&(self.origin + self.edge_v))
+            .with_point(&(self.origin + self.edge_u + self.edge_v))
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+        let denominator = self.normal.dot(&ray.direction);
+        if denominator == 0f64 {
+            return None;
+        }
+        let t = self.normal.dot(&(self.origin - ray.origin)) / denominator;
+        if t < ray.t_min || t > ray.t_max {
+            return None;
+        }
+
+        // Solve location - origin = s edge_u + t edge_v by projecting onto the dual basis.
+        let location = ray.at(t);
+        let offset = location - self.origin;
+        let normal2 = self.normal.magnitude2();
+        let s = offset.cross(self.edge_v).dot(&self.normal) / normal2;
+        let r = self.edge_u.cross(offset).dot(&self.normal) / normal2;
+        if s < 0f64 || s > 1f64 || r < 0f64 || r > 1f64 {
+            return None;
+        }
+
+        let Uv(scale_u, scale_v) = self.uv_scale;
+        Some(Intersection {
+            distance: t,
+            location,
+            geometry: IntersectionGeometry::new(self.edge_u / scale_u, self.edge_v / scale_v),
+            shading_geometry: None,
+            uv: Uv(s * scale_u, r * scale_v).wrap(),
+            material: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn skewed() -> Parallelogram {
+        Parallelogram::new(Point::new(0f64, 0f64, 0f64), Vec3::new(2f64, 0f64, 0f64), Vec3::new(1f64, 1f64, 0f64), Uv(1f64, 1f64))
+    }
+
+    #[test]
+    fn it_should_intersect_inside_the_edges() {
+        let r = Ray::half_infinite(Point::new(1.75f64, 0.5f64, 5f64), -Vec3::Z_AXIS);
+        let i = skewed().intersect(&r).unwrap();
+        assert_eq!(i.distance, 5f64);
+        assert!(i.geometry.normal.as_normalized().z > 1f64 - 1e-9);
+        assert!((i.uv.0 - 0.625f64).abs() < 1e-9 && (i.uv.1 - 0.5f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_should_not_intersect_outside_the_skewed_edge() {
+        let r = Ray::half_infinite(Point::new(0.25f64, 0.5f64, 5f64), -Vec3::Z_AXIS);
+        assert!(skewed().intersect(&r).is_none());
+    }
+
+    #[test]
+    fn it_should_tile_uvs() {
+        let tiled = Parallelogram::new(Point::new(0f64, 0f64, 0f64), Vec3::X_AXIS, Vec3::Y_AXIS, Uv(4f64, 2f64));
+        let r = Ray::half_infinite(Point::new(0.3f64, 0.8f64, 5f64), -Vec3::Z_AXIS);
+        let i = tiled.intersect(&r).unwrap();
+        assert!((i.uv.0 - 0.2f64).abs() < 1e-9 && (i.uv.1 - 0.6f64).abs() < 1e-9);
+    }
+}
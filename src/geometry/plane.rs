@@ -0,0 +1,124 @@
+use std::f64;
+use core::*;
+use math::*;
+
+// The infinite plane y = 0, facing +y. Everything below it counts as inside, so it also works as a
+// half-space in CSG. u runs along +x and v along -z, scaled by uv_scale and repeated every unit.
+#[derive(Debug)]
+pub struct Plane {
+    uv_scale: Uv,
+}
+
+impl Plane {
+    pub fn new(uv_scale: Uv) -> Plane {
+        Plane { uv_scale }
+    }
+
+    fn get_intersection(&self, t: f64, ray: &Ray) -> Intersection {
+        let location = ray.at(t);
+        let Uv(scale_u, scale_v) = self.uv_scale;
+        Intersection {
+            distance: t,
+            // Snap away the rounding error, which would otherwise leave reflected rays starting underneath.
+            location: Point::new(location.x, 0f64, location.z),
+            geometry: IntersectionGeometry::new(
+                Vec3::new(1f64 / scale_u, 0f64, 0f64),
+                Vec3::new(0f64, 0f64, -1f64 / scale_v),
+            ),
+            shading_geometry: None,
+            uv: Uv(location.x * scale_u, -location.z * scale_v).wrap(),
+            material: None,
+        }
+    }
+}
+
+impl Geometry for Plane {
+    fn bound(&self) -> BoundingBox {
+        BoundingBox {
+            min: Point::new(f64::NEG_INFINITY, 0f64, f64::NEG_INFINITY),
+            max: Point::new(f64::INFINITY, 0f64, f64::INFINITY),
+        }
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+        if ray.direction.y == 0f64 {
+            return None;
+        }
+        let t = -ray.origin.y / ray.direction.y;
+        if t >= ray.t_min && t <= ray.t_max {
+            Some(self.get_intersection(t, ray))
+        } else {
+            None
+        }
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<RayInterval> {
+        if ray.direction.y == 0f64 {
+            return if ray.origin.y < 0f64 { vec![RayInterval { enter: None, exit: None }] } else { vec![] };
+        }
+        let line = Ray { t_min: f64::NEG_INFINITY, t_max: f64::INFINITY, ..ray.clone() };
+        intervals_from_crossings(vec![self.get_intersection(-ray.origin.y / ray.direction.y, &line)], &ray.direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use geometry::Sphere;
+
+    fn plane() -> Plane {
+        Plane::new(Uv(1f64, 1f64))
+    }
+
+    #[test]
+    fn it_should_intersect_from_above_facing_up() {
+        let r = Ray::half_infinite(Point::new(0.25f64, 2f64, -0.5f64), -Vec3::Y_AXIS);
+        let i = plane().intersect(&r).unwrap();
+        assert_eq!(i.distance, 2f64);
+        assert!(i.geometry.normal.as_normalized().y > 1f64 - 1e-9);
+        assert_eq!((i.uv.0, i.uv.1), (0.25f64, 0.5f64));
+    }
+
+    #[test]
+    fn it_should_tile_uvs() {
+        let tiled = Plane::new(Uv(0.5f64, 0.5f64));
+        let r = Ray::half_infinite(Point::new(-3f64, 2f64, 3f64), -Vec3::Y_AXIS);
+        let i = tiled.intersect(&r).unwrap();
+        assert_eq!((i.uv.0, i.uv.1), (0.5f64, 0.5f64));
+    }
+
+    #[test]
+    fn it_should_not_intersect_parallel_rays() {
+        let r = Ray::half_infinite(Point::new(0f64, 1f64, 0f64), Vec3::X_AXIS);
+        assert!(plane().intersect(&r).is_none());
+    }
+
+    #[test]
+    fn it_should_be_a_half_space() {
+        let r = Ray::half_infinite(Point::new(0f64, 2f64, 0f64), -Vec3::Y_AXIS);
+        let intervals = plane().intervals(&r);
+        assert_eq!(intervals.len(), 1);
+        assert_eq!((intervals[0].start(), intervals[0].end()), (2f64, f64::INFINITY));
+        let below = Ray::half_infinite(Point::new(0f64, -2f64, 0f64), Vec3::X_AXIS);
+        assert_eq!(plane().intervals(&below).len(), 1);
+    }
+
+    #[test]
+    fn it_should_be_kept_out_of_the_kd_tree() {
+        let mut objects: Vec<Shape> = (0..10)
+            .map(|i| Shape::new(
+                Arc::new(Sphere::new(0.5f64)),
+                Transform::new(Mat4::create_translation(Vec3::new(i as f64 * 2f64, 1f64, 0f64))),
+            ))
+            .collect();
+        objects.push(Shape::new(Arc::new(plane()), Transform::new(IDENTITY_MATRIX)));
+        let tree = VolumeKdTree::from(objects);
+        assert!(!tree.bound().is_bounded());
+
+        let onto_sphere = Ray::half_infinite(Point::new(4f64, 5f64, 0f64), -Vec3::Y_AXIS);
+        assert_eq!(tree.intersect(&onto_sphere).unwrap().distance, 3.5f64);
+        let onto_plane = Ray::half_infinite(Point::new(5f64, 5f64, 0f64), -Vec3::Y_AXIS);
+        assert_eq!(tree.intersect(&onto_plane).unwrap().distance, 5f64);
+    }
+}
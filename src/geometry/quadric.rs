@@ -0,0 +1,59 @@
+use std::f64::consts::PI;
+use core::*;
+use math::*;
+
+pub const TWO_PI: f64 = PI * 2f64;
+
+// The quadrics here follow pbrt's (chapter 3), except that they're symmetric about +y rather than +z, so
+// that they agree with Sphere about which way is up. pbrt's z_min/z_max are therefore y_min/y_max here.
+//
+// phi is the angle around +y, starting at +x and turning right-handedly (i.e. towards -z), so that it
+// lines up with Sphere's u coordinate. A point at radius r and angle phi is (r cos phi, y, -r sin phi).
+pub fn phi_for_point(point: &Point) -> f64 {
+    let phi = (-point.z).atan2(point.x);
+    if phi < 0f64 { phi + TWO_PI } else { phi }
+}
+
+// The derivative of the location with respect to u, where u = phi / phi_max. This is pbrt's
+// (-phi_max y, phi_max x, 0), but written in terms of phi so that it stays well-defined (if tiny) on the
+// axis, where the location alone can't say which way the surface is going.
+pub fn phi_tangent(phi: f64, radius: f64, phi_max: f64) -> Vec3 {
+    Vec3::new(-phi.sin(), 0f64, -phi.cos()) * (phi_max * radius.max(EPSILON))
+}
+
+// The parts of a quadric that differ between them. intersect_quadric and quadric_intervals take care of
+// the rest, including clipping to the quadric's partial extent.
+pub trait Quadric {
+    // Both times the ray's line crosses the whole (unclipped) surface, ignoring the ray's bounds. A root
+    // that's gone off to infinity (e.g. for a ray parallel to a cone's side) is skipped.
+    fn solve(&self, ray: &Ray) -> Option<(f64, f64)>;
+
+    // Whether a point on the unclipped surface survives clipping by y range and phi_max.
+    fn contains(&self, location: &Point) -> bool;
+
+    fn get_intersection(&self, t: f64, ray: &Ray) -> Intersection;
+}
+
+pub fn intersect_quadric<Q: Quadric>(quadric: &Q, ray: &Ray) -> Option<Intersection> {
+    let (t0, t1) = quadric.solve(ray)?;
+    [t0, t1].iter()
+        .find(|&&t| t.is_finite() && t >= ray.t_min && t <= ray.t_max && quadric.contains(&(ray.origin + ray.direction * t)))
+        .map(|&t| quadric.get_intersection(t, ray))
+}
+
+// Clipped quadrics aren't closed, so unlike Sphere's, these are worked out from the direction of each
+// crossing's normal rather than assuming the ray is inside between the two roots.
+pub fn quadric_intervals<Q: Quadric>(quadric: &Q, ray: &Ray) -> Vec<RayInterval> {
+    match quadric.solve(ray) {
+        // A ray that just grazes the surface doesn't go anywhere inside it.
+        Some((t0, t1)) if t1 - t0 > EPSILON => {
+            let line = Ray { t_min: t0, t_max: t1, ..ray.clone() };
+            let crossings = [t0, t1].iter()
+                .filter(|&&t| t.is_finite() && quadric.contains(&(ray.origin + ray.direction * t)))
+                .map(|&t| quadric.get_intersection(t, &line))
+                .collect();
+            intervals_from_crossings(crossings, &ray.direction)
+        }
+        _ => vec![],
+    }
+}
@@ -88,18 +88,10 @@ impl RectPrism {
             material: None,
         }
     }
-}
-
-impl Geometry for RectPrism {
-    fn bound(&self) -> BoundingBox {
-        BoundingBox {
-            min: self.min,
-            max: self.max,
-        }
-    }
 
     // pbrt pg. 194
-    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+    // Both times the ray's line crosses the prism, ignoring the ray's bounds.
+    fn solve(&self, ray: &Ray) -> Option<(f64, f64)> {
         let (mut t0, mut t1) = (f64::NEG_INFINITY, f64::INFINITY);
 
         foreach_axis!(a in {
@@ -110,13 +102,32 @@ impl Geometry for RectPrism {
             if t_near > t_far {
                 swap!(t_near, t_far);
             }
-            t0 = non_nan_max(t0, t_near);
-            t1 = non_nan_min(t1, t_far);
+            // Unlike non_nan_max/min, these ignore the NaNs we get when the ray lies exactly in a face's plane.
+            t0 = t0.max(t_near);
+            t1 = t1.min(t_far);
             if t0 > t1 {
                 return None;
             }
         });
 
+        Some((t0, t1))
+    }
+}
+
+impl Geometry for RectPrism {
+    fn bound(&self) -> BoundingBox {
+        BoundingBox {
+            min: self.min,
+            max: self.max,
+        }
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+        let (t0, t1) = match self.solve(ray) {
+            Some(ts) => ts,
+            None => { return None; }
+        };
+
         if t1 < ray.t_min || t0 > ray.t_max {
             None
         } else if t0 < ray.t_min {
@@ -129,6 +140,19 @@ impl Geometry for RectPrism {
             Some(self.get_intersection(t0, &ray))
         }
     }
+
+    fn intervals(&self, ray: &Ray) -> Vec<RayInterval> {
+        match self.solve(ray) {
+            Some((t0, t1)) => {
+                let line = Ray { t_min: t0, t_max: t1, ..ray.clone() };
+                vec![RayInterval {
+                    enter: Some(self.get_intersection(t0, &line)),
+                    exit: Some(self.get_intersection(t1, &line)),
+                }]
+            }
+            None => vec![],
+        }
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,212 @@
+use core::*;
+use math::*;
+use super::quadric::*;
+
+// A surface of revolution: a profile made of cubic beziers, spun all the way around +y. Each profile point's
+// x is its distance from the axis and its y its height; z is ignored. u goes around the axis like the
+// quadrics' phi, and v runs along the profile, so normals face to the right of the profile's direction
+// when drawn in the xy-plane, i.e. outward for profiles drawn bottom to top.
+//
+// Intersections are solved on each segment directly, rather than tessellating it: along a ray, the height
+// pins down t for any profile parameter s, which leaves a degree 6 polynomial in s for the radius to match.
+#[derive(Debug)]
+pub struct Revolution {
+    // Power basis (lowest degree first) of each segment's radius and height as polynomials in s.
+    segments: Vec<([f64; 4], [f64; 4])>,
+    bound: BoundingBox,
+}
+
+// Rays whose direction is closer to horizontal than this are solved as if they were exactly horizontal,
+// where the general polynomial degenerates.
+const HORIZONTAL_TOLERANCE: f64 = 1e-6f64;
+
+impl Revolution {
+    pub fn new(profile: Vec<CubicBezier>) -> Revolution {
+        assert!(!profile.is_empty(), "a surface of revolution needs at least one profile segment");
+        let power_basis = |p0: f64, p1: f64, p2: f64, p3: f64| [
+            p0,
+            3f64 * (p1 - p0),
+            3f64 * (p0 - 2f64 * p1 + p2),
+            -p0 + 3f64 * p1 - 3f64 * p2 + p3,
+        ];
+        let segments = profile.iter()
+            .map(|b| (power_basis(b.p0.x, b.p1.x, b.p2.x, b.p3.x), power_basis(b.p0.y, b.p1.y, b.p2.y, b.p3.y)))
+            .collect();
+
+        // Beziers stay within their control points' hull.
+        let control_points = profile.iter().flat_map(|b| vec![b.p0, b.p1, b.p2, b.p3]);
+        let (radius, y_min, y_max) = control_points.fold((0f64, f64::INFINITY, f64::NEG_INFINITY), |(radius, y_min, y_max), p| {
+            (radius.max(p.x.abs()), y_min.min(p.y), y_max.max(p.y))
+        });
+        let bound = BoundingBox {
+            min: Point::new(-radius, y_min, -radius),
+            max: Point::new(radius, y_max, radius),
+        };
+
+        Revolution { segments, bound }
+    }
+
+    // Every (segment, s, t) where the ray's line crosses the surface, sorted by t.
+    fn crossings(&self, ray: &Ray) -> Vec<(usize, f64, f64)> {
+        let (d, o) = (&ray.direction, &ray.origin);
+        let mut crossings = vec![];
+
+        for (index, &(ref radius, ref height)) in self.segments.iter().enumerate() {
+            let relative_height = [height[0] - o.y, height[1], height[2], height[3]];
+            if d.y.abs() < HORIZONTAL_TOLERANCE * d.magnitude() {
+                // The ray stays at one height, so find where the profile does too, then solve the circle there.
+                for s in polynomial_roots_between(&relative_height, 0f64, 1f64) {
+                    let r = evaluate_polynomial(radius, s);
+                    let a = d.x * d.x + d.z * d.z;
+                    let b = 2f64 * (d.x * o.x + d.z * o.z);
+                    let c = o.x * o.x + o.z * o.z - r * r;
+                    if let Some((t0, t1)) = quadratic(a, b, c) {
+                        crossings.push((index, s, t0));
+                        crossings.push((index, s, t1));
+                    }
+                }
+            } else {
+                // t = (y(s) - o.y) / d.y puts the ray at the profile's height; multiplying through by d.y keeps
+                // (d.y o.x + d.x (y(s) - o.y))^2 + (d.y o.z + d.z (y(s) - o.y))^2 - d.y^2 r(s)^2 a polynomial.
+                let along = |origin: f64, direction: f64| {
+                    add_polynomials(&[d.y * origin], &relative_height.iter().map(|c| c * direction).collect::<Vec<f64>>())
+                };
+                let (x, z) = (along(o.x, d.x), along(o.z, d.z));
+                let scaled_radius: Vec<f64> = radius.iter().map(|c| c * d.y).collect();
+                let polynomial = add_polynomials(
+                    &add_polynomials(&multiply_polynomials(&x, &x), &multiply_polynomials(&z, &z)),
+                    &multiply_polynomials(&scaled_radius, &scaled_radius).iter().map(|c| -c).collect::<Vec<f64>>(),
+                );
+                for s in polynomial_roots_between(&polynomial, 0f64, 1f64) {
+                    crossings.push((index, s, evaluate_polynomial(&relative_height, s) / d.y));
+                }
+            }
+        }
+
+        crossings.sort_by(|a, b| a.2.partial_cmp(&b.2).unwrap());
+        // Segments share their end points, which would otherwise be found twice.
+        crossings.dedup_by(|b, a| (b.2 - a.2).abs() < EPSILON && (b.0 as f64 + b.1 - a.0 as f64 - a.1).abs() < EPSILON);
+        crossings
+    }
+
+    fn get_intersection(&self, segment: usize, s: f64, t: f64, ray: &Ray) -> Intersection {
+        let (ref radius, ref height) = self.segments[segment];
+        let location = ray.origin + ray.direction * t;
+        let phi = phi_for_point(&location);
+        let derivative = |c: &[f64; 4], s: f64| c[1] + s * (2f64 * c[2] + s * 3f64 * c[3]);
+        // Control points doubled up at an end leave the derivative there zero, so look just inside instead.
+        let (mut dr, mut dy) = (derivative(radius, s), derivative(height, s));
+        if dr == 0f64 && dy == 0f64 {
+            let inside = if s < 0.5f64 { s + 1e-6f64 } else { s - 1e-6f64 };
+            dr = derivative(radius, inside);
+            dy = derivative(height, inside);
+        }
+        let segments = self.segments.len() as f64;
+        Intersection {
+            distance: t,
+            location,
+            geometry: IntersectionGeometry::new(
+                phi_tangent(phi, evaluate_polynomial(radius, s).abs(), TWO_PI),
+                Vec3::new(dr * phi.cos(), dy, -dr * phi.sin()) * segments,
+            ),
+            shading_geometry: None,
+            uv: Uv(phi / TWO_PI, (segment as f64 + s) / segments),
+            material: None,
+        }
+    }
+}
+
+impl Geometry for Revolution {
+    fn bound(&self) -> BoundingBox {
+        self.bound.clone()
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+        self.crossings(ray).into_iter()
+            .find(|&(_, _, t)| t >= ray.t_min && t <= ray.t_max)
+            .map(|(segment, s, t)| self.get_intersection(segment, s, t, ray))
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<RayInterval> {
+        let crossings = self.crossings(ray).into_iter()
+            .map(|(segment, s, t)| self.get_intersection(segment, s, t, ray))
+            .collect();
+        intervals_from_crossings(crossings, &ray.direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A straight profile from (1, -1) to (1, 1) is a unit cylinder.
+    fn cylinder() -> Revolution {
+        Revolution::new(CubicBezier::chain_from_points(&[
+            Point::new(1f64, -1f64, 0f64),
+            Point::new(1f64, -1f64 / 3f64, 0f64),
+            Point::new(1f64, 1f64 / 3f64, 0f64),
+            Point::new(1f64, 1f64, 0f64),
+        ]))
+    }
+
+    // A double cone through (0, -1), (1, 0) and (0, 1), in two segments.
+    fn spindle() -> Revolution {
+        Revolution::new(CubicBezier::chain_from_points(&[
+            Point::new(0f64, -1f64, 0f64),
+            Point::new(1f64 / 3f64, -2f64 / 3f64, 0f64),
+            Point::new(2f64 / 3f64, -1f64 / 3f64, 0f64),
+            Point::new(1f64, 0f64, 0f64),
+            Point::new(2f64 / 3f64, 1f64 / 3f64, 0f64),
+            Point::new(1f64 / 3f64, 2f64 / 3f64, 0f64),
+            Point::new(0f64, 1f64, 0f64),
+        ]))
+    }
+
+    #[test]
+    fn it_should_intersect_horizontally_like_a_cylinder() {
+        let r = Ray::half_infinite(Point::new(0f64, 0.5f64, -5f64), Vec3::Z_AXIS);
+        let i = cylinder().intersect(&r).unwrap();
+        assert!((i.distance - 4f64).abs() < 1e-9);
+        assert!(i.geometry.normal.as_normalized().z < -1f64 + 1e-9);
+        assert!((i.uv.1 - 0.75f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_should_intersect_oblique_rays() {
+        let r = Ray::half_infinite(Point::new(0f64, -2.5f64, -3f64), Vec3::new(0f64, 1f64, 1f64).into_normalized());
+        let i = cylinder().intersect(&r).unwrap();
+        assert!((i.location.z + 1f64).abs() < 1e-9);
+        assert!((i.location.y + 0.5f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_should_see_down_the_open_ends() {
+        let r = Ray::half_infinite(Point::new(0f64, 5f64, 0f64), -Vec3::Y_AXIS);
+        assert!(cylinder().intersect(&r).is_none());
+    }
+
+    #[test]
+    fn it_should_intersect_vertical_rays_near_the_tip() {
+        let r = Ray::half_infinite(Point::new(0.1f64, 5f64, 0f64), -Vec3::Y_AXIS);
+        let i = spindle().intersect(&r).unwrap();
+        assert!((i.distance - 4.1f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_should_put_normals_to_the_right_of_the_profile() {
+        let r = Ray::half_infinite(Point::new(5f64, 0.5f64, 0f64), -Vec3::X_AXIS);
+        let i = spindle().intersect(&r).unwrap();
+        assert!((i.distance - 4.5f64).abs() < 1e-9);
+        let expected = Vec3::new(1f64, 1f64, 0f64).as_normalized();
+        assert!(i.geometry.normal.as_normalized().dot(&expected) > 1f64 - 1e-9);
+    }
+
+    #[test]
+    fn it_should_compute_intervals_through_a_closed_profile() {
+        let r = Ray::half_infinite(Point::new(-5f64, 0.25f64, 0f64), Vec3::X_AXIS);
+        let intervals = spindle().intervals(&r);
+        assert_eq!(intervals.len(), 1);
+        assert!((intervals[0].start() - 4.25f64).abs() < 1e-9);
+        assert!((intervals[0].end() - 5.75f64).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,141 @@
+use core::*;
+use math::*;
+
+// Geometry defined by a distance field, found by sphere tracing (Hart 1996): step along the ray by the
+// distance to the nearest surface, which can't overshoot, until that distance is tiny. The field is only
+// consulted inside the user-supplied bound, which also keeps rays that just miss from marching forever.
+#[derive(Debug)]
+pub struct Sdf {
+    field: Box<DistanceField>,
+    bound: BoundingBox,
+    // How close counts as a hit, and the step for the finite-difference normals.
+    precision: f64,
+    max_steps: usize,
+    // Scales each step; set it below 1 for fields that overestimate distances, like twists and fractals.
+    step_scale: f64,
+}
+
+impl Sdf {
+    pub fn new(field: Box<DistanceField>, bound: BoundingBox, precision: f64, max_steps: usize, step_scale: f64) -> Sdf {
+        assert!(bound.is_bounded(), "distance fields need a finite bound");
+        Sdf { field, bound, precision, max_steps, step_scale }
+    }
+
+    // Central differences.
+    fn gradient(&self, p: Point) -> Vec3 {
+        let h = self.precision;
+        let difference = |offset: Vec3| self.field.distance(p + offset) - self.field.distance(p - offset);
+        Vec3::new(
+            difference(Vec3::new(h, 0f64, 0f64)),
+            difference(Vec3::new(0f64, h, 0f64)),
+            difference(Vec3::new(0f64, 0f64, h)),
+        ) / (2f64 * h)
+    }
+
+    fn get_intersection(&self, t: f64, ray: &Ray) -> Intersection {
+        let location = ray.at(t);
+        let gradient = self.gradient(location);
+        let normal = if gradient.magnitude2() > 0f64 { gradient } else { -ray.direction };
+        // There's no natural parameterization, so map UVs like a sphere around the bound's center.
+        let center = self.bound.min + (self.bound.max - self.bound.min) / 2f64;
+        let from_center = location - center;
+        let uv = if from_center.magnitude2() > 0f64 {
+            sphere_uv_for_normalized_point(from_center.as_normalized().into_point())
+        } else {
+            Uv(0f64, 0f64)
+        };
+
+        Intersection {
+            distance: t,
+            location,
+            geometry: IntersectionGeometry::from_normal(normal),
+            shading_geometry: None,
+            uv,
+            material: None,
+        }
+    }
+}
+
+impl Geometry for Sdf {
+    fn bound(&self) -> BoundingBox {
+        self.bound.clone()
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+        let (t0, t1) = self.bound.intersect(ray)?;
+        // Object-space rays aren't necessarily normalized, so distances need converting into t.
+        let speed = ray.direction.magnitude();
+        let at = |t: f64| ray.origin + ray.direction * t;
+
+        // March on whichever side of the surface the ray starts on, so that e.g. refracted rays can find
+        // their way back out. Rays starting on the surface (within precision) count as being on the side
+        // they're heading towards, and have to get clear of it before anything counts as a hit.
+        let initial = self.field.distance(at(t0));
+        let side = if initial.abs() < self.precision {
+            if self.gradient(at(t0)).dot(&ray.direction) >= 0f64 { 1f64 } else { -1f64 }
+        } else {
+            initial.signum()
+        };
+        let mut is_clear = initial.abs() >= self.precision;
+
+        let mut t = t0;
+        for _ in 0..self.max_steps {
+            let distance = side * self.field.distance(at(t));
+            if is_clear && distance < self.precision {
+                return Some(self.get_intersection(t, ray));
+            }
+            is_clear = is_clear || distance >= self.precision;
+            t += distance.max(self.precision) * self.step_scale / speed;
+            if t > t1 {
+                break;
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use distance_field::*;
+
+    fn unit_sphere() -> Sdf {
+        Sdf::new(
+            Box::new(SphereField { radius: 1f64 }),
+            BoundingBox { min: Point::uniform(-1.5f64), max: Point::uniform(1.5f64) },
+            1e-6f64,
+            256,
+            1f64,
+        )
+    }
+
+    #[test]
+    fn it_should_intersect_from_outside() {
+        let r = Ray::half_infinite(Point::new(0f64, 0f64, -5f64), Vec3::Z_AXIS);
+        let i = unit_sphere().intersect(&r).unwrap();
+        assert!((i.distance - 4f64).abs() < 1e-5);
+        assert!(i.geometry.normal.as_normalized().z < -1f64 + 1e-6);
+    }
+
+    #[test]
+    fn it_should_not_intersect_a_miss() {
+        let r = Ray::half_infinite(Point::new(1.2f64, 0f64, -5f64), Vec3::Z_AXIS);
+        assert!(unit_sphere().intersect(&r).is_none());
+    }
+
+    #[test]
+    fn it_should_leave_the_surface_it_starts_on() {
+        let leaving = Ray::finite(Point::new(0f64, 0f64, -1f64), -Vec3::Z_AXIS, EPSILON, f64::INFINITY);
+        assert!(unit_sphere().intersect(&leaving).is_none());
+        let entering = Ray::finite(Point::new(0f64, 0f64, -1f64), Vec3::Z_AXIS, EPSILON, f64::INFINITY);
+        assert!((unit_sphere().intersect(&entering).unwrap().distance - 2f64).abs() < 1e-5);
+    }
+
+    #[test]
+    fn it_should_intersect_from_inside() {
+        let r = Ray::half_infinite(Point::new(0f64, 0f64, 0f64), Vec3::X_AXIS);
+        let i = unit_sphere().intersect(&r).unwrap();
+        assert!((i.distance - 1f64).abs() < 1e-5);
+        assert!(i.geometry.normal.as_normalized().x > 1f64 - 1e-6);
+    }
+}
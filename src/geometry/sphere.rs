@@ -1,42 +1,79 @@
-use std::f64::consts::PI;
 use core::*;
 use math::*;
+use super::quadric::*;
 
 #[derive(Debug)]
 pub struct Sphere {
     radius: f64,
+    y_min: f64,
+    y_max: f64,
+    phi_max: f64,
 }
 
 impl Sphere {
     pub fn new(radius: f64) -> Sphere {
+        Sphere::partial(radius, -radius, radius, TWO_PI)
+    }
+
+    // pbrt pg. 115. The sphere is clipped to the y range and swept only as far around +y as phi_max.
+    pub fn partial(radius: f64, y_min: f64, y_max: f64, phi_max: f64) -> Sphere {
+        let (y_min, y_max) = (y_min.min(y_max).max(-radius), y_min.max(y_max).min(radius));
         Sphere {
             radius,
+            y_min,
+            y_max,
+            phi_max: phi_max.max(0f64).min(TWO_PI),
         }
     }
 
-    fn get_intersection(&self, t: f64, ray: &Ray) -> Intersection {
-        const TWO_PI: f64 = PI * 2f64;
+    fn is_whole(&self) -> bool {
+        self.y_min <= -self.radius && self.y_max >= self.radius && self.phi_max >= TWO_PI
+    }
+
+    fn latitude(&self, y: f64) -> f64 {
+        (y / self.radius).max(-1f64).min(1f64).asin()
+    }
+}
+
+impl Quadric for Sphere {
+    fn solve(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let (a, b, c) = (
+            ray.direction.magnitude2(),
+            2f64 * (ray.direction.dot(&ray.origin)),
+            ray.origin.dot(&ray.origin) - self.radius * self.radius
+        );
+        quadratic(a, b, c)
+    }
+
+    fn contains(&self, location: &Point) -> bool {
+        self.is_whole() || (
+            location.y >= self.y_min &&
+            location.y <= self.y_max &&
+            phi_for_point(location) <= self.phi_max
+        )
+    }
 
+    fn get_intersection(&self, t: f64, ray: &Ray) -> Intersection {
         let location = ray.at(t);
-        let uv = sphere_uv_for_normalized_point(location / self.radius);
-        let (phi, theta) = sphere_uv_to_polar(uv);
+        // For a whole sphere, this is the same as sphere_uv_for_normalized_point, with v running from the
+        // bottom (v = 0) to the top (v = 1).
+        let phi = phi_for_point(&location);
+        let (latitude_min, latitude_max) = (self.latitude(self.y_min), self.latitude(self.y_max));
+        let latitude = self.latitude(location.y);
+        let uv = Uv(phi / self.phi_max, (latitude - latitude_min) / (latitude_max - latitude_min));
         // pbrt pg. 121, tweaked for different u/v formula (y/z swapped) and right-handedness (signs changed).
-        let u_axis = Vec3::new(
-            // The factor of TWO_PI means that the range [0, 1] for u will trace out the entire
-            // latitutde line (corresponding to v) exactly. If it were normalized, it'd require
-            // a range of [0, 2 * PI * radius-at-the-latitude] to do so.
-            TWO_PI * location.z,
-            0f64,
-            -TWO_PI * location.x,
-        );
+        // The factor of phi_max means that the range [0, 1] for u will trace out the entire latitude line
+        // (corresponding to v) exactly. If it were normalized, it'd require a range of
+        // [0, phi_max * radius-at-the-latitude] to do so.
+        let u_axis = phi_tangent(phi, (location.x * location.x + location.z * location.z).sqrt(), self.phi_max);
         let v_axis = Vec3::new(
-            // This has a factor of PI for the same reason u_axis has a factor of TWO_PI.
+            // This has a factor of the latitude range for the same reason u_axis has a factor of phi_max.
             // pbrt uses some clever equivalencies to compute the sin/cos here without referring to
             // phi, but whatever. This is more straightforward.
-            -PI * location.y * phi.cos(),
-            PI * self.radius * theta.sin(),
-            PI * location.y * phi.sin(),
-        );
+            -location.y * phi.cos(),
+            self.radius * latitude.cos(),
+            location.y * phi.sin(),
+        ) * (latitude_max - latitude_min);
 
         Intersection {
             distance: t,
@@ -49,53 +86,31 @@ impl Sphere {
     }
 }
 
-// pbrt pg. 118
-fn quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
-    let d = b * b - 4f64 * a * c;
-    if d < 0f64 {
-        None
-    } else {
-        let sqrt_d = d.sqrt();
-        let q = -0.5f64 * (b + (if b < 0f64 { -sqrt_d } else { sqrt_d }));
-        let (t0, t1) = (q / a, c / q);
-        if t0 > t1 {
-            Some((t1, t0))
-        } else {
-            Some((t0, t1))
-        }
-    }
-}
-
 impl Geometry for Sphere {
     fn bound(&self) -> BoundingBox {
         BoundingBox {
-            min: Point::uniform(-self.radius),
-            max: Point::uniform(self.radius),
+            min: Point::new(-self.radius, self.y_min, -self.radius),
+            max: Point::new(self.radius, self.y_max, self.radius),
         }
     }
 
     fn intersect(&self, ray: &Ray) -> Option<Intersection> {
-        let (a, b, c) = (
-            ray.direction.magnitude2(),
-            2f64 * (ray.direction.dot(&ray.origin)),
-            ray.origin.dot(&ray.origin) - self.radius * self.radius
-        );
+        intersect_quadric(self, ray)
+    }
 
-        match quadratic(a, b, c) {
+    fn intervals(&self, ray: &Ray) -> Vec<RayInterval> {
+        if !self.is_whole() {
+            return quadric_intervals(self, ray);
+        }
+        match self.solve(ray) {
             Some((t0, t1)) => {
-                if t1 < ray.t_min || t0 > ray.t_max {
-                    None
-                } else if t0 < ray.t_min {
-                    if t1 <= ray.t_max {
-                        Some(self.get_intersection(t1, &ray))
-                    } else {
-                        None
-                    }
-                } else {
-                    Some(self.get_intersection(t0, &ray))
-                }
+                let line = Ray { t_min: t0, t_max: t1, ..ray.clone() };
+                vec![RayInterval {
+                    enter: Some(self.get_intersection(t0, &line)),
+                    exit: Some(self.get_intersection(t1, &line)),
+                }]
             }
-            None => None,
+            None => vec![],
         }
     }
 }
@@ -104,7 +119,7 @@ impl Geometry for Sphere {
 mod tests {
     use super::*;
 
-    const UNIT_SPHERE: Sphere = Sphere { radius: 1f64 };
+    const UNIT_SPHERE: Sphere = Sphere { radius: 1f64, y_min: -1f64, y_max: 1f64, phi_max: TWO_PI };
 
     #[test]
     fn it_should_intersect_a_half_infinite_ray_from_outside() {
@@ -147,4 +162,31 @@ mod tests {
         let r = Ray::finite(Point::new(0f64, 0f64, 0f64), Vec3::Z_AXIS, 0f64, 0.5f64);
         assert!(UNIT_SPHERE.intersect(&r).is_none());
     }
+
+    #[test]
+    fn it_should_see_through_the_clipped_cap_of_a_partial_sphere() {
+        let bowl = Sphere::partial(1f64, -1f64, 0.5f64, TWO_PI);
+        let r = Ray::half_infinite(Point::new(0f64, 5f64, 0f64), -Vec3::Y_AXIS);
+        assert_eq!(bowl.intersect(&r).unwrap().distance, 6f64);
+    }
+
+    #[test]
+    fn it_should_map_uvs_onto_the_partial_extent() {
+        let quarter = Sphere::partial(1f64, 0f64, 1f64, TWO_PI / 4f64);
+        // phi = PI / 4 is halfway between +x and -z.
+        let target = Point::new(0.5f64.sqrt(), 0f64, -0.5f64.sqrt()) * 0.999f64;
+        let r = Ray::half_infinite(Point::new(target.x, 5f64, target.z), -Vec3::Y_AXIS);
+        let i = quarter.intersect(&r).unwrap();
+        assert!((i.uv.0 - 0.5f64).abs() < 1e-9);
+        assert!((i.uv.1 - i.location.y.asin() / (TWO_PI / 4f64)).abs() < 1e-9);
+        assert!(quarter.intersect(&Ray::half_infinite(Point::new(0.1f64, 5f64, 0.1f64), -Vec3::Y_AXIS)).is_none());
+    }
+
+    #[test]
+    fn it_should_match_the_whole_sphere_uv_mapping() {
+        let r = Ray::half_infinite(Point::new(0.3f64, 0.4f64, -5f64), Vec3::Z_AXIS);
+        let i = UNIT_SPHERE.intersect(&r).unwrap();
+        let expected = sphere_uv_for_normalized_point(i.location);
+        assert!((i.uv.0 - expected.0).abs() < 1e-9 && (i.uv.1 - expected.1).abs() < 1e-9);
+    }
 }
@@ -0,0 +1,156 @@
+use core::*;
+use math::*;
+use super::quadric::*;
+
+// A torus around +y, centered on the origin. Like the quadrics, u goes around +y (matching Sphere's u);
+// v goes around the tube, starting from its outermost edge and heading up.
+#[derive(Debug)]
+pub struct Torus {
+    major_radius: f64,
+    minor_radius: f64,
+}
+
+impl Torus {
+    pub fn new(major_radius: f64, minor_radius: f64) -> Torus {
+        Torus {
+            major_radius,
+            minor_radius,
+        }
+    }
+
+    // Every time the ray's line crosses the torus, in order, ignoring the ray's bounds.
+    fn solve(&self, ray: &Ray) -> Vec<f64> {
+        let (d, o) = (&ray.direction, ray.origin);
+        let d2 = d.magnitude2();
+        // The quartic's coefficients grow with the fourth power of the distance to the origin, which loses
+        // precision fast. Solving from the point on the line closest to the origin keeps them small.
+        let t_closest = -d.dot(&o) / d2;
+        let o = o + *d * t_closest;
+
+        let (r2, minor_r2) = (self.major_radius * self.major_radius, self.minor_radius * self.minor_radius);
+        let o_dot_d = d.dot(&o);
+        let k = o.x * o.x + o.y * o.y + o.z * o.z + r2 - minor_r2;
+        // (|o + td|^2 + R^2 - r^2)^2 = 4R^2 ((o.x + t d.x)^2 + (o.z + t d.z)^2)
+        quartic(
+            d2 * d2,
+            4f64 * d2 * o_dot_d,
+            4f64 * o_dot_d * o_dot_d + 2f64 * d2 * k - 4f64 * r2 * (d.x * d.x + d.z * d.z),
+            4f64 * o_dot_d * k - 8f64 * r2 * (o.x * d.x + o.z * d.z),
+            k * k - 4f64 * r2 * (o.x * o.x + o.z * o.z),
+        ).into_iter().map(|t| t + t_closest).collect()
+    }
+
+    fn get_intersection(&self, t: f64, ray: &Ray) -> Intersection {
+        let location = ray.at(t);
+        let phi = phi_for_point(&location);
+        let distance_from_axis = (location.x * location.x + location.z * location.z).sqrt();
+        let theta = {
+            let theta = location.y.atan2(distance_from_axis - self.major_radius);
+            if theta < 0f64 { theta + TWO_PI } else { theta }
+        };
+        // As with Sphere, the factors of 2 PI make [0, 1] in u and v trace out the whole torus.
+        let (sin_theta, cos_theta) = theta.sin_cos();
+        let v_axis = Vec3::new(
+            -self.minor_radius * sin_theta * phi.cos(),
+            self.minor_radius * cos_theta,
+            self.minor_radius * sin_theta * phi.sin(),
+        ) * TWO_PI;
+
+        Intersection {
+            distance: t,
+            location,
+            geometry: IntersectionGeometry::new(phi_tangent(phi, distance_from_axis, TWO_PI), v_axis),
+            shading_geometry: None,
+            uv: Uv(phi / TWO_PI, theta / TWO_PI),
+            material: None,
+        }
+    }
+}
+
+impl Geometry for Torus {
+    fn bound(&self) -> BoundingBox {
+        let outer = self.major_radius + self.minor_radius;
+        BoundingBox {
+            min: Point::new(-outer, -self.minor_radius, -outer),
+            max: Point::new(outer, self.minor_radius, outer),
+        }
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+        self.solve(ray).into_iter()
+            .find(|&t| t >= ray.t_min && t <= ray.t_max)
+            .map(|t| self.get_intersection(t, ray))
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<RayInterval> {
+        let roots = self.solve(ray);
+        if roots.is_empty() {
+            return vec![];
+        }
+        let line = Ray { t_min: roots[0], t_max: roots[roots.len() - 1], ..ray.clone() };
+        let mut crossings = roots.iter().map(|&t| self.get_intersection(t, &line));
+        if roots.len() % 2 == 0 {
+            // The torus is closed, so the crossings alternate between entering and exiting.
+            let mut intervals = vec![];
+            while let (Some(enter), Some(exit)) = (crossings.next(), crossings.next()) {
+                intervals.push(RayInterval { enter: Some(enter), exit: Some(exit) });
+            }
+            intervals
+        } else {
+            let crossings = crossings.collect();
+            // A grazing hit can produce an unpaired root; let the normals sort it out.
+            intervals_from_crossings(crossings, &ray.direction)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ring() -> Torus {
+        Torus::new(2f64, 0.5f64)
+    }
+
+    #[test]
+    fn it_should_intersect_the_outside_of_the_tube() {
+        let r = Ray::half_infinite(Point::new(0f64, 0f64, -5f64), Vec3::Z_AXIS);
+        let i = ring().intersect(&r).unwrap();
+        assert!((i.distance - 2.5f64).abs() < 1e-9);
+        assert!(i.geometry.normal.as_normalized().dot(&Vec3::new(0f64, 0f64, -1f64)) > 1f64 - 1e-9);
+        assert!(i.uv.1.abs() < 1e-9 || (i.uv.1 - 1f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_should_pass_through_the_hole() {
+        let r = Ray::half_infinite(Point::new(0f64, 5f64, 0f64), -Vec3::Y_AXIS);
+        assert!(ring().intersect(&r).is_none());
+    }
+
+    #[test]
+    fn it_should_intersect_the_inside_of_the_tube_from_the_hole() {
+        let r = Ray::half_infinite(Point::new(0f64, 0f64, 0f64), Vec3::X_AXIS);
+        let i = ring().intersect(&r).unwrap();
+        assert!((i.distance - 1.5f64).abs() < 1e-9);
+        // The normal points out of the tube, back towards the hole.
+        assert!(i.geometry.normal.as_normalized().x < -1f64 + 1e-9);
+        assert!((i.uv.1 - 0.5f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_should_intersect_accurately_from_far_away() {
+        let r = Ray::half_infinite(Point::new(0f64, 0.25f64, -1e5f64), Vec3::Z_AXIS);
+        let i = ring().intersect(&r).unwrap();
+        let expected = 1e5f64 - 2f64 - (0.25f64 - 0.0625f64).sqrt();
+        assert!((i.distance - expected).abs() < 1e-6);
+    }
+
+    #[test]
+    fn it_should_produce_one_interval_per_side_of_the_tube() {
+        let r = Ray::half_infinite(Point::new(-5f64, 0f64, 0f64), Vec3::X_AXIS);
+        let intervals = ring().intervals(&r);
+        assert_eq!(intervals.len(), 2);
+        assert!((intervals[0].start() - 2.5f64).abs() < 1e-9 && (intervals[0].end() - 3.5f64).abs() < 1e-9);
+        assert!((intervals[1].start() - 6.5f64).abs() < 1e-9 && (intervals[1].end() - 7.5f64).abs() < 1e-9);
+    }
+}
@@ -11,12 +11,22 @@ pub enum Smoothing {
     Explicit(Vec<Normal>),
 }
 
+// A vertex's direction of increasing u, perpendicular to its normal. v increases along normal cross tangent
+// times the sign, which is negative where the uvs are mirrored.
+#[derive(Debug, Clone, Copy)]
+pub struct Tangent {
+    pub direction: Vec3,
+    pub sign: f64,
+}
+
 #[derive(Debug)]
 pub struct TriangleMeshData {
     pub positions: Vec<Point>,
     pub indices: Vec<TriangleIndices>,
     pub normals: Option<Vec<Normal>>,
     pub uvs: Option<Vec<Uv>>,
+    // Only from generate_tangents, since anything that moves vertices around would invalidate them.
+    pub tangents: Option<Vec<Tangent>>,
 }
 
 pub type TriangleMesh = VolumeKdTree<Triangle>;
@@ -37,7 +47,7 @@ impl TriangleMeshData {
             assert_eq!(positions.len(), uvs.as_ref().unwrap().len());
         }
 
-        TriangleMeshData { positions, indices: indices.clone(), normals, uvs }
+        TriangleMeshData { positions, indices: indices.clone(), normals, uvs, tangents: None }
     }
 
     pub fn into_triangle_mesh(self) -> TriangleMesh {
@@ -131,12 +141,24 @@ impl Geometry for Triangle {
             let (n0, n1, n2) = (normals[i0], normals[i1], normals[i2]);
             // Blergh, can't overload .cross, so we have to convert the normal into a vector.
             let shading_normal = (n0 * b0 + n1 * b1 + n2 * b2).into_vector().into_normalized();
-            let intermediate_u_axis = u_axis.into_normalized();
-            let shading_v_axis = shading_normal.cross(intermediate_u_axis).into_normalized();
-            IntersectionGeometry::new(
-                shading_v_axis.cross(shading_normal),
-                shading_v_axis,
-            )
+            // Tangents keep the shading frame lined up across triangles, rather than following each one's uvs.
+            // Either way, v runs against normal cross u where the uvs are mirrored.
+            let (intermediate_u_axis, sign) = match self.mesh.tangents {
+                Some(ref tangents) => {
+                    let (t0, t1, t2) = (tangents[i0], tangents[i1], tangents[i2]);
+                    let tangent = t0.direction * b0 + t1.direction * b1 + t2.direction * b2;
+                    let sign = if t0.sign * b0 + t1.sign * b1 + t2.sign * b2 < 0f64 { -1f64 } else { 1f64 };
+                    (if tangent.magnitude2() > 0f64 { tangent.into_normalized() } else { u_axis.into_normalized() }, sign)
+                }
+                None => (u_axis.into_normalized(), if shading_normal.cross(u_axis).dot(&v_axis) < 0f64 { -1f64 } else { 1f64 }),
+            };
+            let bitangent = shading_normal.cross(intermediate_u_axis).into_normalized();
+            // The normal is given rather than derived from the axes, since they're left-handed when mirrored.
+            IntersectionGeometry {
+                normal: shading_normal.into_normal(),
+                u_axis: bitangent.cross(shading_normal),
+                v_axis: bitangent * sign,
+            }
         });
 
         Some(Intersection {
@@ -153,6 +175,7 @@ impl Geometry for Triangle {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use geometry::generate_tangents;
 
     lazy_static! {
         static ref SINGLE_TRIANGLE: TriangleMesh = TriangleMeshData::new(
@@ -190,4 +213,25 @@ mod tests {
         let r = Ray::finite(Point::new(0f64, 0f64, -5f64), Vec3::Z_AXIS, 0f64, 1f64);
         assert!(SINGLE_TRIANGLE.intersect(&r).is_none());
     }
+
+    // SINGLE_TRIANGLE, smooth, with v increasing down the screen.
+    fn mirrored_triangle() -> TriangleMeshData {
+        TriangleMeshData::new(
+            vec![Point::new(-1f64, -1f64, 0f64), Point::new(1f64, -1f64, 0f64), Point::new(0f64, 1f64, 0f64)],
+            Smoothing::Explicit(vec![Vec3::Z_AXIS.into_normal(); 3]),
+            Some(vec![Uv(0f64, 1f64), Uv(1f64, 1f64), Uv(0.5f64, 0f64)]),
+            vec![(0, 1, 2)],
+        )
+    }
+
+    #[test]
+    fn it_should_point_the_shading_v_axis_along_mirrored_uvs() {
+        let r = Ray::half_infinite(Point::new(0f64, 0f64, -3f64), Vec3::Z_AXIS);
+        for mesh in vec![mirrored_triangle(), generate_tangents(mirrored_triangle())] {
+            let shading_geometry = mesh.into_triangle_mesh().intersect(&r).unwrap().shading_geometry.unwrap();
+            assert!((shading_geometry.normal.into_vector() - Vec3::Z_AXIS).magnitude() < 1e-9);
+            assert!((shading_geometry.u_axis - Vec3::X_AXIS).magnitude() < 1e-9);
+            assert!((shading_geometry.v_axis + Vec3::Y_AXIS).magnitude() < 1e-9);
+        }
+    }
 }
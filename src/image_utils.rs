@@ -1,5 +1,6 @@
 use std::path::Path;
 use image::{ RgbImage, open as openImage };
+use core::*;
 
 pub fn load_image(path: &Path) -> RgbImage {
     match openImage(path) {
@@ -7,3 +8,25 @@ pub fn load_image(path: &Path) -> RgbImage {
         Err(reason) => { panic!("could not open image at {:?}: {:?}", path, reason); }
     }
 }
+
+// The image's brightness from 0 to 1, by row from the top, e.g. for heightfields.
+pub fn image_to_heights(image: &RgbImage) -> Vec<Vec<f64>> {
+    (0..image.height())
+        .map(|y| (0..image.width())
+            .map(|x| {
+                let pixel = image.get_pixel(x, y);
+                (pixel[0] as f64 + pixel[1] as f64 + pixel[2] as f64) / (3f64 * 255f64)
+            })
+            .collect())
+        .collect()
+}
+
+// Bakes a color into an image, since textures can't be scaled.
+pub fn scale_image(mut image: RgbImage, scale: Color) -> RgbImage {
+    for pixel in image.pixels_mut() {
+        pixel.data[0] = (pixel.data[0] as f64 * scale.r.min(1f64)) as u8;
+        pixel.data[1] = (pixel.data[1] as f64 * scale.g.min(1f64)) as u8;
+        pixel.data[2] = (pixel.data[2] as f64 * scale.b.min(1f64)) as u8;
+    }
+    image
+}
@@ -0,0 +1,54 @@
+use std::path::Path;
+use std::str::FromStr;
+use math::*;
+use file_utils::*;
+
+// Reads patches in the format the Utah teapot is usually distributed in: the number of patches, then each
+// patch's 16 control point indices (1-based, rows of constant v first), then the number of control points
+// and each one's x, y and z. Values can be separated by commas and/or whitespace.
+pub fn load_bezier_patches(path: &Path) -> Vec<BicubicBezier> {
+    parse_bezier_patches(&strip_comments(read_file_contents(path)))
+}
+
+fn parse_bezier_patches(source: &str) -> Vec<BicubicBezier> {
+    let mut values = source.split(|c: char| c == ',' || c.is_whitespace()).filter(|value| !value.is_empty());
+    let mut next = |what: &str| values.next().expect(&format!("bezier patch file ended early, looking for {}", what));
+    let mut next_usize = |what: &str| usize::from_str(next(what)).expect(&format!("bezier patch file has a bad {}", what));
+
+    let patch_count = next_usize("the number of patches");
+    let patch_indices: Vec<Vec<usize>> = (0..patch_count)
+        .map(|_| (0..16).map(|_| next_usize("control point index")).collect())
+        .collect();
+    let point_count = next_usize("the number of control points");
+    let mut next_f64 = |what: &str| f64::from_str(next(what)).expect(&format!("bezier patch file has a bad {}", what));
+    let points: Vec<Point> = (0..point_count)
+        .map(|_| Point::new(next_f64("coordinate"), next_f64("coordinate"), next_f64("coordinate")))
+        .collect();
+
+    patch_indices.iter()
+        .map(|indices| {
+            let mut control_points = [Point::uniform(0f64); 16];
+            for (control_point, &index) in control_points.iter_mut().zip(indices) {
+                assert!(index >= 1 && index <= point_count, "bezier patch control point index {} is out of range", index);
+                *control_point = points[index - 1];
+            }
+            BicubicBezier { control_points }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_parse_patches() {
+        let indices: Vec<String> = (1..17).rev().map(|i| i.to_string()).collect();
+        let points: Vec<String> = (0..16).map(|i| format!("{}, 0.5,{}", i, -i)).collect();
+        let source = format!("1\n{}\n16\n{}\n", indices.join(","), points.join("\n"));
+        let patches = parse_bezier_patches(&source);
+        assert_eq!(patches.len(), 1);
+        let first = patches[0].control_points[0];
+        assert_eq!((first.x, first.y, first.z), (15f64, 0.5f64, -15f64));
+    }
+}
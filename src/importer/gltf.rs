@@ -0,0 +1,605 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::str;
+use std::sync::Arc;
+use image::{ RgbImage, load_from_memory };
+use json::{ self, JsonValue };
+use core::*;
+use math::*;
+use geometry::*;
+use material::*;
+use file_utils::*;
+use image_utils::*;
+use importer::scene_builder::*;
+
+// Reads a glTF 2.0 scene, either as JSON with its buffers and images in other files (or data URIs), or
+// packed into a single binary .glb. Brings in its meshes, with their node transforms and materials, plus
+// its first camera and any KHR_lights_punctual lights.
+//
+// Our materials are much simpler than glTF's metallic-roughness model, so they're approximated: metals
+// become mirrors that fade out as they get rougher, since there's no glossy reflection, and transmission
+// (or blended alpha) becomes perfect specular transmission.
+pub fn load_gltf(path: &Path, processing: &MeshProcessing) -> ImportedScene {
+    let bytes = read_file_bytes(path);
+    let directory = path.parent().expect("gltf file has no parent directory");
+    let (document, binary) = if bytes.starts_with(b"glTF") {
+        parse_glb(&bytes)
+    } else {
+        (str::from_utf8(&bytes).expect("gltf file is not text").to_owned(), None)
+    };
+    let document = json::parse(&document).expect("gltf file is not valid JSON");
+    GltfImporter::new(document, binary, directory, processing).import()
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    assert!(offset + 4 <= bytes.len(), "glb file ended early");
+    let mut raw = [0u8; 4];
+    raw.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(raw)
+}
+
+// A .glb is a 12 byte header, then a JSON chunk and optionally a binary chunk, each with their length and
+// type first.
+fn parse_glb(bytes: &[u8]) -> (String, Option<Vec<u8>>) {
+    const JSON_CHUNK: u32 = 0x4E4F534A;
+    const BINARY_CHUNK: u32 = 0x004E4942;
+    assert_eq!(read_u32(bytes, 4), 2, "only version 2 glb files are supported");
+    let (mut document, mut binary) = (None, None);
+    let mut offset = 12;
+    while offset + 8 <= bytes.len() {
+        let (length, chunk_type) = (read_u32(bytes, offset) as usize, read_u32(bytes, offset + 4));
+        let start = offset + 8;
+        assert!(start + length <= bytes.len(), "glb file ended early");
+        let chunk = &bytes[start..start + length];
+        match chunk_type {
+            JSON_CHUNK => document = Some(str::from_utf8(chunk).expect("glb JSON chunk is not text").to_owned()),
+            BINARY_CHUNK => binary = Some(chunk.to_vec()),
+            _ => {}
+        }
+        offset = start + length;
+    }
+    (document.expect("glb file has no JSON chunk"), binary)
+}
+
+fn decode_base64(encoded: &str) -> Vec<u8> {
+    let value = |c: u8| match c {
+        b'A'..=b'Z' => c - b'A',
+        b'a'..=b'z' => c - b'a' + 26,
+        b'0'..=b'9' => c - b'0' + 52,
+        b'+' | b'-' => 62,
+        b'/' | b'_' => 63,
+        _ => panic!("invalid base64 character \"{}\"", c as char),
+    };
+    let digits: Vec<u8> = encoded.bytes().filter(|&c| c != b'=' && !(c as char).is_whitespace()).map(value).collect();
+    let mut bytes = Vec::with_capacity(digits.len() * 3 / 4);
+    for group in digits.chunks(4) {
+        let bits = group.iter().enumerate().fold(0u32, |bits, (i, &digit)| bits | (digit as u32) << (18 - 6 * i));
+        for i in 0..group.len() - 1 {
+            bytes.push((bits >> (16 - 8 * i)) as u8);
+        }
+    }
+    bytes
+}
+
+fn get_f64(value: &JsonValue, default: f64) -> f64 {
+    value.as_f64().unwrap_or(default)
+}
+
+fn get_f64s(value: &JsonValue, default: &[f64]) -> Vec<f64> {
+    if value.is_array() {
+        value.members().map(|member| member.as_f64().expect("gltf file has a non-number in a number array")).collect()
+    } else {
+        default.to_vec()
+    }
+}
+
+fn get_index(value: &JsonValue, what: &str) -> usize {
+    value.as_usize().expect(&format!("gltf file is missing a {} index", what))
+}
+
+// glTF matrices are column-major.
+fn matrix_from_columns(values: &[f64]) -> Mat4 {
+    let mut cells = [[0f64; 4]; 4];
+    for (i, &value) in values.iter().enumerate() {
+        cells[i % 4][i / 4] = value;
+    }
+    Mat4 { cells }
+}
+
+fn quaternion_to_matrix(x: f64, y: f64, z: f64, w: f64) -> Mat4 {
+    Mat4 {
+        cells: [
+            [1f64 - 2f64 * (y * y + z * z), 2f64 * (x * y - z * w), 2f64 * (x * z + y * w), 0f64],
+            [2f64 * (x * y + z * w), 1f64 - 2f64 * (x * x + z * z), 2f64 * (y * z - x * w), 0f64],
+            [2f64 * (x * z - y * w), 2f64 * (y * z + x * w), 1f64 - 2f64 * (x * x + y * y), 0f64],
+            [0f64, 0f64, 0f64, 1f64],
+        ],
+    }
+}
+
+fn node_matrix(node: &JsonValue) -> Mat4 {
+    if node["matrix"].is_array() {
+        return matrix_from_columns(&get_f64s(&node["matrix"], &[]));
+    }
+    let t = get_f64s(&node["translation"], &[0f64, 0f64, 0f64]);
+    let r = get_f64s(&node["rotation"], &[0f64, 0f64, 0f64, 1f64]);
+    let s = get_f64s(&node["scale"], &[1f64, 1f64, 1f64]);
+    Mat4::create_translation(Vec3::new(t[0], t[1], t[2]))
+        * quaternion_to_matrix(r[0], r[1], r[2], r[3])
+        * Mat4::create_scale(Vec3::new(s[0], s[1], s[2]))
+}
+
+type Primitive = (Arc<Geometry>, Arc<Material>);
+
+struct GltfImporter<'a> {
+    document: JsonValue,
+    directory: &'a Path,
+    processing: &'a MeshProcessing,
+    buffers: Vec<Vec<u8>>,
+    images: HashMap<usize, RgbImage>,
+    // Keyed by index, with None for the default material that primitives without one get.
+    materials: HashMap<Option<usize>, Arc<Material>>,
+    meshes: HashMap<usize, Vec<Primitive>>,
+    scene: ImportedScene,
+}
+
+impl<'a> GltfImporter<'a> {
+    fn new(document: JsonValue, binary: Option<Vec<u8>>, directory: &'a Path, processing: &'a MeshProcessing) -> GltfImporter<'a> {
+        let mut binary = binary;
+        let buffers = document["buffers"]
+            .members()
+            .map(|buffer| match buffer["uri"].as_str() {
+                Some(uri) => GltfImporter::load_uri(uri, directory),
+                // Only the first buffer may be missing a URI, in which case it's the glb's binary chunk.
+                None => binary.take().expect("gltf buffer has no data"),
+            })
+            .collect();
+        GltfImporter {
+            document,
+            directory,
+            processing,
+            buffers,
+            images: HashMap::new(),
+            materials: HashMap::new(),
+            meshes: HashMap::new(),
+            scene: ImportedScene { objects: vec![], lights: vec![], camera: None, image_dimensions: None, background_color: None },
+        }
+    }
+
+    fn load_uri(uri: &str, directory: &Path) -> Vec<u8> {
+        if uri.starts_with("data:") {
+            let comma = uri.find(',').expect("gltf data URI has no data");
+            assert!(uri[..comma].ends_with(";base64"), "only base64 gltf data URIs are supported");
+            decode_base64(&uri[comma + 1..])
+        } else {
+            // Relative URIs might have spaces and such escaped, but that's rare enough to not bother.
+            read_file_bytes(&directory.join(uri))
+        }
+    }
+
+    fn import(mut self) -> ImportedScene {
+        let roots: Vec<usize> = {
+            let scene_index = self.document["scene"].as_usize().unwrap_or(0);
+            let scene = &self.document["scenes"][scene_index];
+            if scene["nodes"].is_array() {
+                scene["nodes"].members().map(|node| get_index(node, "node")).collect()
+            } else {
+                // Without any scenes, everything that isn't a child is a root.
+                let nodes = &self.document["nodes"];
+                let children: Vec<usize> = nodes.members().flat_map(|node| node["children"].members()).map(|child| get_index(child, "child")).collect();
+                (0..nodes.len()).filter(|i| !children.contains(i)).collect()
+            }
+        };
+        for root in roots {
+            self.import_node(root, &IDENTITY_MATRIX);
+        }
+        self.scene
+    }
+
+    fn import_node(&mut self, index: usize, parent_to_world: &Mat4) {
+        let (node_to_world, mesh, camera, light, children) = {
+            let node = &self.document["nodes"][index];
+            let children: Vec<usize> = node["children"].members().map(|child| get_index(child, "child")).collect();
+            (
+                parent_to_world * node_matrix(node),
+                node["mesh"].as_usize(),
+                node["camera"].as_usize(),
+                node["extensions"]["KHR_lights_punctual"]["light"].as_usize(),
+                children,
+            )
+        };
+        let transform = Transform::new(node_to_world.clone());
+
+        if let Some(mesh) = mesh {
+            for (geometry, material) in self.get_mesh(mesh) {
+                self.scene.objects.push(SceneObject { shape: Shape::new(geometry, transform.clone()), material: Some(material), alpha: None });
+            }
+        }
+        if let Some(camera) = camera {
+            if self.scene.camera.is_none() {
+                self.scene.camera = Some(self.camera(camera, &transform));
+            }
+        }
+        if let Some(light) = light {
+            let light = self.light(light, &transform);
+            self.scene.lights.extend(light);
+        }
+        for child in children {
+            self.import_node(child, &node_to_world);
+        }
+    }
+
+    // Cameras look down their -z with +y up.
+    fn camera(&self, index: usize, transform: &Transform) -> CameraBuilder {
+        let camera = &self.document["cameras"][index];
+        let position = Point::uniform(0f64).transform(transform);
+        let look_at = Point::new(0f64, 0f64, -1f64).transform(transform);
+        let up = Vec3::Y_AXIS.transform(transform);
+        match camera["type"].as_str() {
+            Some("orthographic") => {
+                let (x_mag, y_mag) = (get_f64(&camera["orthographic"]["xmag"], 1f64), get_f64(&camera["orthographic"]["ymag"], 1f64));
+                CameraBuilder::Orthographic((position, look_at, up, Some((x_mag * 2f64, y_mag * 2f64))))
+            }
+            _ => {
+                let perspective = &camera["perspective"];
+                let y_fov = get_f64(&perspective["yfov"], 0.8f64);
+                // Our field of view is across the narrower side of the image.
+                let fov = match perspective["aspectRatio"].as_f64() {
+                    Some(aspect) if aspect < 1f64 => 2f64 * ((y_fov / 2f64).tan() * aspect).atan(),
+                    _ => y_fov,
+                };
+                CameraBuilder::Perspective((position, look_at, up, None), fov.to_degrees())
+            }
+        }
+    }
+
+    // Lights point down their -z. Their intensities are used as-is, and spot lights are approximated by
+    // point lights since we don't have any.
+    fn light(&self, index: usize, transform: &Transform) -> Option<ImportedLight> {
+        let light = &self.document["extensions"]["KHR_lights_punctual"]["lights"][index];
+        let color = get_f64s(&light["color"], &[1f64, 1f64, 1f64]);
+        let color = Color::new(color[0], color[1], color[2]) * get_f64(&light["intensity"], 1f64);
+        match light["type"].as_str() {
+            Some("directional") => Some(ImportedLight::Directional(Vec3::new(0f64, 0f64, -1f64).transform(transform), color)),
+            Some("point") => Some(ImportedLight::Point(Point::uniform(0f64).transform(transform), color)),
+            Some("spot") => {
+                eprintln!("warning: approximating gltf spot light with a point light");
+                Some(ImportedLight::Point(Point::uniform(0f64).transform(transform), color))
+            }
+            other => {
+                eprintln!("warning: ignoring gltf light of unknown type {:?}", other);
+                None
+            }
+        }
+    }
+
+    fn get_mesh(&mut self, index: usize) -> Vec<Primitive> {
+        if let Some(primitives) = self.meshes.get(&index) {
+            return primitives.iter().map(|&(ref geometry, ref material)| (Arc::clone(geometry), Arc::clone(material))).collect();
+        }
+        let primitive_count = self.document["meshes"][index]["primitives"].len();
+        let mut primitives = Vec::<Primitive>::new();
+        for i in 0..primitive_count {
+            let (mesh, material) = {
+                let primitive = &self.document["meshes"][index]["primitives"][i];
+                (self.read_primitive(primitive), primitive["material"].as_usize())
+            };
+            if let Some(mesh) = mesh {
+                let material = self.get_material(material);
+                primitives.push((Arc::new(self.processing.apply(mesh).into_triangle_mesh()), material));
+            }
+        }
+        self.meshes.insert(index, primitives.iter().map(|&(ref geometry, ref material)| (Arc::clone(geometry), Arc::clone(material))).collect());
+        primitives
+    }
+
+    // Every element of an accessor, with its components one after another, converted to f64.
+    fn read_accessor(&self, index: usize) -> (Vec<f64>, usize) {
+        let accessor = &self.document["accessors"][index];
+        let count = get_index(&accessor["count"], "accessor count");
+        let components = match accessor["type"].as_str() {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") | Some("MAT2") => 4,
+            Some("MAT3") => 9,
+            Some("MAT4") => 16,
+            other => panic!("unknown gltf accessor type {:?}", other),
+        };
+        if accessor["sparse"].is_object() {
+            eprintln!("warning: ignoring sparse gltf accessor values");
+        }
+        let view_index = match accessor["bufferView"].as_usize() {
+            Some(view_index) => view_index,
+            // Accessors without data are all zeroes.
+            None => return (vec![0f64; count * components], components),
+        };
+
+        let component_type = accessor["componentType"].as_u32().expect("gltf accessor has no component type");
+        let size = match component_type {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            _ => panic!("unknown gltf component type {}", component_type),
+        };
+        let is_normalized = accessor["normalized"].as_bool().unwrap_or(false);
+        let view = &self.document["bufferViews"][view_index];
+        let buffer = &self.buffers[get_index(&view["buffer"], "buffer")];
+        let start = view["byteOffset"].as_usize().unwrap_or(0) + accessor["byteOffset"].as_usize().unwrap_or(0);
+        let stride = view["byteStride"].as_usize().unwrap_or(components * size);
+        assert!(count == 0 || start + (count - 1) * stride + components * size <= buffer.len(), "gltf accessor runs past the end of its buffer");
+
+        let mut values = Vec::with_capacity(count * components);
+        for i in 0..count {
+            for c in 0..components {
+                let offset = start + i * stride + c * size;
+                let b = &buffer[offset..offset + size];
+                let value = match component_type {
+                    5120 => b[0] as i8 as f64,
+                    5121 => b[0] as f64,
+                    5122 => i16::from_le_bytes([b[0], b[1]]) as f64,
+                    5123 => u16::from_le_bytes([b[0], b[1]]) as f64,
+                    5125 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    _ => f32::from_bits(u32::from_le_bytes([b[0], b[1], b[2], b[3]])) as f64,
+                };
+                values.push(if is_normalized {
+                    match component_type {
+                        5120 => (value / 127f64).max(-1f64),
+                        5121 => value / 255f64,
+                        5122 => (value / 32767f64).max(-1f64),
+                        5123 => value / 65535f64,
+                        _ => value,
+                    }
+                } else {
+                    value
+                });
+            }
+        }
+        (values, components)
+    }
+
+    fn read_primitive(&self, primitive: &JsonValue) -> Option<TriangleMeshData> {
+        let attributes = &primitive["attributes"];
+        let (positions, _) = self.read_accessor(get_index(&attributes["POSITION"], "position accessor"));
+        let positions: Vec<Point> = positions.chunks(3).map(|p| Point::new(p[0], p[1], p[2])).collect();
+        let normals = attributes["NORMAL"].as_usize().map(|index| {
+            self.read_accessor(index).0.chunks(3).map(|n| Normal::new(n[0], n[1], n[2])).collect()
+        });
+        // glTF has v going down, like images.
+        let uvs = attributes["TEXCOORD_0"].as_usize().map(|index| {
+            self.read_accessor(index).0.chunks(2).map(|uv| Uv(uv[0], 1f64 - uv[1])).collect()
+        });
+        let vertices: Vec<usize> = match primitive["indices"].as_usize() {
+            Some(index) => self.read_accessor(index).0.into_iter().map(|i| i as usize).collect(),
+            None => (0..positions.len()).collect(),
+        };
+        assert!(vertices.iter().all(|&i| i < positions.len()), "gltf index is out of range");
+
+        let triangle_count = vertices.len().saturating_sub(2);
+        let indices: Vec<TriangleIndices> = match primitive["mode"].as_u32().unwrap_or(4) {
+            4 => vertices.chunks(3).filter(|t| t.len() == 3).map(|t| (t[0], t[1], t[2])).collect(),
+            // Every other triangle in a strip is wound backwards.
+            5 => (0..triangle_count)
+                .map(|i| if i % 2 == 0 { (vertices[i], vertices[i + 1], vertices[i + 2]) } else { (vertices[i + 1], vertices[i], vertices[i + 2]) })
+                .collect(),
+            6 => (0..triangle_count).map(|i| (vertices[0], vertices[i + 1], vertices[i + 2])).collect(),
+            mode => {
+                eprintln!("warning: ignoring gltf primitive with unsupported mode {}", mode);
+                return None;
+            }
+        };
+        if indices.is_empty() {
+            return None;
+        }
+        // Without normals, glTF meshes are flat shaded.
+        let smoothing = normals.map_or(Smoothing::None, Smoothing::Explicit);
+        Some(TriangleMeshData::new(positions, smoothing, uvs, indices))
+    }
+
+    fn get_image(&mut self, index: usize) -> RgbImage {
+        if let Some(image) = self.images.get(&index) {
+            return image.clone();
+        }
+        let image = {
+            let image = &self.document["images"][index];
+            match image["uri"].as_str() {
+                Some(uri) if !uri.starts_with("data:") => load_image(&self.directory.join(uri)),
+                Some(uri) => load_from_memory(&GltfImporter::load_uri(uri, self.directory)).expect("could not decode gltf image").to_rgb(),
+                None => {
+                    let view = &self.document["bufferViews"][get_index(&image["bufferView"], "image buffer view")];
+                    let buffer = &self.buffers[get_index(&view["buffer"], "buffer")];
+                    let start = view["byteOffset"].as_usize().unwrap_or(0);
+                    let length = get_index(&view["byteLength"], "buffer view length");
+                    load_from_memory(&buffer[start..start + length]).expect("could not decode gltf image").to_rgb()
+                }
+            }
+        };
+        self.images.insert(index, image.clone());
+        image
+    }
+
+    fn get_material(&mut self, index: Option<usize>) -> Arc<Material> {
+        if let Some(material) = self.materials.get(&index) {
+            return Arc::clone(material);
+        }
+        let material = self.convert_material(index);
+        self.materials.insert(index, Arc::clone(&material));
+        material
+    }
+
+    // Without an index, this is glTF's default material, which is what every property's default adds up to.
+    fn convert_material(&mut self, index: Option<usize>) -> Arc<Material> {
+        let (base_color, alpha, texture, metallic, roughness, transmission, index_of_refraction) = {
+            let default = JsonValue::new_object();
+            let material = match index {
+                Some(index) => &self.document["materials"][index],
+                None => &default,
+            };
+            let pbr = &material["pbrMetallicRoughness"];
+            let base = get_f64s(&pbr["baseColorFactor"], &[1f64, 1f64, 1f64, 1f64]);
+            let alpha = if material["alphaMode"].as_str() == Some("BLEND") { base[3] } else { 1f64 };
+            let texture = pbr["baseColorTexture"]["index"].as_usize()
+                .and_then(|texture| self.document["textures"][texture]["source"].as_usize());
+            let extensions = &material["extensions"];
+            (
+                Color::new(base[0], base[1], base[2]),
+                alpha,
+                texture,
+                get_f64(&pbr["metallicFactor"], 1f64),
+                get_f64(&pbr["roughnessFactor"], 1f64),
+                get_f64(&extensions["KHR_materials_transmission"]["transmissionFactor"], 0f64),
+                get_f64(&extensions["KHR_materials_ior"]["ior"], 1.5f64),
+            )
+        };
+
+        // Transmission refracts; blended alpha just lets light through.
+        let (transmission, index_of_refraction) = if transmission > 0f64 {
+            (base_color * transmission * (1f64 - metallic), index_of_refraction)
+        } else {
+            (Color::WHITE * (1f64 - alpha), 1f64)
+        };
+        let diffuse_weight = (1f64 - metallic) * (1f64 - transmission.average()) * alpha;
+        let reflection = base_color * metallic * (1f64 - roughness);
+        let diffuse: Box<Texture> = match texture {
+            Some(image) => Box::new(scale_image(self.get_image(image), base_color * diffuse_weight)),
+            None => Box::new(base_color * diffuse_weight),
+        };
+
+        if !reflection.is_nonzero() && !transmission.is_nonzero() {
+            Arc::new(FlatMaterial { texture: diffuse })
+        } else {
+            Arc::new(PhongMaterial {
+                diffuse,
+                specular: Box::new(Color::BLACK),
+                reflection: Box::new(reflection),
+                transmission: Box::new(transmission),
+                index_of_refraction,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn import(document: &str, binary: Option<Vec<u8>>) -> ImportedScene {
+        GltfImporter::new(json::parse(document).unwrap(), binary, Path::new("."), &MeshProcessing::default()).import()
+    }
+
+    // One triangle's positions (0 0 0, 1 0 0, 0 1 0) as floats, then its indices as unsigned shorts.
+    const TRIANGLE_BASE64: &str = "AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAABAAIAAAA=";
+
+    fn document(buffer: &str, materials: &str, nodes: &str) -> String {
+        format!(r#"{{
+            "asset": {{ "version": "2.0" }},
+            "buffers": [{}],
+            "bufferViews": [
+                {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+                {{ "buffer": 0, "byteOffset": 36, "byteLength": 6 }}
+            ],
+            "accessors": [
+                {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" }},
+                {{ "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }}
+            ],
+            "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0 }}, "indices": 1, "material": 0 }}] }}],
+            "materials": [{}],
+            "cameras": [{{ "type": "perspective", "perspective": {{ "yfov": 1.0, "znear": 0.1 }} }}],
+            "extensions": {{ "KHR_lights_punctual": {{ "lights": [{{ "type": "point", "intensity": 10 }}] }} }},
+            "scene": 0,
+            "scenes": [{{ "nodes": [0] }}],
+            "nodes": [{}]
+        }}"#, buffer, materials, nodes)
+    }
+
+    #[test]
+    fn it_should_decode_base64() {
+        assert_eq!(decode_base64("aGVsbG8="), b"hello".to_vec());
+        assert_eq!(decode_base64("aGk"), b"hi".to_vec());
+    }
+
+    #[test]
+    fn it_should_place_meshes_cameras_and_lights_with_their_nodes() {
+        let buffer = format!(r#"{{ "byteLength": 44, "uri": "data:application/octet-stream;base64,{}" }}"#, TRIANGLE_BASE64);
+        let nodes = r#"
+            { "translation": [0, 0, 5], "children": [1, 2, 3] },
+            { "mesh": 0, "scale": [2, 2, 2] },
+            { "camera": 0, "translation": [0, 0, 10] },
+            { "rotation": [0, 0.7071068, 0, 0.7071068], "mesh": 0, "extensions": { "KHR_lights_punctual": { "light": 0 } } }
+        "#;
+        let scene = import(&document(&buffer, r#"{ "pbrMetallicRoughness": { "metallicFactor": 0 } }"#, nodes), None);
+
+        assert_eq!(scene.objects.len(), 2);
+        let bound = scene.objects[0].bound();
+        assert!((bound.max.x - 2f64).abs() < 1e-6 && (bound.min.z - 5f64).abs() < 1e-6);
+        // A quarter turn around +y takes +x to -z.
+        let bound = scene.objects[1].bound();
+        assert!((bound.min.z - 4f64).abs() < 1e-6 && bound.max.x.abs() < 1e-6);
+
+        match scene.camera.unwrap() {
+            CameraBuilder::Perspective((position, look_at, _, _), fov) => {
+                assert!((position.z - 15f64).abs() < 1e-9 && (look_at.z - 14f64).abs() < 1e-9);
+                assert!((fov - 1f64.to_degrees()).abs() < 1e-9);
+            }
+            _ => panic!("expected a perspective camera"),
+        }
+        match scene.lights[0] {
+            ImportedLight::Point(position, intensity) => {
+                assert!((position.z - 5f64).abs() < 1e-9);
+                assert_eq!(intensity.g, 10f64);
+            }
+            _ => panic!("expected a point light"),
+        }
+    }
+
+    #[test]
+    fn it_should_read_glb_binary_chunks() {
+        let json = document(r#"{ "byteLength": 44 }"#, r#"{}"#, r#"{ "mesh": 0 }"#);
+        // Chunks are padded to 4 bytes: JSON with spaces, binary with zeroes.
+        let mut json = json.into_bytes();
+        while json.len() % 4 != 0 {
+            json.push(b' ');
+        }
+        let binary = decode_base64(TRIANGLE_BASE64);
+        let mut glb = vec![];
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&((12 + 8 + json.len() + 8 + binary.len()) as u32).to_le_bytes());
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(&json);
+        glb.extend_from_slice(&(binary.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend_from_slice(&binary);
+
+        let (document, binary) = parse_glb(&glb);
+        let scene = import(&document, binary);
+        assert_eq!(scene.objects.len(), 1);
+        assert!((scene.objects[0].bound().max.y - 1f64).abs() < 1e-6);
+    }
+
+    #[test]
+    fn it_should_share_a_default_material_between_primitives_without_one() {
+        let buffer = format!(r#"{{ "byteLength": 44, "uri": "data:application/octet-stream;base64,{}" }}"#, TRIANGLE_BASE64);
+        let document = document(&buffer, r#"{}"#, r#"{ "mesh": 0 }"#).replace(r#", "material": 0"#, "");
+        let processing = MeshProcessing::default();
+        let mut importer = GltfImporter::new(json::parse(&document).unwrap(), None, Path::new("."), &processing);
+        let (_, material) = importer.get_mesh(0).remove(0);
+        assert!(Arc::ptr_eq(&material, &importer.get_material(None)));
+        assert_eq!(importer.materials.len(), 1);
+
+        let scene = importer.import();
+        assert!(Arc::ptr_eq(scene.objects[0].material.as_ref().unwrap(), &material));
+    }
+
+    #[test]
+    fn it_should_process_meshes_as_they_are_read() {
+        let buffer = format!(r#"{{ "byteLength": 44, "uri": "data:application/octet-stream;base64,{}" }}"#, TRIANGLE_BASE64);
+        let document = json::parse(&document(&buffer, r#"{}"#, r#"{ "mesh": 0 }"#)).unwrap();
+        let processing = MeshProcessing { crease_angle: Some(30f64.to_radians()), ..Default::default() };
+        let scene = GltfImporter::new(document, None, Path::new("."), &processing).import();
+        // Only meshes with normals have a shading geometry.
+        let r = Ray::half_infinite(Point::new(0.25f64, 0.25f64, 1f64), -Vec3::Z_AXIS);
+        assert!(scene.objects[0].intersect(&r).unwrap().shading_geometry.is_some());
+    }
+}
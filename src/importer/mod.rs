@@ -1,3 +1,8 @@
+mod bezier_patches;
+mod gltf;
+mod obj;
+mod pbrt;
+mod ply;
 mod scene_builder;
 
 lalrpop_mod!(pub parser);
@@ -22,6 +27,7 @@ pub struct SceneFile {
 pub fn parse(path: &Path) -> SceneFile {
     let mut builder = SceneBuilder::new();
     parse_into_builder(path, &mut builder, &SceneFileParser::new());
+    builder.write_exports();
     SceneFile {
         camera: builder.build_camera(),
         animation: builder.build_animation(),
@@ -0,0 +1,513 @@
+use std::collections::HashMap;
+use std::path::{ Path, PathBuf };
+use std::str::FromStr;
+use std::sync::Arc;
+use core::*;
+use math::*;
+use geometry::*;
+use material::*;
+use file_utils::*;
+use image_utils::*;
+
+// The parts of an MTL material that map onto our own materials. Everything else (Ka, Ns, the other maps...)
+// is ignored.
+#[derive(Debug, Clone)]
+pub struct MtlMaterial {
+    pub diffuse: Color,
+    pub diffuse_map: Option<PathBuf>,
+    pub specular: Color,
+    pub index_of_refraction: f64,
+    // How opaque it is, from d (or 1 - Tr).
+    pub dissolve: f64,
+    pub illumination_model: u32,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> MtlMaterial {
+        MtlMaterial {
+            // Light grey, like most viewers show materials without a Kd.
+            diffuse: Color::new(0.8f64, 0.8f64, 0.8f64),
+            diffuse_map: None,
+            specular: Color::BLACK,
+            index_of_refraction: 1f64,
+            dissolve: 1f64,
+            illumination_model: 2,
+        }
+    }
+}
+
+impl MtlMaterial {
+    // Plain diffuse materials are flat; anything shiny or see-through becomes phong. Illumination models 3
+    // and up are the ray traced ones, so only they get Ks as a mirror reflection. Diffuse maps are
+    // multiplied by Kd, and both fade with dissolve.
+    pub fn to_material(&self) -> Arc<Material> {
+        let diffuse = self.diffuse * self.dissolve;
+        let diffuse: Box<Texture> = match self.diffuse_map {
+            Some(ref path) => Box::new(scale_image(load_image(path), diffuse)),
+            None => Box::new(diffuse),
+        };
+        if !self.specular.is_nonzero() && self.dissolve >= 1f64 {
+            return Arc::new(FlatMaterial { texture: diffuse });
+        }
+        let reflection = if self.illumination_model >= 3 { self.specular } else { Color::BLACK };
+        Arc::new(PhongMaterial {
+            diffuse,
+            specular: Box::new(self.specular),
+            reflection: Box::new(reflection),
+            transmission: Box::new(Color::WHITE * (1f64 - self.dissolve)),
+            index_of_refraction: self.index_of_refraction,
+        })
+    }
+}
+
+// Faces with the same group and material end up in the same part, no matter where they are in the file.
+#[derive(Debug)]
+pub struct ObjPart {
+    pub group: String,
+    pub material: Option<String>,
+    pub mesh: TriangleMeshData,
+}
+
+#[derive(Debug)]
+pub struct ObjModel {
+    pub parts: Vec<ObjPart>,
+    pub materials: HashMap<String, MtlMaterial>,
+}
+
+impl ObjModel {
+    // One object per part, each with its MTL material if it has one. Undefined materials are left off
+    // with a warning so whatever encloses the model can supply one.
+    pub fn into_scene_objects(self, processing: &MeshProcessing) -> Vec<SceneObject> {
+        let ObjModel { parts, materials } = self;
+        let mut converted = HashMap::<String, Arc<Material>>::new();
+        parts
+            .into_iter()
+            .map(|part| {
+                let material = part.material.and_then(|name| {
+                    if let Some(material) = converted.get(&name) {
+                        return Some(Arc::clone(material));
+                    }
+                    match materials.get(&name) {
+                        Some(material) => {
+                            let material = material.to_material();
+                            converted.insert(name, Arc::clone(&material));
+                            Some(material)
+                        }
+                        None => {
+                            eprintln!("warning: obj file uses undefined material \"{}\"", name);
+                            None
+                        }
+                    }
+                });
+                SceneObject {
+                    shape: Shape::new(Arc::new(processing.apply(part.mesh).into_triangle_mesh()), IDENTITY_TRANSFORM.clone()),
+                    material,
+                    alpha: None,
+                }
+            })
+            .collect()
+    }
+
+    // Every part in one mesh, for operations that work on meshes rather than models. Normals and UVs
+    // are only kept if every part has them.
+    pub fn into_triangle_mesh_data(self) -> TriangleMeshData {
+        let has_normals = self.parts.iter().all(|part| part.mesh.normals.is_some());
+        let has_uvs = self.parts.iter().all(|part| part.mesh.uvs.is_some());
+        let (mut positions, mut normals, mut uvs, mut indices) = (vec![], vec![], vec![], vec![]);
+        for part in self.parts {
+            let offset = positions.len();
+            let TriangleMeshData { positions: part_positions, indices: part_indices, normals: part_normals, uvs: part_uvs, .. } = part.mesh;
+            positions.extend(part_positions);
+            indices.extend(part_indices.into_iter().map(|(a, b, c)| (a + offset, b + offset, c + offset)));
+            if has_normals {
+                normals.extend(part_normals.unwrap());
+            }
+            if has_uvs {
+                uvs.extend(part_uvs.unwrap());
+            }
+        }
+        let smoothing = if has_normals { Smoothing::Explicit(normals) } else { Smoothing::None };
+        TriangleMeshData::new(positions, smoothing, if has_uvs { Some(uvs) } else { None }, indices)
+    }
+}
+
+// Reads a Wavefront OBJ file and any MTL libraries it references, which are looked up next to it.
+pub fn load_obj(path: &Path) -> ObjModel {
+    let directory = path.parent().expect("obj file has no parent directory").to_owned();
+    parse_obj(&read_file_contents(path), &|library| {
+        let library_path = directory.join(library);
+        let library_directory = library_path.parent().expect("mtl file has no parent directory").to_owned();
+        parse_mtl(&read_file_contents(&library_path), &library_directory)
+    })
+}
+
+struct PartBuilder {
+    group: String,
+    material: Option<String>,
+    positions: Vec<Point>,
+    normals: Vec<Option<Normal>>,
+    uvs: Vec<Option<Uv>>,
+    // Vertices are shared between faces when they use the same position, UV and normal.
+    vertices: HashMap<(usize, Option<usize>, Option<usize>), usize>,
+    indices: Vec<TriangleIndices>,
+    is_smooth: bool,
+}
+
+impl PartBuilder {
+    // Normals are only used if the file gives one for every vertex; otherwise the part is smoothed if any
+    // of its faces asked to be.
+    fn build(self) -> ObjPart {
+        let smoothing = if self.normals.iter().all(|normal| normal.is_some()) {
+            Smoothing::Explicit(self.normals.into_iter().map(|normal| normal.unwrap()).collect())
+        } else if self.is_smooth {
+            Smoothing::Implicit
+        } else {
+            Smoothing::None
+        };
+        let uvs = if self.uvs.iter().all(|uv| uv.is_some()) {
+            Some(self.uvs.into_iter().map(|uv| uv.unwrap()).collect())
+        } else {
+            None
+        };
+        ObjPart {
+            group: self.group,
+            material: self.material,
+            mesh: TriangleMeshData::new(self.positions, smoothing, uvs, self.indices),
+        }
+    }
+}
+
+fn parse_f64s(values: &[&str], what: &str) -> Vec<f64> {
+    values.iter().map(|value| f64::from_str(value).expect(&format!("obj file has a bad {}", what))).collect()
+}
+
+// OBJ indices start at 1, and negative ones count back from the most recent.
+fn resolve_index(index: &str, count: usize, what: &str) -> usize {
+    let index = i64::from_str(index).expect(&format!("obj file has a bad {} index", what));
+    let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+    assert!(index != 0 && resolved >= 0 && resolved < count as i64, "obj file {} index {} is out of range", what, index);
+    resolved as usize
+}
+
+fn parse_obj(source: &str, load_library: &Fn(&str) -> HashMap<String, MtlMaterial>) -> ObjModel {
+    let mut positions = Vec::<Point>::new();
+    let mut uvs = Vec::<Uv>::new();
+    let mut normals = Vec::<Normal>::new();
+    let mut materials = HashMap::<String, MtlMaterial>::new();
+    let mut parts = Vec::<PartBuilder>::new();
+    let mut part_indices = HashMap::<(String, Option<String>), usize>::new();
+    let (mut group, mut material, mut is_smooth) = (String::from("default"), None::<String>, false);
+
+    for line in source.lines() {
+        let line = line.split('#').next().unwrap();
+        let values: Vec<&str> = line.split_whitespace().collect();
+        let (keyword, values) = match values.split_first() {
+            Some((keyword, values)) => (*keyword, values),
+            None => continue,
+        };
+        match keyword {
+            "v" => {
+                let v = parse_f64s(values, "position");
+                assert!(v.len() >= 3, "obj file positions need 3 coordinates");
+                positions.push(Point::new(v[0], v[1], v[2]));
+            }
+            "vt" => {
+                let v = parse_f64s(values, "uv");
+                assert!(!v.is_empty(), "obj file uvs need at least 1 coordinate");
+                uvs.push(Uv(v[0], v.get(1).cloned().unwrap_or(0f64)));
+            }
+            "vn" => {
+                let v = parse_f64s(values, "normal");
+                assert!(v.len() >= 3, "obj file normals need 3 coordinates");
+                normals.push(Normal::new(v[0], v[1], v[2]));
+            }
+            "g" | "o" => {
+                group = if values.is_empty() { String::from("default") } else { values.join(" ") };
+            }
+            "usemtl" => {
+                material = values.first().map(|name| name.to_string());
+            }
+            "mtllib" => {
+                for library in values {
+                    materials.extend(load_library(library));
+                }
+            }
+            "s" => {
+                is_smooth = values.first().map_or(false, |&value| value != "off" && value != "0");
+            }
+            "f" => {
+                assert!(values.len() >= 3, "obj file faces need at least 3 vertices");
+                let key = (group.clone(), material.clone());
+                let part_index = *part_indices.entry(key).or_insert_with(|| {
+                    parts.push(PartBuilder {
+                        group: group.clone(),
+                        material: material.clone(),
+                        positions: vec![],
+                        normals: vec![],
+                        uvs: vec![],
+                        vertices: HashMap::new(),
+                        indices: vec![],
+                        is_smooth: false,
+                    });
+                    parts.len() - 1
+                });
+                let part = &mut parts[part_index];
+                part.is_smooth = part.is_smooth || is_smooth;
+
+                // Each vertex is v, v/vt, v//vn or v/vt/vn.
+                let face: Vec<usize> = values
+                    .iter()
+                    .map(|vertex| {
+                        let mut references = vertex.split('/');
+                        let position = resolve_index(references.next().unwrap(), positions.len(), "position");
+                        let uv = references.next().filter(|r| !r.is_empty()).map(|r| resolve_index(r, uvs.len(), "uv"));
+                        let normal = references.next().filter(|r| !r.is_empty()).map(|r| resolve_index(r, normals.len(), "normal"));
+                        let PartBuilder { ref mut vertices, positions: ref mut part_positions, uvs: ref mut part_uvs, normals: ref mut part_normals, .. } = *part;
+                        *vertices.entry((position, uv, normal)).or_insert_with(|| {
+                            part_positions.push(positions[position]);
+                            part_uvs.push(uv.map(|i| uvs[i]));
+                            part_normals.push(normal.map(|i| normals[i]));
+                            part_positions.len() - 1
+                        })
+                    })
+                    .collect();
+                let face_positions: Vec<Point> = face.iter().map(|&i| part.positions[i]).collect();
+                part.indices.extend(triangulate(&face_positions).into_iter().map(|(a, b, c)| (face[a], face[b], face[c])));
+            }
+            // Lines, points, free-form geometry and so on.
+            _ => {}
+        }
+    }
+
+    ObjModel {
+        parts: parts.into_iter().map(PartBuilder::build).collect(),
+        materials,
+    }
+}
+
+// Splits a planar polygon into triangles wound the same way, by ear clipping so concave polygons work too.
+// Returns indices into the polygon.
+fn triangulate(polygon: &[Point]) -> Vec<TriangleIndices> {
+    if polygon.len() == 3 {
+        return vec![(0, 1, 2)];
+    }
+
+    // Newell's method, so it's right even if the first corner is concave.
+    let normal = (1..polygon.len() - 1).fold(Vec3::uniform(0f64), |normal, i| {
+        normal + (polygon[i] - polygon[0]).cross(polygon[i + 1] - polygon[0])
+    });
+    let is_left_turn = |a: Point, b: Point, c: Point| (b - a).cross(c - b).dot(&normal) > 0f64;
+
+    let mut remaining: Vec<usize> = (0..polygon.len()).collect();
+    let mut triangles = vec![];
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let ear = (0..count).find(|&i| {
+            let (a, b, c) = (remaining[(i + count - 1) % count], remaining[i], remaining[(i + 1) % count]);
+            let (pa, pb, pc) = (polygon[a], polygon[b], polygon[c]);
+            is_left_turn(pa, pb, pc) && remaining.iter().filter(|&&j| j != a && j != b && j != c).all(|&j| {
+                let p = polygon[j];
+                !(is_left_turn(pa, pb, p) && is_left_turn(pb, pc, p) && is_left_turn(pc, pa, p))
+            })
+        });
+        match ear {
+            Some(i) => {
+                triangles.push((remaining[(i + count - 1) % count], remaining[i], remaining[(i + 1) % count]));
+                remaining.remove(i);
+            }
+            // Degenerate or self-intersecting, so there's nothing better to do than a fan.
+            None => break,
+        }
+    }
+    triangles.extend((1..remaining.len() - 1).map(|i| (remaining[0], remaining[i], remaining[i + 1])));
+    triangles
+}
+
+// Reads an MTL library. Texture paths are relative to `directory`.
+fn parse_mtl(source: &str, directory: &Path) -> HashMap<String, MtlMaterial> {
+    let mut materials = HashMap::<String, MtlMaterial>::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+    let parse_color = |values: &[&str]| {
+        let v = parse_f64s(values, "color");
+        match v.len() {
+            1 => Color::new(v[0], v[0], v[0]),
+            3 => Color::new(v[0], v[1], v[2]),
+            _ => panic!("mtl file colors need 1 or 3 values"),
+        }
+    };
+
+    for line in source.lines() {
+        let line = line.split('#').next().unwrap();
+        let values: Vec<&str> = line.split_whitespace().collect();
+        let (keyword, values) = match values.split_first() {
+            Some((keyword, values)) => (*keyword, values),
+            None => continue,
+        };
+        if keyword == "newmtl" {
+            let name = values.join(" ");
+            if let Some((name, material)) = current.replace((name, MtlMaterial::default())) {
+                materials.insert(name, material);
+            }
+            continue;
+        }
+        let material = match current {
+            Some((_, ref mut material)) => material,
+            None => continue,
+        };
+        match keyword {
+            // Like unknown keywords, lines missing their values are skipped.
+            "Kd" | "Ks" | "Ni" | "d" | "Tr" | "illum" | "map_Kd" if values.is_empty() => {
+                eprintln!("warning: mtl file has no value for {}, ignoring it", keyword);
+            }
+            "Kd" => material.diffuse = parse_color(values),
+            "Ks" => material.specular = parse_color(values),
+            "Ni" => material.index_of_refraction = parse_f64s(values, "index of refraction")[0],
+            "d" => material.dissolve = parse_f64s(values, "dissolve")[0],
+            "Tr" => material.dissolve = 1f64 - parse_f64s(values, "transparency")[0],
+            "illum" => material.illumination_model = u32::from_str(values[0]).expect("mtl file has a bad illumination model"),
+            // Options like -s come before the file name.
+            "map_Kd" => material.diffuse_map = values.last().map(|file| directory.join(file)),
+            _ => {}
+        }
+    }
+    if let Some((name, material)) = current {
+        materials.insert(name, material);
+    }
+    materials
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> ObjModel {
+        parse_obj(source, &|_| HashMap::new())
+    }
+
+    #[test]
+    fn it_should_parse_every_face_format() {
+        let model = parse("
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            vt 0 0
+            vt 1 0
+            vt 1 1
+            vn 0 0 1
+            f 1 2 3
+            f 1/1 2/2 3/3
+            f 1//1 2//1 3//1
+            f -4/-3/-1 -3/-2/-1 -2/-1/-1 # negative indices count back
+        ");
+        assert_eq!(model.parts.len(), 1);
+        let mesh = &model.parts[0].mesh;
+        assert_eq!(mesh.indices.len(), 4);
+        // The last face shares its vertices with none of the others, since they're each missing something.
+        assert_eq!(mesh.positions.len(), 12);
+        assert_eq!(mesh.indices[3], (9, 10, 11));
+        assert_eq!(mesh.positions[10].x, 1f64);
+        // Not every vertex has a UV or a normal, and nothing's smoothed.
+        assert!(mesh.uvs.is_none() && mesh.normals.is_none());
+    }
+
+    #[test]
+    fn it_should_share_vertices_and_keep_uvs_and_normals() {
+        let model = parse("
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            vt 0 0
+            vt 1 0
+            vt 1 1
+            vt 0 1
+            vn 0 0 1
+            f 1/1/1 2/2/1 3/3/1 4/4/1
+        ");
+        let mesh = &model.parts[0].mesh;
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.indices.len(), 2);
+        assert_eq!(mesh.uvs.as_ref().unwrap()[2].0, 1f64);
+        assert_eq!(mesh.normals.as_ref().unwrap()[3].z, 1f64);
+    }
+
+    #[test]
+    fn it_should_triangulate_concave_polygons() {
+        // An L, whose first corner is the concave one, so a fan from it would poke outside.
+        let polygon = [
+            Point::new(1f64, 1f64, 0f64), Point::new(1f64, 2f64, 0f64), Point::new(0f64, 2f64, 0f64),
+            Point::new(0f64, 0f64, 0f64), Point::new(2f64, 0f64, 0f64), Point::new(2f64, 1f64, 0f64),
+        ];
+        let triangles = triangulate(&polygon);
+        assert_eq!(triangles.len(), 4);
+        let mut area = 0f64;
+        for &(a, b, c) in &triangles {
+            let z = (polygon[b] - polygon[a]).cross(polygon[c] - polygon[a]).z;
+            assert!(z > 0f64);
+            area += z / 2f64;
+        }
+        assert!((area - 3f64).abs() < 1e-12);
+    }
+
+    #[test]
+    fn it_should_split_groups_and_materials_into_parts() {
+        let model = parse_obj("
+            mtllib shapes.mtl
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            g first
+            usemtl red
+            f 1 2 3
+            usemtl blue
+            s 1
+            f 1 2 3
+            g second
+            f 1 2 3
+            g first
+            usemtl red
+            s off
+            f 3 2 1
+        ", &|library| {
+            assert_eq!(library, "shapes.mtl");
+            parse_mtl("
+                newmtl red
+                Kd 1 0 0
+                newmtl blue
+                Kd 0 0 1
+                Ks 0.5
+                Ni 1.5
+                d 0.25
+                map_Kd -s 2 2 1 blue.png
+            ", Path::new("textures"))
+        });
+
+        let parts: Vec<(&str, Option<&str>, usize)> = model.parts
+            .iter()
+            .map(|part| (part.group.as_str(), part.material.as_ref().map(|m| m.as_str()), part.mesh.indices.len()))
+            .collect();
+        assert_eq!(parts, vec![("first", Some("red"), 2), ("first", Some("blue"), 1), ("second", Some("blue"), 1)]);
+        assert!(model.parts[0].mesh.normals.is_none());
+        assert!(model.parts[1].mesh.normals.is_some());
+
+        let blue = &model.materials["blue"];
+        assert_eq!((blue.diffuse.b, blue.specular.g, blue.index_of_refraction, blue.dissolve), (1f64, 0.5f64, 1.5f64, 0.25f64));
+        assert_eq!(blue.diffuse_map, Some(Path::new("textures").join("blue.png")));
+        assert!(model.materials["red"].diffuse_map.is_none());
+    }
+
+    #[test]
+    fn it_should_skip_mtl_lines_without_values() {
+        let materials = parse_mtl("
+            newmtl bare
+            Kd
+            d
+            Tr
+            illum
+            Ni 1.5
+        ", Path::new("."));
+        let bare = &materials["bare"];
+        assert_eq!((bare.dissolve, bare.illumination_model, bare.index_of_refraction), (1f64, 2, 1.5f64));
+    }
+}
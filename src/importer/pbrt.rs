@@ -0,0 +1,886 @@
+use std::collections::HashMap;
+use std::path::{ Path, PathBuf };
+use std::str::FromStr;
+use std::sync::Arc;
+use image::RgbImage;
+use core::*;
+use math::*;
+use geometry::*;
+use material::*;
+use file_utils::*;
+use image_utils::*;
+use importer::ply::*;
+use importer::scene_builder::*;
+
+// Reads a pbrt-v3 scene file, with its camera, film resolution, shapes, materials and lights. Anything we
+// can't represent is skipped or approximated with a warning:
+//  - Area lights become point lights with the same power at their shape's center, and the shapes themselves
+//    aren't drawn, since we don't have area lights.
+//  - Infinite lights only become the background color.
+//  - Materials other than matte, plastic, glass and mirror are approximated by matte ones.
+//  - Sampler, Integrator, PixelFilter, Accelerator, media and motion blur are ignored.
+//
+// pbrt is left-handed, so the scene is mirrored across x to come out the same as pbrt would render it.
+pub fn load_pbrt(path: &Path) -> ImportedScene {
+    let directory = path.parent().expect("pbrt file has no parent directory");
+    let mut importer = PbrtImporter::new(directory);
+    importer.run(&read_file_contents(path));
+    importer.finish()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Number(f64),
+    Open,
+    Close,
+}
+
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut chars = source.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            '#' => while chars.next().map_or(false, |c| c != '\n') {},
+            '[' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ']' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '"' => {
+                chars.next();
+                let mut string = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => string.extend(chars.next()),
+                        Some(c) => string.push(c),
+                        None => panic!("pbrt file has an unterminated string"),
+                    }
+                }
+                tokens.push(Token::Str(string));
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '[' || c == ']' || c == '"' || c == '#' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(match f64::from_str(&word) {
+                    Ok(number) => Token::Number(number),
+                    // Booleans can be written without quotes.
+                    Err(_) if word == "true" || word == "false" => Token::Str(word),
+                    Err(_) => Token::Word(word),
+                });
+            }
+        }
+    }
+    tokens
+}
+
+#[derive(Debug)]
+struct Statement {
+    directive: String,
+    arguments: Vec<Token>,
+}
+
+// Every directive runs until the next one, except ActiveTransform, whose argument is a bare word too.
+fn group_statements(tokens: Vec<Token>) -> Vec<Statement> {
+    let mut statements: Vec<Statement> = vec![];
+    let mut tokens = tokens.into_iter();
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Word(directive) => {
+                let arguments = if directive == "ActiveTransform" { tokens.next().into_iter().collect() } else { vec![] };
+                statements.push(Statement { directive, arguments });
+            }
+            argument => match statements.last_mut() {
+                Some(statement) if statement.directive != "ActiveTransform" => statement.arguments.push(argument),
+                _ => panic!("pbrt file has {:?} outside of any directive", argument),
+            },
+        }
+    }
+    statements
+}
+
+#[derive(Debug, Clone)]
+struct Param {
+    kind: String,
+    name: String,
+    numbers: Vec<f64>,
+    strings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Params(Vec<Param>);
+
+// Parameters are a "type name" string followed by either a single value or a bracketed list of them.
+fn parse_params(arguments: &[Token]) -> Params {
+    let mut params = vec![];
+    let mut i = 0;
+    while i < arguments.len() {
+        let declaration = match arguments[i] {
+            Token::Str(ref declaration) => declaration,
+            ref other => panic!("expected a pbrt parameter but found {:?}", other),
+        };
+        let (kind, name) = {
+            let words: Vec<&str> = declaration.split_whitespace().collect();
+            assert_eq!(words.len(), 2, "pbrt parameter \"{}\" should be a type and a name", declaration);
+            (words[0].to_owned(), words[1].to_owned())
+        };
+        let values = match arguments.get(i + 1) {
+            Some(&Token::Open) => {
+                let end = arguments[i + 2..].iter().position(|token| *token == Token::Close)
+                    .expect("pbrt parameter list has no closing bracket") + i + 2;
+                let values = &arguments[i + 2..end];
+                i = end + 1;
+                values
+            }
+            Some(_) => {
+                i += 2;
+                &arguments[i - 1..i]
+            }
+            None => panic!("pbrt parameter \"{}\" has no value", declaration),
+        };
+        let mut param = Param { kind, name, numbers: vec![], strings: vec![] };
+        for value in values {
+            match *value {
+                Token::Number(number) => param.numbers.push(number),
+                Token::Str(ref string) => param.strings.push(string.clone()),
+                ref other => panic!("pbrt parameter \"{}\" has an invalid value {:?}", declaration, other),
+            }
+        }
+        params.push(param);
+    }
+    Params(params)
+}
+
+// Splits off the leading strings most directives start with, like a shape's type, from their parameters.
+fn split_arguments(directive: &str, arguments: &[Token], count: usize) -> (Vec<String>, Params) {
+    assert!(arguments.len() >= count, "pbrt {} is missing arguments", directive);
+    let strings = arguments[..count]
+        .iter()
+        .map(|argument| match *argument {
+            Token::Str(ref string) => string.clone(),
+            ref other => panic!("pbrt {} expected a string but found {:?}", directive, other),
+        })
+        .collect();
+    (strings, parse_params(&arguments[count..]))
+}
+
+// The numbers of a directive like Translate, with or without brackets around them.
+fn numbers(directive: &str, arguments: &[Token], count: usize) -> Vec<f64> {
+    let numbers: Vec<f64> = arguments
+        .iter()
+        .filter_map(|argument| match *argument {
+            Token::Number(number) => Some(number),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(numbers.len(), count, "pbrt {} should have {} numbers", directive, count);
+    numbers
+}
+
+impl Params {
+    fn get(&self, name: &str) -> Option<&Param> {
+        self.0.iter().find(|param| param.name == name)
+    }
+
+    fn floats(&self, name: &str) -> Option<&[f64]> {
+        self.get(name).map(|param| &param.numbers[..])
+    }
+
+    fn float(&self, name: &str, default: f64) -> f64 {
+        self.floats(name).and_then(|numbers| numbers.first().cloned()).unwrap_or(default)
+    }
+
+    fn string(&self, name: &str) -> Option<&str> {
+        self.get(name).and_then(|param| param.strings.first()).map(|string| string.as_str())
+    }
+
+    fn bool(&self, name: &str, default: bool) -> bool {
+        self.string(name).map_or(default, |string| string == "true")
+    }
+
+    fn point(&self, name: &str, default: Point) -> Point {
+        match self.floats(name) {
+            Some(p) if p.len() == 3 => Point::new(p[0], p[1], p[2]),
+            _ => default,
+        }
+    }
+
+    fn points(&self, name: &str) -> Option<Vec<Point>> {
+        self.floats(name).map(|p| p.chunks(3).filter(|p| p.len() == 3).map(|p| Point::new(p[0], p[1], p[2])).collect())
+    }
+
+    fn color(&self, name: &str, default: Color) -> Color {
+        let param = match self.get(name) {
+            Some(param) => param,
+            None => return default,
+        };
+        match (param.kind.as_str(), &param.numbers[..]) {
+            ("rgb", &[r, g, b]) | ("color", &[r, g, b]) => Color::new(r, g, b),
+            // To linear sRGB.
+            ("xyz", &[x, y, z]) => Color::new(
+                3.240479f64 * x - 1.537150f64 * y - 0.498535f64 * z,
+                -0.969256f64 * x + 1.875991f64 * y + 0.041556f64 * z,
+                0.055648f64 * x - 0.204043f64 * y + 1.057311f64 * z,
+            ),
+            ("float", &[value]) => Color::new(value, value, value),
+            _ => {
+                eprintln!("warning: ignoring pbrt {} parameter \"{}\", which isn't supported", param.kind, name);
+                default
+            }
+        }
+    }
+}
+
+// pbrt pg. 92. pbrt's LookAt is left-handed, with the camera's right being up cross the direction.
+fn look_at(position: Point, target: Point, up: Vec3) -> Mat4 {
+    let direction = (target - position).into_normalized();
+    let right = up.into_normalized().cross(direction).into_normalized();
+    let up = direction.cross(right);
+    Mat4 {
+        cells: [
+            [right.x, up.x, direction.x, position.x],
+            [right.y, up.y, direction.y, position.y],
+            [right.z, up.z, direction.z, position.z],
+            [0f64, 0f64, 0f64, 1f64],
+        ],
+    }.invert().expect("pbrt LookAt is degenerate")
+}
+
+// pbrt matrices are written column by column.
+fn matrix_from_columns(values: &[f64]) -> Mat4 {
+    let mut cells = [[0f64; 4]; 4];
+    for (i, &value) in values.iter().enumerate() {
+        cells[i % 4][i / 4] = value;
+    }
+    Mat4 { cells }
+}
+
+fn determinant(m: &Mat4) -> f64 {
+    let c = &m.cells;
+    c[0][0] * (c[1][1] * c[2][2] - c[1][2] * c[2][1])
+        - c[0][1] * (c[1][0] * c[2][2] - c[1][2] * c[2][0])
+        + c[0][2] * (c[1][0] * c[2][1] - c[1][1] * c[2][0])
+}
+
+fn mirror_x() -> Mat4 {
+    Mat4::create_scale(Vec3::new(-1f64, 1f64, 1f64))
+}
+
+// pbrt's quadrics are around +z, and ours are around +y.
+fn z_up() -> Mat4 {
+    Mat4::create_rotation(TWO_PI / 4f64, Vec3::X_AXIS)
+}
+
+fn is_readable_image(filename: &str) -> bool {
+    let extension = Path::new(filename).extension().and_then(|extension| extension.to_str()).unwrap_or("").to_lowercase();
+    ["png", "jpg", "jpeg", "tga", "bmp", "gif"].contains(&extension.as_str())
+}
+
+#[derive(Debug, Clone)]
+enum NamedTexture {
+    Constant(Color),
+    Image(RgbImage),
+}
+
+#[derive(Clone)]
+struct GraphicsState {
+    transform: Mat4,
+    // None is pbrt's "none" material, for shapes that aren't drawn.
+    material: Option<Arc<Material>>,
+    // The radiance of the AreaLightSource, if there is one, and whether it's two-sided.
+    area_light: Option<(Color, bool)>,
+}
+
+// One of a shape's geometries, placed in the shape's space, with its area and center there for
+// approximating area lights.
+struct ShapePart {
+    geometry: Arc<Geometry>,
+    part_to_shape: Mat4,
+    area: f64,
+    center: Point,
+}
+
+// A shape of an ObjectBegin, with its transform in the object. Each instance places its own copy of it, but
+// they all share the geometry.
+type InstancePart = (Arc<Geometry>, Mat4, Arc<Material>);
+
+struct PbrtImporter {
+    directory: PathBuf,
+    state: GraphicsState,
+    attribute_stack: Vec<GraphicsState>,
+    transform_stack: Vec<Mat4>,
+    coordinate_systems: HashMap<String, Mat4>,
+    named_materials: HashMap<String, Option<Arc<Material>>>,
+    textures: HashMap<String, NamedTexture>,
+    instances: HashMap<String, Vec<InstancePart>>,
+    // The name and parts of the ObjectBegin that's being read, if any.
+    current_instance: Option<(String, Vec<InstancePart>)>,
+    // pbrt world to ours, which mirrors the scene unless the camera already does.
+    to_world: Mat4,
+    camera: Option<(Mat4, String, Params)>,
+    film: Option<Params>,
+    scene: ImportedScene,
+}
+
+impl PbrtImporter {
+    fn new(directory: &Path) -> PbrtImporter {
+        PbrtImporter {
+            directory: directory.to_path_buf(),
+            state: GraphicsState {
+                transform: IDENTITY_MATRIX,
+                material: Some(Arc::new(FlatMaterial { texture: Box::new(Color::new(0.5f64, 0.5f64, 0.5f64)) })),
+                area_light: None,
+            },
+            attribute_stack: vec![],
+            transform_stack: vec![],
+            coordinate_systems: HashMap::new(),
+            named_materials: HashMap::new(),
+            textures: HashMap::new(),
+            instances: HashMap::new(),
+            current_instance: None,
+            to_world: mirror_x(),
+            camera: None,
+            film: None,
+            scene: ImportedScene { objects: vec![], lights: vec![], camera: None, image_dimensions: None, background_color: None },
+        }
+    }
+
+    fn run(&mut self, source: &str) {
+        for statement in group_statements(tokenize(source)) {
+            self.execute(&statement);
+        }
+    }
+
+    fn concatenate(&mut self, m: Mat4) {
+        self.state.transform = &self.state.transform * m;
+    }
+
+    fn execute(&mut self, statement: &Statement) {
+        let directive = statement.directive.as_str();
+        let arguments = &statement.arguments[..];
+        match directive {
+            "Identity" => self.state.transform = IDENTITY_MATRIX,
+            "Translate" => {
+                let v = numbers(directive, arguments, 3);
+                self.concatenate(Mat4::create_translation(Vec3::new(v[0], v[1], v[2])));
+            }
+            "Scale" => {
+                let v = numbers(directive, arguments, 3);
+                self.concatenate(Mat4::create_scale(Vec3::new(v[0], v[1], v[2])));
+            }
+            "Rotate" => {
+                let v = numbers(directive, arguments, 4);
+                self.concatenate(Mat4::create_rotation(v[0].to_radians(), Vec3::new(v[1], v[2], v[3]).into_normalized()));
+            }
+            "LookAt" => {
+                let v = numbers(directive, arguments, 9);
+                self.concatenate(look_at(Point::new(v[0], v[1], v[2]), Point::new(v[3], v[4], v[5]), Vec3::new(v[6], v[7], v[8])));
+            }
+            "Transform" => self.state.transform = matrix_from_columns(&numbers(directive, arguments, 16)),
+            "ConcatTransform" => self.concatenate(matrix_from_columns(&numbers(directive, arguments, 16))),
+            "CoordinateSystem" => {
+                let (names, _) = split_arguments(directive, arguments, 1);
+                self.coordinate_systems.insert(names[0].clone(), self.state.transform.clone());
+            }
+            "CoordSysTransform" => {
+                let (names, _) = split_arguments(directive, arguments, 1);
+                match self.coordinate_systems.get(&names[0]) {
+                    Some(transform) => self.state.transform = transform.clone(),
+                    None => eprintln!("warning: ignoring unknown pbrt coordinate system \"{}\"", names[0]),
+                }
+            }
+            "TransformBegin" => self.transform_stack.push(self.state.transform.clone()),
+            "TransformEnd" => match self.transform_stack.pop() {
+                Some(transform) => self.state.transform = transform,
+                None => eprintln!("warning: ignoring unmatched pbrt TransformEnd"),
+            },
+            "AttributeBegin" => self.attribute_stack.push(self.state.clone()),
+            "AttributeEnd" => match self.attribute_stack.pop() {
+                Some(state) => self.state = state,
+                None => eprintln!("warning: ignoring unmatched pbrt AttributeEnd"),
+            },
+            "WorldBegin" => {
+                self.state.transform = IDENTITY_MATRIX;
+                self.coordinate_systems.insert("world".to_owned(), IDENTITY_MATRIX);
+            }
+            "WorldEnd" => {}
+            "Camera" => {
+                let (kind, params) = split_arguments(directive, arguments, 1);
+                let camera_to_world = self.state.transform.invert().expect("pbrt camera transform is not invertible");
+                // Our cameras are right-handed, so the world only needs mirroring if the camera isn't already.
+                if determinant(&camera_to_world) < 0f64 {
+                    self.to_world = IDENTITY_MATRIX;
+                }
+                self.coordinate_systems.insert("camera".to_owned(), camera_to_world.clone());
+                self.camera = Some((camera_to_world, kind[0].clone(), params));
+            }
+            "Film" => self.film = Some(split_arguments(directive, arguments, 1).1),
+            "Material" => {
+                let (kind, params) = split_arguments(directive, arguments, 1);
+                self.state.material = self.create_material(&kind[0], &params);
+            }
+            "MakeNamedMaterial" => {
+                let (name, params) = split_arguments(directive, arguments, 1);
+                let material = self.create_material(params.string("type").unwrap_or("matte"), &params);
+                self.named_materials.insert(name[0].clone(), material);
+            }
+            "NamedMaterial" => {
+                let (name, _) = split_arguments(directive, arguments, 1);
+                match self.named_materials.get(&name[0]) {
+                    Some(material) => self.state.material = material.clone(),
+                    None => eprintln!("warning: ignoring unknown pbrt material \"{}\"", name[0]),
+                }
+            }
+            "Texture" => {
+                let (strings, params) = split_arguments(directive, arguments, 3);
+                self.create_texture(&strings[0], &strings[1], &strings[2], &params);
+            }
+            "LightSource" => {
+                let (kind, params) = split_arguments(directive, arguments, 1);
+                self.add_light(&kind[0], &params);
+            }
+            "AreaLightSource" => {
+                let (kind, params) = split_arguments(directive, arguments, 1);
+                if kind[0] == "diffuse" {
+                    eprintln!("warning: approximating pbrt area light with point lights");
+                    let radiance = params.color("L", Color::WHITE) * params.color("scale", Color::WHITE);
+                    self.state.area_light = Some((radiance, params.bool("twosided", false)));
+                } else {
+                    eprintln!("warning: ignoring pbrt area light of unknown type \"{}\"", kind[0]);
+                }
+            }
+            "Shape" => {
+                let (kind, params) = split_arguments(directive, arguments, 1);
+                self.add_shape(&kind[0], &params);
+            }
+            "ObjectBegin" => {
+                let (name, _) = split_arguments(directive, arguments, 1);
+                self.attribute_stack.push(self.state.clone());
+                self.current_instance = Some((name[0].clone(), vec![]));
+            }
+            "ObjectEnd" => {
+                if let Some((name, parts)) = self.current_instance.take() {
+                    self.instances.insert(name, parts);
+                }
+                if let Some(state) = self.attribute_stack.pop() {
+                    self.state = state;
+                }
+            }
+            "ObjectInstance" => {
+                let (name, _) = split_arguments(directive, arguments, 1);
+                match self.instances.get(&name[0]).cloned() {
+                    Some(parts) => for (geometry, part_to_shape, material) in parts {
+                        self.add_object(geometry, part_to_shape, material);
+                    },
+                    None => eprintln!("warning: ignoring unknown pbrt object \"{}\"", name[0]),
+                }
+            }
+            "Include" => {
+                let (path, _) = split_arguments(directive, arguments, 1);
+                let source = read_file_contents(&self.directory.join(&path[0]));
+                self.run(&source);
+            }
+            "Sampler" | "Integrator" | "PixelFilter" | "Accelerator" | "MakeNamedMedium" | "MediumInterface"
+            | "TransformTimes" | "ActiveTransform" | "ReverseOrientation" => {
+                eprintln!("warning: ignoring unsupported pbrt directive {}", directive);
+            }
+            _ => eprintln!("warning: ignoring unknown pbrt directive {}", directive),
+        }
+    }
+
+    // Places an object with the current transform, either in the world or in the object being defined.
+    fn add_object(&mut self, geometry: Arc<Geometry>, part_to_shape: Mat4, material: Arc<Material>) {
+        let object_to_pbrt_world = &self.state.transform * part_to_shape;
+        match self.current_instance {
+            Some((_, ref mut parts)) => parts.push((geometry, object_to_pbrt_world, material)),
+            None => {
+                let object_to_world = Transform::new(&self.to_world * object_to_pbrt_world);
+                self.scene.objects.push(SceneObject { shape: Shape::new(geometry, object_to_world), material: Some(material), alpha: None });
+            }
+        }
+    }
+
+    fn add_shape(&mut self, kind: &str, params: &Params) {
+        let part = match self.create_shape(kind, params) {
+            Some(part) => part,
+            None => return,
+        };
+        let area_light = if self.current_instance.is_some() { None } else { self.state.area_light };
+        match area_light {
+            // A diffuse emitter sends out pi * L * area, which a point light does with an intensity of a quarter
+            // of L * area. Its shape would block it, so that's left out.
+            Some((radiance, is_two_sided)) => {
+                let part_to_world = Transform::new(&self.to_world * (&self.state.transform * part.part_to_shape));
+                let scale = determinant(&part_to_world.m).abs().powf(2f64 / 3f64);
+                let sides = if is_two_sided { 2f64 } else { 1f64 };
+                let intensity = radiance * (part.area * scale * sides / 4f64);
+                self.scene.lights.push(ImportedLight::Point(part.center.transform(&part_to_world), intensity));
+            }
+            None => {
+                if self.current_instance.is_some() && self.state.area_light.is_some() {
+                    eprintln!("warning: ignoring pbrt area light inside an object");
+                }
+                if let Some(material) = self.state.material.clone() {
+                    self.add_object(part.geometry, part.part_to_shape, material);
+                }
+            }
+        }
+    }
+
+    fn create_shape(&self, kind: &str, params: &Params) -> Option<ShapePart> {
+        let phi_max = params.float("phimax", 360f64).to_radians();
+        let portion = phi_max.max(0f64).min(TWO_PI) / TWO_PI;
+        match kind {
+            "sphere" => {
+                let radius = params.float("radius", 1f64);
+                let (z_min, z_max) = (params.float("zmin", -radius), params.float("zmax", radius));
+                Some(ShapePart {
+                    geometry: Arc::new(Sphere::partial(radius, z_min, z_max, phi_max)),
+                    part_to_shape: z_up(),
+                    area: TWO_PI * radius * (z_max - z_min).abs().min(2f64 * radius) * portion,
+                    center: Point::uniform(0f64),
+                })
+            }
+            "cylinder" => {
+                let radius = params.float("radius", 1f64);
+                let (z_min, z_max) = (params.float("zmin", -1f64), params.float("zmax", 1f64));
+                Some(ShapePart {
+                    geometry: Arc::new(Cylinder::new(radius, z_min, z_max, phi_max)),
+                    part_to_shape: z_up(),
+                    area: TWO_PI * radius * (z_max - z_min).abs() * portion,
+                    center: Point::new(0f64, (z_min + z_max) / 2f64, 0f64),
+                })
+            }
+            "disk" => {
+                let (height, radius, inner_radius) = (params.float("height", 0f64), params.float("radius", 1f64), params.float("innerradius", 0f64));
+                Some(ShapePart {
+                    geometry: Arc::new(Disk::new(height, radius, inner_radius, phi_max)),
+                    part_to_shape: z_up(),
+                    area: TWO_PI / 2f64 * (radius * radius - inner_radius * inner_radius) * portion,
+                    center: Point::new(0f64, height, 0f64),
+                })
+            }
+            "trianglemesh" => self.create_triangle_mesh(params).map(PbrtImporter::mesh_part),
+            "plymesh" => match params.string("filename") {
+                Some(filename) => Some(PbrtImporter::mesh_part(load_ply(&self.directory.join(filename)).mesh)),
+                None => {
+                    eprintln!("warning: ignoring pbrt plymesh without a filename");
+                    None
+                }
+            },
+            _ => {
+                eprintln!("warning: ignoring pbrt shape of unsupported type \"{}\"", kind);
+                None
+            }
+        }
+    }
+
+    fn create_triangle_mesh(&self, params: &Params) -> Option<TriangleMeshData> {
+        let positions = match params.points("P") {
+            Some(positions) => positions,
+            None => {
+                eprintln!("warning: ignoring pbrt trianglemesh without positions");
+                return None;
+            }
+        };
+        let indices: Vec<usize> = match params.floats("indices") {
+            Some(indices) => indices.iter().map(|&i| i as usize).collect(),
+            None if positions.len() == 3 => vec![0, 1, 2],
+            None => {
+                eprintln!("warning: ignoring pbrt trianglemesh without indices");
+                return None;
+            }
+        };
+        assert!(indices.len() % 3 == 0, "pbrt trianglemesh indices should be a multiple of 3");
+        assert!(indices.iter().all(|&i| i < positions.len()), "pbrt trianglemesh index is out of range");
+
+        let normals = params.floats("N").map(|n| n.chunks(3).filter(|n| n.len() == 3).map(|n| Normal::new(n[0], n[1], n[2])).collect::<Vec<_>>());
+        let uvs = params.floats("uv").or_else(|| params.floats("st")).map(|uv| uv.chunks(2).filter(|uv| uv.len() == 2).map(|uv| Uv(uv[0], uv[1])).collect::<Vec<_>>());
+        let normals = normals.filter(|normals| normals.len() == positions.len());
+        let uvs = uvs.filter(|uvs| uvs.len() == positions.len());
+        // Without normals, pbrt meshes are flat shaded.
+        let smoothing = normals.map_or(Smoothing::None, Smoothing::Explicit);
+        let triangles = indices.chunks(3).map(|t| (t[0], t[1], t[2])).collect();
+        Some(TriangleMeshData::new(positions, smoothing, uvs, triangles))
+    }
+
+    // The mesh's center is its area-weighted average.
+    fn mesh_part(mesh: TriangleMeshData) -> ShapePart {
+        let (mut area, mut sum) = (0f64, Vec3::uniform(0f64));
+        for &(i0, i1, i2) in &mesh.indices {
+            let (p0, p1, p2) = (mesh.positions[i0], mesh.positions[i1], mesh.positions[i2]);
+            let triangle_area = (p1 - p0).cross(p2 - p0).magnitude() / 2f64;
+            area += triangle_area;
+            sum = sum + (p0.into_vector() + p1.into_vector() + p2.into_vector()) * (triangle_area / 3f64);
+        }
+        let center = if area > 0f64 { Point::uniform(0f64) + sum / area } else { Point::uniform(0f64) };
+        ShapePart { geometry: Arc::new(mesh.into_triangle_mesh()), part_to_shape: IDENTITY_MATRIX, area, center }
+    }
+
+    fn create_texture(&mut self, name: &str, value_type: &str, class: &str, params: &Params) {
+        if value_type == "float" {
+            eprintln!("warning: ignoring pbrt float texture \"{}\", since it can't be used by anything we support", name);
+            return;
+        }
+        let texture = match class {
+            "constant" => NamedTexture::Constant(params.color("value", Color::WHITE)),
+            "imagemap" => match params.string("filename") {
+                // Both pbrt and us have v going up, the opposite of the image's rows.
+                Some(filename) if is_readable_image(filename) => NamedTexture::Image(load_image(&self.directory.join(filename))),
+                filename => {
+                    eprintln!("warning: ignoring pbrt image texture {:?}, which isn't in a format we can read", filename);
+                    return;
+                }
+            },
+            _ => {
+                eprintln!("warning: ignoring pbrt texture \"{}\" of unsupported class \"{}\"", name, class);
+                return;
+            }
+        };
+        self.textures.insert(name.to_owned(), texture);
+    }
+
+    fn get_texture(&self, params: &Params, name: &str, default: Color) -> Box<Texture> {
+        match params.get(name) {
+            Some(param) if param.kind == "texture" => {
+                match param.strings.first().and_then(|texture| self.textures.get(texture)) {
+                    Some(&NamedTexture::Constant(color)) => Box::new(color),
+                    Some(&NamedTexture::Image(ref image)) => Box::new(image.clone()),
+                    None => {
+                        eprintln!("warning: ignoring unknown or unsupported pbrt texture {:?}", param.strings.first());
+                        Box::new(default)
+                    }
+                }
+            }
+            _ => Box::new(params.color(name, default)),
+        }
+    }
+
+    // Our materials don't have glossy reflection or Fresnel, so plastic loses its highlights and glass gets its
+    // reflection and transmission split by how much it reflects head-on.
+    fn create_material(&self, kind: &str, params: &Params) -> Option<Arc<Material>> {
+        let material: Arc<Material> = match kind {
+            "" | "none" => return None,
+            "matte" => Arc::new(FlatMaterial { texture: self.get_texture(params, "Kd", Color::new(0.5f64, 0.5f64, 0.5f64)) }),
+            "plastic" => Arc::new(PhongMaterial {
+                diffuse: self.get_texture(params, "Kd", Color::new(0.25f64, 0.25f64, 0.25f64)),
+                specular: Box::new(params.color("Ks", Color::new(0.25f64, 0.25f64, 0.25f64))),
+                reflection: Box::new(Color::BLACK),
+                transmission: Box::new(Color::BLACK),
+                index_of_refraction: 1f64,
+            }),
+            "mirror" => Arc::new(PhongMaterial {
+                diffuse: Box::new(Color::BLACK),
+                specular: Box::new(Color::BLACK),
+                reflection: Box::new(params.color("Kr", Color::new(0.9f64, 0.9f64, 0.9f64))),
+                transmission: Box::new(Color::BLACK),
+                index_of_refraction: 1f64,
+            }),
+            "glass" => {
+                let index_of_refraction = params.float("eta", params.float("index", 1.5f64));
+                let reflectance = ((index_of_refraction - 1f64) / (index_of_refraction + 1f64)).powi(2);
+                Arc::new(PhongMaterial {
+                    diffuse: Box::new(Color::BLACK),
+                    specular: Box::new(Color::BLACK),
+                    reflection: Box::new(params.color("Kr", Color::WHITE) * reflectance),
+                    transmission: Box::new(params.color("Kt", Color::WHITE) * (1f64 - reflectance)),
+                    index_of_refraction,
+                })
+            }
+            _ => {
+                eprintln!("warning: approximating pbrt {} material with a matte one", kind);
+                Arc::new(FlatMaterial { texture: self.get_texture(params, "Kd", Color::new(0.5f64, 0.5f64, 0.5f64)) })
+            }
+        };
+        Some(material)
+    }
+
+    fn add_light(&mut self, kind: &str, params: &Params) {
+        let to_world = Transform::new(&self.to_world * &self.state.transform);
+        let scale = params.color("scale", Color::WHITE);
+        let light = match kind {
+            "point" | "spot" => {
+                if kind == "spot" {
+                    eprintln!("warning: approximating pbrt spot light with a point light");
+                }
+                let position = params.point("from", Point::uniform(0f64));
+                ImportedLight::Point(position.transform(&to_world), params.color("I", Color::WHITE) * scale)
+            }
+            "distant" => {
+                let direction = params.point("to", Point::new(0f64, 0f64, 1f64)) - params.point("from", Point::uniform(0f64));
+                ImportedLight::Directional(direction.transform(&to_world), params.color("L", Color::WHITE) * scale)
+            }
+            "infinite" => {
+                eprintln!("warning: approximating pbrt infinite light with the background color");
+                if params.get("mapname").is_some() {
+                    eprintln!("warning: ignoring pbrt infinite light's environment map");
+                }
+                self.scene.background_color = Some(params.color("L", Color::WHITE) * scale);
+                return;
+            }
+            _ => {
+                eprintln!("warning: ignoring pbrt light of unsupported type \"{}\"", kind);
+                return;
+            }
+        };
+        self.scene.lights.push(light);
+    }
+
+    // The camera is built at the end, once the film's size is known. pbrt cameras look down their +z.
+    fn finish(mut self) -> ImportedScene {
+        let dimensions = self.film.as_ref().map(|film| (film.float("xresolution", 640f64) as u32, film.float("yresolution", 480f64) as u32));
+        self.scene.image_dimensions = dimensions;
+        if let Some((camera_to_world, kind, params)) = self.camera.take() {
+            let to_world = Transform::new(&self.to_world * camera_to_world);
+            let position = Point::uniform(0f64).transform(&to_world);
+            let look_at = Point::new(0f64, 0f64, 1f64).transform(&to_world);
+            let up = Vec3::Y_AXIS.transform(&to_world);
+            let screen_size = params.floats("screenwindow").filter(|window| window.len() == 4).map(|w| (w[1] - w[0], w[3] - w[2]));
+            self.scene.camera = Some(match kind.as_str() {
+                "orthographic" => CameraBuilder::Orthographic((position, look_at, up, screen_size)),
+                _ => {
+                    if kind != "perspective" {
+                        eprintln!("warning: approximating pbrt {} camera with a perspective one", kind);
+                    }
+                    CameraBuilder::Perspective((position, look_at, up, screen_size), params.float("fov", 90f64))
+                }
+            });
+        }
+        self.scene
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn import(source: &str) -> ImportedScene {
+        let mut importer = PbrtImporter::new(Path::new("."));
+        importer.run(source);
+        importer.finish()
+    }
+
+    #[test]
+    fn it_should_parse_directives_and_parameters() {
+        let statements = group_statements(tokenize(r#"
+            # A comment with "quotes" and [brackets].
+            Translate 1 2 -3.5e1
+            Shape "trianglemesh" "point P" [0 0 0 1 0 0 0 1 0] "integer indices" [0 1 2]
+                "bool smooth" true "string name" "a \"b\""
+            ActiveTransform StartTime
+            WorldEnd
+        "#));
+        assert_eq!(statements.len(), 4);
+        assert_eq!(numbers("Translate", &statements[0].arguments, 3), vec![1f64, 2f64, -35f64]);
+
+        let (kind, params) = split_arguments("Shape", &statements[1].arguments, 1);
+        assert_eq!(kind, vec!["trianglemesh".to_owned()]);
+        assert_eq!(params.points("P").unwrap().len(), 3);
+        assert_eq!(params.floats("indices").unwrap(), &[0f64, 1f64, 2f64]);
+        assert!(params.bool("smooth", false));
+        assert_eq!(params.string("name"), Some("a \"b\""));
+        assert_eq!(params.get("name").unwrap().kind, "string");
+
+        assert_eq!(statements[2].arguments, vec![Token::Word("StartTime".to_owned())]);
+        assert!(statements[3].arguments.is_empty());
+    }
+
+    #[test]
+    fn it_should_place_shapes_lights_and_the_camera() {
+        let scene = import(r#"
+            LookAt 0 0 -10  0 0 0  0 1 0
+            Camera "perspective" "float fov" 45
+            Film "image" "integer xresolution" 200 "integer yresolution" 100
+            Sampler "halton"
+            WorldBegin
+            LightSource "point" "rgb I" [5 5 5] "point from" [1 2 3]
+            LightSource "distant" "point from" [0 1 0] "point to" [0 0 0]
+            AttributeBegin
+              Translate 3 0 0
+              Material "glass"
+              Shape "sphere" "float radius" 2
+            AttributeEnd
+            Shape "trianglemesh" "point P" [0 0 0 1 0 0 0 1 0]
+            Shape "hyperboloid"
+            WorldEnd
+        "#);
+
+        assert_eq!(scene.image_dimensions, Some((200, 100)));
+        match scene.camera.unwrap() {
+            CameraBuilder::Perspective((position, look_at, up, _), fov) => {
+                assert!((position.z + 10f64).abs() < 1e-9 && look_at.z > position.z && up.y > 0.99f64);
+                assert_eq!(fov, 45f64);
+            }
+            _ => panic!("expected a perspective camera"),
+        }
+
+        // The world is mirrored across x.
+        assert_eq!(scene.lights.len(), 2);
+        match scene.lights[0] {
+            ImportedLight::Point(position, intensity) => {
+                assert!((position.x + 1f64).abs() < 1e-9 && (position.z - 3f64).abs() < 1e-9);
+                assert_eq!(intensity.r, 5f64);
+            }
+            _ => panic!("expected a point light"),
+        }
+        match scene.lights[1] {
+            ImportedLight::Directional(direction, _) => assert!(direction.y < -0.99f64),
+            _ => panic!("expected a directional light"),
+        }
+
+        assert_eq!(scene.objects.len(), 2);
+        let bound = scene.objects[0].bound();
+        assert!((bound.min.x + 5f64).abs() < 1e-6 && (bound.max.x + 1f64).abs() < 1e-6);
+        assert!((bound.max.z - 2f64).abs() < 1e-6);
+        let bound = scene.objects[1].bound();
+        assert!((bound.min.x + 1f64).abs() < 1e-6 && (bound.max.y - 1f64).abs() < 1e-6);
+    }
+
+    #[test]
+    fn it_should_approximate_area_lights_and_keep_named_materials() {
+        let scene = import(r#"
+            WorldBegin
+            MakeNamedMaterial "shiny" "string type" "mirror"
+            MakeNamedMaterial "hidden" "string type" "none"
+            AttributeBegin
+              AreaLightSource "diffuse" "rgb L" [2 2 2]
+              Translate 0 4 0
+              Scale 2 2 2
+              Shape "trianglemesh" "point P" [-1 0 -1 1 0 -1 1 0 1 -1 0 1] "integer indices" [0 1 2 0 2 3]
+            AttributeEnd
+            NamedMaterial "shiny"
+            Shape "sphere"
+            NamedMaterial "hidden"
+            Shape "sphere"
+            Unknown "directive" 1 2 3
+            LightSource "infinite" "rgb L" [0.1 0.2 0.3]
+        "#);
+
+        // A 4x4 emitter with a radiance of 2 has an intensity of 2 * 16 / 4.
+        assert_eq!(scene.lights.len(), 1);
+        match scene.lights[0] {
+            ImportedLight::Point(position, intensity) => {
+                assert!((position - Point::new(0f64, 4f64, 0f64)).magnitude() < 1e-9);
+                assert!((intensity.g - 8f64).abs() < 1e-9);
+            }
+            _ => panic!("expected a point light"),
+        }
+        assert_eq!(scene.objects.len(), 1);
+        assert_eq!(scene.background_color.unwrap().b, 0.3f64);
+        assert!(scene.camera.is_none() && scene.image_dimensions.is_none());
+    }
+}
@@ -0,0 +1,417 @@
+use std::path::Path;
+use std::str::{ self, FromStr };
+use core::*;
+use math::*;
+use geometry::*;
+use file_utils::*;
+
+// What's read from a PLY file. Vertex colors are kept alongside the mesh since meshes have nowhere to put
+// them.
+#[derive(Debug)]
+pub struct PlyMesh {
+    pub mesh: TriangleMeshData,
+    pub colors: Option<Vec<Color>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScalarType {
+    Int8,
+    Uint8,
+    Int16,
+    Uint16,
+    Int32,
+    Uint32,
+    Float32,
+    Float64,
+}
+
+impl ScalarType {
+    fn from_name(name: &str) -> ScalarType {
+        match name {
+            "char" | "int8" => ScalarType::Int8,
+            "uchar" | "uint8" => ScalarType::Uint8,
+            "short" | "int16" => ScalarType::Int16,
+            "ushort" | "uint16" => ScalarType::Uint16,
+            "int" | "int32" => ScalarType::Int32,
+            "uint" | "uint32" => ScalarType::Uint32,
+            "float" | "float32" => ScalarType::Float32,
+            "double" | "float64" => ScalarType::Float64,
+            _ => panic!("unknown ply property type \"{}\"", name),
+        }
+    }
+
+    fn size(&self) -> usize {
+        match *self {
+            ScalarType::Int8 | ScalarType::Uint8 => 1,
+            ScalarType::Int16 | ScalarType::Uint16 => 2,
+            ScalarType::Int32 | ScalarType::Uint32 | ScalarType::Float32 => 4,
+            ScalarType::Float64 => 8,
+        }
+    }
+
+    // What integer colors are out of; float colors are already 0 to 1.
+    fn color_scale(&self) -> f64 {
+        match *self {
+            ScalarType::Uint8 | ScalarType::Int8 => 255f64,
+            ScalarType::Uint16 | ScalarType::Int16 => 65535f64,
+            _ => 1f64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PropertyType {
+    Scalar(ScalarType),
+    // The type of the length, then the type of the items.
+    List(ScalarType, ScalarType),
+}
+
+#[derive(Debug)]
+struct Property {
+    name: String,
+    property_type: PropertyType,
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+// Everything is read as f64, which holds every value of every PLY type exactly.
+trait ValueReader {
+    fn read(&mut self, scalar_type: ScalarType) -> f64;
+}
+
+struct AsciiReader<'a> {
+    values: str::SplitWhitespace<'a>,
+}
+
+impl<'a> ValueReader for AsciiReader<'a> {
+    fn read(&mut self, _scalar_type: ScalarType) -> f64 {
+        let value = self.values.next().expect("ply file ended early");
+        f64::from_str(value).expect(&format!("ply file has a bad value \"{}\"", value))
+    }
+}
+
+struct BinaryReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    is_big_endian: bool,
+}
+
+impl<'a> ValueReader for BinaryReader<'a> {
+    fn read(&mut self, scalar_type: ScalarType) -> f64 {
+        let size = scalar_type.size();
+        assert!(self.position + size <= self.bytes.len(), "ply file ended early");
+        let mut raw = [0u8; 8];
+        raw[..size].copy_from_slice(&self.bytes[self.position..self.position + size]);
+        self.position += size;
+        if self.is_big_endian {
+            raw[..size].reverse();
+        }
+        let u = u64::from_le_bytes(raw);
+        match scalar_type {
+            ScalarType::Int8 => u as u8 as i8 as f64,
+            ScalarType::Uint8 => u as u8 as f64,
+            ScalarType::Int16 => u as u16 as i16 as f64,
+            ScalarType::Uint16 => u as u16 as f64,
+            ScalarType::Int32 => u as u32 as i32 as f64,
+            ScalarType::Uint32 => u as u32 as f64,
+            ScalarType::Float32 => f32::from_bits(u as u32) as f64,
+            ScalarType::Float64 => f64::from_bits(u),
+        }
+    }
+}
+
+pub fn load_ply(path: &Path) -> PlyMesh {
+    parse_ply(&read_file_bytes(path))
+}
+
+fn parse_header(header: &str) -> (Format, Vec<Element>) {
+    let mut lines = header.lines().map(|line| line.trim());
+    assert_eq!(lines.next(), Some("ply"), "ply files must start with \"ply\"");
+    let mut format = None;
+    let mut elements = Vec::<Element>::new();
+    for line in lines {
+        let values: Vec<&str> = line.split_whitespace().collect();
+        match values.first().cloned() {
+            Some("format") => {
+                format = Some(match values.get(1).cloned() {
+                    Some("ascii") => Format::Ascii,
+                    Some("binary_little_endian") => Format::BinaryLittleEndian,
+                    Some("binary_big_endian") => Format::BinaryBigEndian,
+                    _ => panic!("unknown ply format \"{}\"", line),
+                });
+            }
+            Some("element") => {
+                assert_eq!(values.len(), 3, "ply element declarations need a name and a count");
+                elements.push(Element {
+                    name: values[1].to_owned(),
+                    count: usize::from_str(values[2]).expect("ply file has a bad element count"),
+                    properties: vec![],
+                });
+            }
+            Some("property") => {
+                let element = elements.last_mut().expect("ply property declared before any element");
+                let property = if values.get(1) == Some(&"list") {
+                    assert_eq!(values.len(), 5, "ply list properties need two types and a name");
+                    Property {
+                        name: values[4].to_owned(),
+                        property_type: PropertyType::List(ScalarType::from_name(values[2]), ScalarType::from_name(values[3])),
+                    }
+                } else {
+                    assert_eq!(values.len(), 3, "ply properties need a type and a name");
+                    Property { name: values[2].to_owned(), property_type: PropertyType::Scalar(ScalarType::from_name(values[1])) }
+                };
+                element.properties.push(property);
+            }
+            Some("comment") | Some("obj_info") | None => {}
+            _ => panic!("unknown ply header line \"{}\"", line),
+        }
+    }
+    (format.expect("ply header has no format"), elements)
+}
+
+// Where in a vertex's scalar properties each thing we care about is, if it's there at all.
+struct VertexLayout {
+    position: [usize; 3],
+    normal: Option<[usize; 3]>,
+    uv: Option<[usize; 2]>,
+    color: Option<([usize; 3], f64)>,
+}
+
+impl VertexLayout {
+    fn new(element: &Element) -> VertexLayout {
+        let find = |name: &str| element.properties.iter().position(|property| property.name == name);
+        let find_all = |names: &[&str]| names.iter().map(|name| find(name)).collect::<Option<Vec<usize>>>();
+        let position = find_all(&["x", "y", "z"]).expect("ply vertices need x, y and z");
+        let normal = find_all(&["nx", "ny", "nz"]).map(|i| [i[0], i[1], i[2]]);
+        let uv = [["u", "v"], ["s", "t"], ["texture_u", "texture_v"], ["texture_s", "texture_t"]]
+            .iter()
+            .filter_map(|names| find_all(names))
+            .next()
+            .map(|i| [i[0], i[1]]);
+        let color = find_all(&["red", "green", "blue"]).map(|i| {
+            let scale = match element.properties[i[0]].property_type {
+                PropertyType::Scalar(scalar_type) => scalar_type.color_scale(),
+                PropertyType::List(..) => panic!("ply vertex colors cannot be lists"),
+            };
+            ([i[0], i[1], i[2]], scale)
+        });
+        VertexLayout { position: [position[0], position[1], position[2]], normal, uv, color }
+    }
+}
+
+// Reads every property of one element, with lists' items one after another.
+fn read_element(reader: &mut ValueReader, element: &Element, values: &mut Vec<f64>, list: &mut Vec<f64>, list_index: Option<usize>) {
+    values.clear();
+    list.clear();
+    for (i, property) in element.properties.iter().enumerate() {
+        match property.property_type {
+            PropertyType::Scalar(scalar_type) => values.push(reader.read(scalar_type)),
+            PropertyType::List(count_type, item_type) => {
+                let count = reader.read(count_type) as usize;
+                for _ in 0..count {
+                    let item = reader.read(item_type);
+                    if Some(i) == list_index {
+                        list.push(item);
+                    }
+                }
+                values.push(count as f64);
+            }
+        }
+    }
+}
+
+fn parse_ply(bytes: &[u8]) -> PlyMesh {
+    const END_HEADER: &[u8] = b"end_header";
+    let header_end = bytes
+        .windows(END_HEADER.len())
+        .position(|window| window == END_HEADER)
+        .expect("ply file has no end_header");
+    let body_start = bytes[header_end..]
+        .iter()
+        .position(|&byte| byte == b'\n')
+        .map_or(bytes.len(), |i| header_end + i + 1);
+    let header = str::from_utf8(&bytes[..header_end]).expect("ply header is not text");
+    let (format, elements) = parse_header(header);
+
+    let body = &bytes[body_start..];
+    let mut reader: Box<ValueReader> = match format {
+        Format::Ascii => Box::new(AsciiReader { values: str::from_utf8(body).expect("ascii ply file is not text").split_whitespace() }),
+        Format::BinaryLittleEndian => Box::new(BinaryReader { bytes: body, position: 0, is_big_endian: false }),
+        Format::BinaryBigEndian => Box::new(BinaryReader { bytes: body, position: 0, is_big_endian: true }),
+    };
+
+    let mut positions = Vec::<Point>::new();
+    let mut normals = Vec::<Normal>::new();
+    let mut uvs = Vec::<Uv>::new();
+    let mut colors = Vec::<Color>::new();
+    let mut indices = Vec::<TriangleIndices>::new();
+    let mut layout = None;
+    let (mut values, mut list) = (vec![], vec![]);
+
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => {
+                let l = VertexLayout::new(element);
+                positions.reserve(element.count);
+                for _ in 0..element.count {
+                    read_element(&mut *reader, element, &mut values, &mut list, None);
+                    positions.push(Point::new(values[l.position[0]], values[l.position[1]], values[l.position[2]]));
+                    if let Some(n) = l.normal {
+                        normals.push(Normal::new(values[n[0]], values[n[1]], values[n[2]]));
+                    }
+                    if let Some(uv) = l.uv {
+                        uvs.push(Uv(values[uv[0]], values[uv[1]]));
+                    }
+                    if let Some((c, scale)) = l.color {
+                        colors.push(Color::new(values[c[0]], values[c[1]], values[c[2]]) / scale);
+                    }
+                }
+                layout = Some(l);
+            }
+            "face" => {
+                let list_index = element.properties
+                    .iter()
+                    .position(|property| property.name == "vertex_indices" || property.name == "vertex_index")
+                    .expect("ply faces need vertex_indices");
+                indices.reserve(element.count * 2);
+                for _ in 0..element.count {
+                    read_element(&mut *reader, element, &mut values, &mut list, Some(list_index));
+                    assert!(list.len() >= 3, "ply faces need at least 3 vertices");
+                    // Triangles and quads are what's expected, which fans handle fine.
+                    let face: Vec<usize> = list.iter().map(|&i| i as usize).collect();
+                    indices.extend((1..face.len() - 1).map(|i| (face[0], face[i], face[i + 1])));
+                }
+            }
+            // Edges, materials and anything else still have to be read past.
+            _ => {
+                for _ in 0..element.count {
+                    read_element(&mut *reader, element, &mut values, &mut list, None);
+                }
+            }
+        }
+    }
+
+    let layout = layout.expect("ply file has no vertices");
+    assert!(indices.iter().all(|&(a, b, c)| a.max(b).max(c) < positions.len()), "ply face index is out of range");
+    let smoothing = if layout.normal.is_some() { Smoothing::Explicit(normals) } else { Smoothing::Implicit };
+    PlyMesh {
+        mesh: TriangleMeshData::new(positions, smoothing, if layout.uv.is_some() { Some(uvs) } else { None }, indices),
+        colors: if layout.color.is_some() { Some(colors) } else { None },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+property float confidence
+element face 1
+property uchar flags
+property list uchar int vertex_indices
+end_header
+";
+
+    fn check_quad(ply: PlyMesh) {
+        assert_eq!(ply.mesh.positions.len(), 4);
+        assert_eq!(ply.mesh.positions[2].x, 1f64);
+        assert_eq!(ply.mesh.positions[2].y, 1f64);
+        assert_eq!(ply.mesh.indices, vec![(0, 1, 2), (0, 2, 3)]);
+        assert!(ply.mesh.uvs.is_none());
+        assert!(ply.mesh.normals.unwrap()[0].z > 0f64);
+        assert_eq!(ply.colors.unwrap()[1].r, 1f64);
+    }
+
+    #[test]
+    fn it_should_read_ascii() {
+        let source = format!("ply\nformat ascii 1.0\ncomment made by hand\n{}{}", HEADER, "
+            0 0 0 0 0 0 0.5
+            1 0 0 255 0 0 0.5
+            1 1 0 0 255 0 0.5
+            0 1 0 0 0 255 0.5
+            7 4 0 1 2 3
+        ");
+        check_quad(parse_ply(source.as_bytes()));
+    }
+
+    #[test]
+    fn it_should_read_either_binary_byte_order() {
+        for &(format, is_big_endian) in &[("binary_little_endian", false), ("binary_big_endian", true)] {
+            let mut bytes = format!("ply\nformat {} 1.0\n{}", format, HEADER).into_bytes();
+            {
+                let mut push = |raw: &mut [u8]| {
+                    if is_big_endian {
+                        raw.reverse();
+                    }
+                    bytes.extend_from_slice(raw);
+                };
+                let corners = [(0f32, 0f32, [0u8, 0, 0]), (1f32, 0f32, [255, 0, 0]), (1f32, 1f32, [0, 255, 0]), (0f32, 1f32, [0, 0, 255])];
+                for &(x, y, color) in &corners {
+                    for &value in &[x, y, 0f32] {
+                        push(&mut value.to_bits().to_le_bytes());
+                    }
+                    for &channel in &color {
+                        push(&mut [channel]);
+                    }
+                    push(&mut 0.5f32.to_bits().to_le_bytes());
+                }
+                push(&mut [7u8]);
+                push(&mut [4u8]);
+                for i in 0..4i32 {
+                    push(&mut i.to_le_bytes());
+                }
+            }
+            check_quad(parse_ply(&bytes));
+        }
+    }
+
+    #[test]
+    fn it_should_use_normals_and_uvs_and_skip_other_elements() {
+        let source = "ply
+format ascii 1.0
+element vertex 3
+property double x
+property double y
+property double z
+property float nx
+property float ny
+property float nz
+property float s
+property float t
+element edge 1
+property int vertex1
+property int vertex2
+element face 1
+property list uchar uint vertex_index
+end_header
+0 0 0 0 1 0 0 0
+1 0 0 0 1 0 1 0
+0 0 1 0 1 0 0 1
+0 1
+3 0 2 1
+";
+        let ply = parse_ply(source.as_bytes());
+        assert_eq!(ply.mesh.indices, vec![(0, 2, 1)]);
+        assert_eq!(ply.mesh.normals.unwrap()[2].y, 1f64);
+        assert_eq!(ply.mesh.uvs.unwrap()[2].1, 1f64);
+        assert!(ply.colors.is_none());
+    }
+}
@@ -1,8 +1,13 @@
 use std::collections::HashMap;
 use std::boxed::Box;
+use std::path::{ Path, PathBuf };
 use std::sync::Arc;
 use core::*;
 use math::*;
+use light::*;
+use geometry::TriangleMeshData;
+use exporter::format_mesh;
+use file_utils::write_file_bytes;
 
 #[derive(Default)]
 pub struct SceneBuilder {
@@ -14,12 +19,41 @@ pub struct SceneBuilder {
     depth_limit: Option<u32>,
     background_color: Option<Color>,
     materials: HashMap<String, Arc<Material>>,
+    geometries: HashMap<String, RegisteredGeometry>,
     // TODO: Should transform be an Arc instead? Feels like this can get expensive.
     transform_stack: Vec<Transform>,
+    groups: Vec<GroupBuilder>,
+    // Formatted meshes and where they go, held until the whole scene has parsed.
+    exports: Vec<(PathBuf, Vec<u8>)>,
     pub objects: Vec<SceneObject>,
     pub lights: Vec<LightType>,
 }
 
+// A group that's still being parsed. Its transforms and objects are local to it; when it ends, they're
+// built into a SceneGroup and placed in the enclosing group (or the scene) as a single object.
+struct GroupBuilder {
+    name: Option<String>,
+    material: Option<Arc<Material>>,
+    group_to_parent: Transform,
+    transform_stack: Vec<Transform>,
+    objects: Vec<SceneObject>,
+    is_missing_materials: bool,
+}
+
+// A geometry as objects refer to it. Groups and imported models whose every object has a material are
+// self-contained, so they can be placed without one.
+#[derive(Debug, Clone)]
+pub struct RegisteredGeometry {
+    pub geometry: Arc<Geometry>,
+    pub is_self_contained: bool,
+}
+
+impl RegisteredGeometry {
+    pub fn new(geometry: Arc<Geometry>) -> RegisteredGeometry {
+        RegisteredGeometry { geometry, is_self_contained: false }
+    }
+}
+
 pub type CameraCommon = (Point, Point, Vec3, Option<ScreenSize>);
 
 #[derive(Debug, Clone, Copy)]
@@ -28,6 +62,23 @@ pub enum CameraBuilder {
     Perspective(CameraCommon, f64)
 }
 
+// Lights from other formats, before they're placed in the scene.
+#[derive(Debug, Clone, Copy)]
+pub enum ImportedLight {
+    Point(Point, Color),
+    Directional(Vec3, Color),
+}
+
+// A whole scene from another format, in its own coordinate system.
+#[derive(Debug)]
+pub struct ImportedScene {
+    pub objects: Vec<SceneObject>,
+    pub lights: Vec<ImportedLight>,
+    pub camera: Option<CameraBuilder>,
+    pub image_dimensions: Option<(u32, u32)>,
+    pub background_color: Option<Color>,
+}
+
 fn camera_to_world(common: CameraCommon) -> Mat4 {
     Mat4::create_look_at(common.0, common.1, common.2).invert().unwrap()
 }
@@ -74,8 +125,53 @@ impl SceneBuilder {
         self.materials.insert(key, Arc::from(material));
     }
 
+    pub fn register_geometry(&mut self, name: &str, geometry: Box<Geometry>) {
+        self.register_shared_geometry(name, RegisteredGeometry::new(Arc::from(geometry)));
+    }
+
+    pub fn register_shared_geometry(&mut self, name: &str, geometry: RegisteredGeometry) {
+        let key = name.to_owned();
+        if self.geometries.contains_key(&key) {
+            panic!("cannot redefine geometry \"{}\"", key);
+        }
+        self.geometries.insert(key, geometry);
+    }
+
+    // Groups up the objects of a model from another format, like an OBJ file with its materials. It's
+    // self-contained if every object came with a material.
+    pub fn import_model(&mut self, objects: Vec<SceneObject>) -> RegisteredGeometry {
+        assert!(!objects.is_empty(), "cannot import a model with nothing in it");
+        let is_self_contained = objects.iter().all(|object| object.material.is_some());
+        RegisteredGeometry {
+            geometry: Arc::new(SceneGroup::new(objects, None)),
+            is_self_contained,
+        }
+    }
+
+    pub fn get_geometry(&self, name: &str) -> RegisteredGeometry {
+        self.geometries.get(name).expect(format!("no geometry named \"{}\" defined", name).as_str()).clone()
+    }
+
+    pub fn get_material(&self, name: &str) -> Arc<Material> {
+        Arc::clone(self.materials.get(name).expect(format!("no material named \"{}\" defined", name).as_str()))
+    }
+
+    fn transform_stack(&self) -> &Vec<Transform> {
+        match self.groups.last() {
+            Some(group) => &group.transform_stack,
+            None => &self.transform_stack,
+        }
+    }
+
+    fn transform_stack_mut(&mut self) -> &mut Vec<Transform> {
+        match self.groups.last_mut() {
+            Some(group) => &mut group.transform_stack,
+            None => &mut self.transform_stack,
+        }
+    }
+
     pub fn get_current_transform(&self) -> Transform {
-        match self.transform_stack.last() {
+        match self.transform_stack().last() {
             Some(transform) => transform.clone(),
             None => IDENTITY_TRANSFORM.clone(),
         }
@@ -83,12 +179,12 @@ impl SceneBuilder {
 
     pub fn push_transform(&mut self, mat: Mat4) {
         let new_transform_matrix = mat * self.get_current_transform().m;
-        self.transform_stack.push(Transform::new(new_transform_matrix));
+        self.transform_stack_mut().push(Transform::new(new_transform_matrix));
     }
 
     pub fn pop_n_transforms(&mut self, count: u32) {
         for _ in 0..count {
-            match self.transform_stack.pop() {
+            match self.transform_stack_mut().pop() {
                 Some(_) => {},
                 None => { panic!("tried to pop an empty transform stack"); },
             };
@@ -96,17 +192,122 @@ impl SceneBuilder {
     }
 
     pub fn pop_all_transforms(&mut self) {
-        let count = self.transform_stack.len() as u32;
+        let count = self.transform_stack().len() as u32;
         self.pop_n_transforms(count);
     }
 
-    pub fn add_object(&mut self, partial_object: (Box<Geometry>, &str)) {
+    pub fn add_object(&mut self, partial_object: (RegisteredGeometry, Option<&str>, Option<AlphaMask>)) {
+        let (RegisteredGeometry { geometry, is_self_contained }, material_name, alpha) = partial_object;
+        let material = material_name.map(|name| self.get_material(name));
+        if material.is_none() && !is_self_contained && !self.groups.iter().any(|g| g.material.is_some()) {
+            panic!("objects must specify a material unless they are inside a group that sets one");
+        }
+        let object = SceneObject {
+            shape: Shape::new(geometry, self.get_current_transform()),
+            material,
+            alpha,
+        };
+        self.push_object(object, is_self_contained);
+    }
+
+    fn push_object(&mut self, object: SceneObject, is_self_contained: bool) {
+        let is_missing_material = object.material.is_none() && !is_self_contained;
+        match self.groups.last_mut() {
+            Some(group) => {
+                group.is_missing_materials = group.is_missing_materials || is_missing_material;
+                group.objects.push(object);
+            }
+            None => {
+                if is_missing_material {
+                    panic!("objects must specify a material unless they are inside a group that sets one");
+                }
+                self.objects.push(object);
+            }
+        }
+    }
+
+    pub fn begin_group(&mut self, name: Option<&str>, material_name: Option<&str>) {
+        let group = GroupBuilder {
+            name: name.map(|n| n.to_owned()),
+            material: material_name.map(|n| self.get_material(n)),
+            group_to_parent: self.get_current_transform(),
+            transform_stack: vec![],
+            objects: vec![],
+            is_missing_materials: false,
+        };
+        self.groups.push(group);
+    }
+
+    pub fn end_group(&mut self) {
+        let group = self.groups.pop().expect("tried to end a group that was never started");
+        let is_self_contained = group.material.is_some() || !group.is_missing_materials;
+
+        if group.objects.is_empty() {
+            eprintln!("warning: ignoring empty group{}", group.name.as_ref().map(|n| format!(" \"{}\"", n)).unwrap_or(String::new()));
+            return;
+        }
+
+        let geometry: Arc<Geometry> = Arc::new(SceneGroup::new(group.objects, group.material));
+
+        if let Some(ref name) = group.name {
+            self.register_shared_geometry(name, RegisteredGeometry { geometry: Arc::clone(&geometry), is_self_contained });
+        }
+
+        let object = SceneObject {
+            shape: Shape::new(geometry, group.group_to_parent),
+            material: None,
+            alpha: None,
+        };
+        self.push_object(object, is_self_contained);
+    }
+
+    // Places a scene from another format with the current transform. Its objects become one model, and its
+    // camera, image dimensions and background are only used if the scene file hasn't set them.
+    pub fn import_scene(&mut self, scene: ImportedScene) {
         let transform = self.get_current_transform();
-        let material_name = partial_object.1.to_owned();
-        self.objects.push(SceneObject {
-            shape: Shape::new(Arc::from(partial_object.0), transform),
-            material: Arc::clone(self.materials.get(&material_name).expect(format!("no texture named \"{}\" defined", material_name).as_str())),
-        });
+        if !scene.objects.is_empty() {
+            let model = self.import_model(scene.objects);
+            self.add_object((model, None, None));
+        }
+        for light in scene.lights {
+            self.add_light(match light {
+                ImportedLight::Point(position, intensity) => {
+                    LightType::Delta(Box::new(PointLight::new(position.transform(&transform), intensity)))
+                }
+                ImportedLight::Directional(direction, radiance) => {
+                    LightType::Delta(Box::new(DirectionalLight::new(direction.transform(&transform), radiance)))
+                }
+            });
+        }
+        if self.camera.is_none() {
+            let place = |(position, look_at, up, screen_size): CameraCommon| {
+                (position.transform(&transform), look_at.transform(&transform), up.transform(&transform), screen_size)
+            };
+            self.camera = scene.camera.map(|camera| match camera {
+                CameraBuilder::Orthographic(common) => CameraBuilder::Orthographic(place(common)),
+                CameraBuilder::Perspective(common, fov) => CameraBuilder::Perspective(place(common), fov),
+            });
+        }
+        if self.image_dimensions.is_none() {
+            self.image_dimensions = scene.image_dimensions;
+        }
+        if self.background_color.is_none() {
+            self.background_color = scene.background_color;
+        }
+    }
+
+    pub fn add_export(&mut self, mesh: &TriangleMeshData, path: &Path) {
+        match format_mesh(mesh, path) {
+            Ok(bytes) => self.exports.push((path.to_path_buf(), bytes)),
+            Err(reason) => eprintln!("warning: {}; skipping it", reason),
+        }
+    }
+
+    // Only called once parsing succeeds, so a scene with a mistake further down doesn't leave files behind.
+    pub fn write_exports(&self) {
+        for &(ref path, ref bytes) in &self.exports {
+            write_file_bytes(path, bytes);
+        }
     }
 
     pub fn add_light(&mut self, light: LightType) {
@@ -145,3 +346,126 @@ impl SceneBuilder {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use super::*;
+    use importer::parser::SceneFileParser;
+
+    fn parse_source(source: &str) -> SceneBuilder {
+        let mut builder = SceneBuilder::new();
+        SceneFileParser::new().parse(&mut builder, Path::new("."), source).expect("test scene should parse");
+        builder
+    }
+
+    const MATERIALS: &str = "
+        material red flat { texture 1 0 0 }
+        material blue flat { texture 0 0 1 }
+    ";
+
+    #[test]
+    fn it_should_share_one_geometry_between_instances() {
+        let builder = parse_source(&format!("{}
+            geometry orb sphere {{ radius 1 }}
+            object {{ geometry orb material red }}
+            transform translate 3 0 0
+            object {{ geometry orb material blue }}
+        ", MATERIALS));
+        let (first, second) = (builder.get_geometry("orb").geometry, builder.get_geometry("orb").geometry);
+        assert!(Arc::ptr_eq(&first, &second));
+        // The definition, both objects and the two handles above.
+        assert_eq!(Arc::strong_count(&first), 5);
+        assert_eq!(builder.objects.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot redefine geometry \"orb\"")]
+    fn it_should_not_allow_redefining_a_geometry() {
+        parse_source("
+            geometry orb sphere { radius 1 }
+            geometry orb sphere { radius 2 }
+        ");
+    }
+
+    // A red snowman whose eye, in a nested blue group, is raised by a transform that's local to the outer group.
+    fn snowman() -> SceneBuilder {
+        parse_source(&format!("{}
+            group snowman {{
+                material red
+                object {{ geometry sphere {{ radius 1 }} }}
+                transform translate 0 3 0
+                group {{
+                    material blue
+                    object {{ geometry sphere {{ radius 0.25 }} }}
+                }}
+            }}
+            object {{ geometry sphere {{ radius 1 }} material blue }}
+        ", MATERIALS))
+    }
+
+    fn material_hit_from(builder: &SceneBuilder, object: usize, origin: Point) -> Arc<Material> {
+        let ray = Ray::half_infinite(origin, -Vec3::Z_AXIS);
+        builder.objects[object].intersect(&ray).expect("ray should hit").material.expect("hit should have a material")
+    }
+
+    #[test]
+    fn it_should_let_the_innermost_group_material_win() {
+        let builder = snowman();
+        let (red, blue) = (builder.get_material("red"), builder.get_material("blue"));
+        assert!(Arc::ptr_eq(&material_hit_from(&builder, 0, Point::new(0f64, 0f64, 5f64)), &red));
+        assert!(Arc::ptr_eq(&material_hit_from(&builder, 0, Point::new(0f64, 3f64, 5f64)), &blue));
+    }
+
+    #[test]
+    fn it_should_scope_transforms_to_their_group() {
+        let builder = snowman();
+        assert_eq!(builder.objects.len(), 2);
+        // The eye was raised inside the group...
+        assert!(builder.objects[0].bound().max.y > 3f64);
+        // ...but nothing after the group was.
+        let bound = builder.objects[1].bound();
+        assert!((bound.min.y + 1f64).abs() < 1e-9 && (bound.max.y - 1f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_should_place_self_contained_groups_without_a_material() {
+        let builder = parse_source(&format!("{}
+            group dome {{
+                object {{ geometry sphere {{ radius 1 }} material red }}
+            }}
+            transform translate 3 0 0
+            object {{ geometry dome }}
+        ", MATERIALS));
+        assert!(builder.get_geometry("dome").is_self_contained);
+        assert_eq!(builder.objects.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "objects must specify a material")]
+    fn it_should_not_place_objects_without_a_material() {
+        parse_source("object { geometry sphere { radius 1 } }");
+    }
+
+    const TRIANGLE: &str = "triangle_mesh { positions [0 0 0, 1 0 0, 0 1 0] indices [0 1 2] }";
+
+    #[test]
+    fn it_should_hold_exports_until_asked_to_write_them() {
+        let builder = parse_source(&format!("{}
+            object {{ geometry export \"out/triangle.ply\" {} material red }}
+        ", MATERIALS, TRIANGLE));
+        assert_eq!(builder.exports.len(), 1);
+        assert_eq!(builder.exports[0].0, PathBuf::from("out/triangle.ply"));
+        assert!(builder.exports[0].1.starts_with(b"ply\n"));
+        assert_eq!(builder.objects.len(), 1);
+    }
+
+    #[test]
+    fn it_should_skip_exports_with_unknown_extensions() {
+        let builder = parse_source(&format!("{}
+            object {{ geometry export \"out/triangle.stl\" {} material red }}
+        ", MATERIALS, TRIANGLE));
+        assert!(builder.exports.is_empty());
+        assert_eq!(builder.objects.len(), 1);
+    }
+}
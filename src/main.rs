@@ -7,14 +7,19 @@ extern crate terminal_size;
 extern crate ordered_float;
 extern crate rayon;
 extern crate noise;
+extern crate json;
 #[macro_use]
 extern crate lalrpop_util;
 
 #[macro_use]
 mod math;
+#[macro_use]
+mod stats;
 
 mod bxdf;
 mod core;
+mod distance_field;
+mod exporter;
 mod file_utils;
 mod geometry;
 mod image_utils;
@@ -41,6 +46,7 @@ use core::*;
 use scene::Scene;
 use renderer::Renderer;
 use progress_bar::ProgressBar;
+use stats::{ PixelStats, StatsCollector };
 
 fn seconds_since(t: SystemTime) -> f64 {
     let duration = t.elapsed().unwrap();
@@ -61,13 +67,25 @@ macro_rules! log_timing {
 }
 
 fn main() {
-    let mut args = env::args();
-    args.next(); // Skip executable name.
+    let mut scene_file_path: Option<String> = None;
+    let mut should_render_heatmap = false;
+
+    for arg in env::args().skip(1) { // Skip executable name.
+        match arg.as_str() {
+            "--heatmap" => { should_render_heatmap = true; },
+            _ => {
+                if scene_file_path.is_some() {
+                    panic!("more than one scene file provided");
+                }
+                scene_file_path = Some(arg);
+            },
+        }
+    }
 
-    let scene_file_path = args.next().expect("exactly one argument required, but got zero");
+    let scene_file_path = scene_file_path.expect("exactly one scene file required, but got zero");
 
-    if args.count() > 0 {
-        panic!("more than one argument provided");
+    if should_render_heatmap && !stats::ENABLED {
+        panic!("--heatmap requires traversal statistics; rebuild with --features stats");
     }
 
     let scene_file = log_timing!(
@@ -84,9 +102,9 @@ fn main() {
     ].iter().collect();
     create_dir_all(&output_directory).expect("could not create output directory");
 
-    let get_output_filename = move |i: u32| -> Box<Path> {
+    let get_output_filename = move |i: u32, suffix: &str| -> Box<Path> {
         let mut p = output_directory.clone();
-        p.push(Path::new(&format!("{:03}.png", i)));
+        p.push(Path::new(&format!("{:03}{}.png", i, suffix)));
         p.into_boxed_path()
     };
 
@@ -120,10 +138,13 @@ fn main() {
         scene_file.parameters,
         moving_camera.clone());
 
+    let mut collected_stats = StatsCollector::new();
+
     for frame_number in 0..frame_count {
         progress.increment_frame();
 
         let mut img = RgbImage::new(width, height);
+        collected_stats.begin_frame();
 
         (0..width)
             .into_par_iter()
@@ -131,23 +152,38 @@ fn main() {
                 (0..height)
                     .map(|image_y| {
                         let color = renderer.render_pixel(image_x, image_y);
+                        let stats = stats::take_pixel_stats();
                         progress.increment_operations(1);
-                        (image_x, image_y, color)
+                        (image_x, image_y, color, stats)
                     })
-                    .collect::<Vec<(u32, u32, Color)>>()
+                    .collect::<Vec<(u32, u32, Color, PixelStats)>>()
             })
-            .collect::<Vec<Vec<(u32, u32, Color)>>>()
+            .collect::<Vec<Vec<(u32, u32, Color, PixelStats)>>>()
             .into_iter()
             .flat_map(|v| v.into_iter())
-            .for_each(|(x, y, color)| {
+            .for_each(|(x, y, color, stats)| {
                 img.put_pixel(x, y, *Rgb::from_slice(&color.as_bytes()));
+                collected_stats.add_pixel(x, y, stats);
             });
 
 
-        let ref mut output_file = File::create(&get_output_filename(frame_number)).expect("error creating output file");
+        let ref mut output_file = File::create(&get_output_filename(frame_number, "")).expect("error creating output file");
         image::ImageRgb8(img).save(output_file, image::PNG).expect("error saving image");
 
+        #[cfg(feature = "stats")]
+        {
+            if should_render_heatmap {
+                let ref mut heatmap_file = File::create(&get_output_filename(frame_number, "-heatmap")).expect("error creating heatmap file");
+                image::ImageRgb8(collected_stats.render_heatmap(width, height)).save(heatmap_file, image::PNG).expect("error saving heatmap");
+            }
+        }
+
         moving_camera = moving_camera.transform(&scene_file.animation.1);
         renderer = renderer.with_camera(moving_camera.clone());
     }
+
+    #[cfg(feature = "stats")]
+    {
+        eprintln!("{}", collected_stats.total());
+    }
 }
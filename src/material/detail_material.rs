@@ -0,0 +1,126 @@
+use core::*;
+use math::*;
+
+// How far apart in uv a bump texture is sampled to find its slope.
+const BUMP_DELTA: f64 = 0.0005;
+
+#[derive(Debug)]
+pub enum SurfaceDetail {
+    // A tangent-space normal per texel, with red along u, green along v and blue out of the surface.
+    NormalMap(Box<Texture>),
+    // Heights, from the texture's average, that are scaled and then treated as if the surface were displaced
+    // along its normal by them.
+    Bump { texture: Box<Texture>, scale: f64 },
+}
+
+// Wraps any material so that it shades with a perturbed normal, for detail that isn't in the geometry.
+#[derive(Debug)]
+pub struct DetailMaterial {
+    pub material: Box<Material>,
+    pub detail: SurfaceDetail,
+}
+
+impl Material for DetailMaterial {
+    fn get_bsdf(&self, intersection: &Intersection) -> Bsdf {
+        let base = intersection.shading_geometry.unwrap_or(intersection.geometry);
+        let normal = base.normal.into_vector().into_normalized();
+        let perturbed = match self.detail {
+            SurfaceDetail::NormalMap(ref texture) => normal_from_map(texture.get_color(intersection.uv), normal, &base),
+            SurfaceDetail::Bump { ref texture, scale } => normal_from_bump(texture.as_ref(), scale, intersection.uv, normal, &intersection.geometry),
+        };
+        let shading_geometry = match perturbed {
+            Some(perturbed) => axes_for_normal(perturbed, base.u_axis),
+            None => base,
+        };
+        self.material.get_bsdf(&Intersection {
+            shading_geometry: Some(shading_geometry),
+            ..intersection.clone()
+        })
+    }
+}
+
+// u follows the surface's as closely as the new normal allows, so anisotropic bxdfs keep their orientation.
+fn axes_for_normal(normal: Vec3, u_axis: Vec3) -> IntersectionGeometry {
+    let u_axis = u_axis - normal * normal.dot(&u_axis);
+    if u_axis.magnitude2() > 0f64 {
+        let u_axis = u_axis.into_normalized();
+        IntersectionGeometry::new(u_axis, normal.cross(u_axis))
+    } else {
+        IntersectionGeometry::from_normal(normal)
+    }
+}
+
+fn normal_from_map(color: Color, normal: Vec3, base: &IntersectionGeometry) -> Option<Vec3> {
+    let tangent = base.u_axis - normal * normal.dot(&base.u_axis);
+    if tangent.magnitude2() == 0f64 {
+        return None;
+    }
+    let tangent = tangent.into_normalized();
+    // v decides which way green points, so maps still work where uvs are mirrored.
+    let bitangent = normal.cross(tangent);
+    let bitangent = if bitangent.dot(&base.v_axis) < 0f64 { -bitangent } else { bitangent };
+    let (x, y, z) = (2f64 * color.r - 1f64, 2f64 * color.g - 1f64, 2f64 * color.b - 1f64);
+    let perturbed = tangent * x + bitangent * y + normal * z;
+    if perturbed.magnitude2() > 0f64 { Some(perturbed.into_normalized()) } else { None }
+}
+
+// Blinn's, with the surface's uv derivatives for the slope but the shading normal for the direction, so smooth
+// shading stays smooth underneath the bumps.
+fn normal_from_bump(texture: &Texture, scale: f64, uv: Uv, normal: Vec3, geometry: &IntersectionGeometry) -> Option<Vec3> {
+    let height = |uv: Uv| texture.get_color(uv).average() * scale;
+    let Uv(u, v) = uv;
+    let center = height(uv);
+    let (slope_u, slope_v) = (
+        (height(Uv(u + BUMP_DELTA, v)) - center) / BUMP_DELTA,
+        (height(Uv(u, v + BUMP_DELTA)) - center) / BUMP_DELTA,
+    );
+    let (dpdu, dpdv) = (geometry.u_axis, geometry.v_axis);
+    let cross = dpdu.cross(dpdv);
+    if cross.magnitude2() == 0f64 {
+        return None;
+    }
+    // Surfaces whose uvs run the other way around their normal push the bumps the other way too.
+    let orientation = if cross.dot(&normal) < 0f64 { -1f64 } else { 1f64 };
+    let perturbed = normal * cross.magnitude() + (normal.cross(dpdv) * slope_u - normal.cross(dpdu) * slope_v) * orientation;
+    if perturbed.magnitude2() > 0f64 { Some(perturbed.into_normalized()) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flat_geometry() -> IntersectionGeometry {
+        IntersectionGeometry::new(Vec3::new(2f64, 0f64, 0f64), Vec3::new(0f64, 2f64, 0f64))
+    }
+
+    #[derive(Debug)]
+    struct Ramp;
+
+    impl Texture for Ramp {
+        fn get_color(&self, uv: Uv) -> Color {
+            Color::new(uv.0, uv.0, uv.0)
+        }
+    }
+
+    #[test]
+    fn it_should_tilt_the_normal_per_the_map() {
+        let geometry = flat_geometry();
+        let flat = normal_from_map(Color::new(0.5f64, 0.5f64, 1f64), Vec3::Z_AXIS, &geometry).unwrap();
+        assert!((flat - Vec3::Z_AXIS).magnitude() < 1e-9);
+
+        let tilted = normal_from_map(Color::new(1f64, 0.5f64, 1f64), Vec3::Z_AXIS, &geometry).unwrap();
+        assert!((tilted - Vec3::new(1f64, 0f64, 1f64).into_normalized()).magnitude() < 1e-9);
+
+        // Mirrored v flips green.
+        let mirrored = IntersectionGeometry { v_axis: -geometry.v_axis, ..geometry };
+        let tilted = normal_from_map(Color::new(0.5f64, 1f64, 1f64), Vec3::Z_AXIS, &mirrored).unwrap();
+        assert!((tilted - Vec3::new(0f64, -1f64, 1f64).into_normalized()).magnitude() < 1e-9);
+    }
+
+    #[test]
+    fn it_should_lean_bumps_away_from_rising_heights() {
+        // Height rises along u by 1 per unit of u, which is 2 units of distance, so the slope is 1/2.
+        let bumped = normal_from_bump(&Ramp, 1f64, Uv(0.5f64, 0.5f64), Vec3::Z_AXIS, &flat_geometry()).unwrap();
+        assert!((bumped - Vec3::new(-1f64, 0f64, 2f64).into_normalized()).magnitude() < 1e-6);
+    }
+}
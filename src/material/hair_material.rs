@@ -0,0 +1,36 @@
+use core::*;
+use bxdf::*;
+
+// Where the hair's color comes from.
+#[derive(Debug)]
+pub enum HairAbsorption {
+    SigmaA(Color),
+    // The overall color, which is much easier to pick.
+    Reflectance(Box<Texture>),
+    Melanin { eumelanin: f64, pheomelanin: f64 },
+}
+
+// For curves, whose v runs across the width, which is where the hair BSDF needs to know it was hit.
+#[derive(Debug)]
+pub struct HairMaterial {
+    pub absorption: HairAbsorption,
+    pub index_of_refraction: f64,
+    pub beta_m: f64,
+    pub beta_n: f64,
+    // Degrees.
+    pub alpha: f64,
+}
+
+impl Material for HairMaterial {
+    fn get_bsdf(&self, intersection: &Intersection) -> Bsdf {
+        let sigma_a = match self.absorption {
+            HairAbsorption::SigmaA(sigma_a) => sigma_a,
+            HairAbsorption::Reflectance(ref texture) => Hair::sigma_a_from_reflectance(texture.get_color(intersection.uv), self.beta_n),
+            HairAbsorption::Melanin { eumelanin, pheomelanin } => Hair::sigma_a_from_melanin(eumelanin, pheomelanin),
+        };
+        let h = (2f64 * intersection.uv.1 - 1f64).max(-1f64).min(1f64);
+        Bsdf::new(vec![
+            Box::new(Hair::new(h, self.index_of_refraction, sigma_a, self.beta_m, self.beta_n, self.alpha.to_radians()))
+        ], intersection)
+    }
+}
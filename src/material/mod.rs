@@ -1,7 +1,11 @@
+mod detail_material;
 mod flat_material;
+mod hair_material;
 mod measured_material;
 mod phong_material;
 
+pub use self::detail_material::*;
 pub use self::flat_material::*;
+pub use self::hair_material::*;
 pub use self::measured_material::*;
 pub use self::phong_material::*;
@@ -1,10 +1,11 @@
-use math::xyz::Point;
+use math::xyz::{ Point, Vec3 };
 
 pub trait Curve {
     // t: 0-1
     fn at(&self, t: f64) -> Point;
 }
 
+#[derive(Debug, Clone, Copy)]
 pub struct CubicBezier {
     pub p0: Point,
     pub p1: Point,
@@ -21,6 +22,35 @@ impl Curve for CubicBezier {
     }
 }
 
+impl CubicBezier {
+    pub fn derivative(&self, t: f64) -> Vec3 {
+        (self.p1 - self.p0) * (3f64 * (1f64 - t).powi(2))
+            + (self.p2 - self.p1) * (6f64 * (1f64 - t) * t)
+            + (self.p3 - self.p2) * (3f64 * t.powi(2))
+    }
+
+    // de Casteljau's algorithm at t = 0.5.
+    pub fn split(&self) -> (CubicBezier, CubicBezier) {
+        let midpoint = |a: Point, b: Point| a + (b - a) * 0.5f64;
+        let (p01, p12, p23) = (midpoint(self.p0, self.p1), midpoint(self.p1, self.p2), midpoint(self.p2, self.p3));
+        let (p012, p123) = (midpoint(p01, p12), midpoint(p12, p23));
+        let middle = midpoint(p012, p123);
+        (
+            CubicBezier { p0: self.p0, p1: p01, p2: p012, p3: middle },
+            CubicBezier { p0: middle, p1: p123, p2: p23, p3: self.p3 },
+        )
+    }
+
+    // Chains of beziers written as p0, p1, p2, p3, p1', p2', p3', ..., where each segment starts at the
+    // previous one's end, so there must be 3n + 1 points.
+    pub fn chain_from_points(points: &[Point]) -> Vec<CubicBezier> {
+        assert!(points.len() >= 4 && (points.len() - 1) % 3 == 0, "cubic bezier paths must have exactly 3n + 1 points");
+        points.windows(4).step_by(3)
+            .map(|p| CubicBezier { p0: p[0], p1: p[1], p2: p[2], p3: p[3] })
+            .collect()
+    }
+}
+
 pub struct CurvePath {
     pub curves: Vec<Box<Curve>>,
 }
@@ -60,3 +90,132 @@ impl CurvePath {
         CurvePath { curves: beziers }
     }
 }
+
+// A tensor product of cubic beziers. Control points are in rows of constant v, each running along u, so
+// control_points[4 * j + i] weighs in at the ith along u and the jth along v.
+#[derive(Debug, Clone, Copy)]
+pub struct BicubicBezier {
+    pub control_points: [Point; 16],
+}
+
+impl BicubicBezier {
+    fn row(&self, j: usize) -> CubicBezier {
+        let p = &self.control_points[(4 * j)..(4 * j + 4)];
+        CubicBezier { p0: p[0], p1: p[1], p2: p[2], p3: p[3] }
+    }
+
+    fn column(&self, i: usize) -> CubicBezier {
+        let p = &self.control_points;
+        CubicBezier { p0: p[i], p1: p[4 + i], p2: p[8 + i], p3: p[12 + i] }
+    }
+
+    fn from_rows(rows: [CubicBezier; 4]) -> BicubicBezier {
+        let mut control_points = [Point::uniform(0f64); 16];
+        for (j, row) in rows.iter().enumerate() {
+            control_points[(4 * j)..(4 * j + 4)].copy_from_slice(&[row.p0, row.p1, row.p2, row.p3]);
+        }
+        BicubicBezier { control_points }
+    }
+
+    fn from_columns(columns: [CubicBezier; 4]) -> BicubicBezier {
+        let mut control_points = [Point::uniform(0f64); 16];
+        for (i, column) in columns.iter().enumerate() {
+            for (j, &p) in [column.p0, column.p1, column.p2, column.p3].iter().enumerate() {
+                control_points[4 * j + i] = p;
+            }
+        }
+        BicubicBezier { control_points }
+    }
+
+    // The curve of constant v through the patch.
+    fn curve_along_u(&self, v: f64) -> CubicBezier {
+        let (c0, c1, c2, c3) = (self.column(0), self.column(1), self.column(2), self.column(3));
+        CubicBezier { p0: c0.at(v), p1: c1.at(v), p2: c2.at(v), p3: c3.at(v) }
+    }
+
+    // The curve of constant u through the patch.
+    fn curve_along_v(&self, u: f64) -> CubicBezier {
+        let (r0, r1, r2, r3) = (self.row(0), self.row(1), self.row(2), self.row(3));
+        CubicBezier { p0: r0.at(u), p1: r1.at(u), p2: r2.at(u), p3: r3.at(u) }
+    }
+
+    pub fn at(&self, u: f64, v: f64) -> Point {
+        self.curve_along_u(v).at(u)
+    }
+
+    pub fn partial_u(&self, u: f64, v: f64) -> Vec3 {
+        self.curve_along_u(v).derivative(u)
+    }
+
+    pub fn partial_v(&self, u: f64, v: f64) -> Vec3 {
+        self.curve_along_v(u).derivative(v)
+    }
+
+    // partial_u x partial_v, unnormalized. Patches often collapse an edge to a point (like the top of the
+    // teapot's lid) where that vanishes, in which case the normal just inside the patch stands in.
+    pub fn normal(&self, u: f64, v: f64) -> Vec3 {
+        let normal = self.partial_u(u, v).cross(self.partial_v(u, v));
+        if normal.magnitude2() > 1e-20 {
+            normal
+        } else {
+            let inside = |x: f64| x + (0.5f64 - x) * 1e-4;
+            self.partial_u(inside(u), inside(v)).cross(self.partial_v(inside(u), inside(v)))
+        }
+    }
+
+    // Halves the patch along u, into [0, 0.5] and [0.5, 1].
+    pub fn split_u(&self) -> (BicubicBezier, BicubicBezier) {
+        let halves: Vec<(CubicBezier, CubicBezier)> = (0..4).map(|j| self.row(j).split()).collect();
+        (
+            BicubicBezier::from_rows([halves[0].0, halves[1].0, halves[2].0, halves[3].0]),
+            BicubicBezier::from_rows([halves[0].1, halves[1].1, halves[2].1, halves[3].1]),
+        )
+    }
+
+    // Halves the patch along v, into [0, 0.5] and [0.5, 1].
+    pub fn split_v(&self) -> (BicubicBezier, BicubicBezier) {
+        let halves: Vec<(CubicBezier, CubicBezier)> = (0..4).map(|i| self.column(i).split()).collect();
+        (
+            BicubicBezier::from_columns([halves[0].0, halves[1].0, halves[2].0, halves[3].0]),
+            BicubicBezier::from_columns([halves[0].1, halves[1].1, halves[2].1, halves[3].1]),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // z = x y over [0, 1]^2, which is bilinear and so has evenly spaced control points.
+    fn saddle() -> BicubicBezier {
+        let mut control_points = [Point::uniform(0f64); 16];
+        for j in 0..4 {
+            for i in 0..4 {
+                let (x, y) = (i as f64 / 3f64, j as f64 / 3f64);
+                control_points[4 * j + i] = Point::new(x, y, x * y);
+            }
+        }
+        BicubicBezier { control_points }
+    }
+
+    #[test]
+    fn it_should_evaluate_patches_and_their_partials() {
+        let p = saddle().at(0.25f64, 0.5f64);
+        assert!((p.x - 0.25f64).abs() < 1e-12 && (p.y - 0.5f64).abs() < 1e-12 && (p.z - 0.125f64).abs() < 1e-12);
+        let du = saddle().partial_u(0.25f64, 0.5f64);
+        assert!((du.x - 1f64).abs() < 1e-12 && du.y.abs() < 1e-12 && (du.z - 0.5f64).abs() < 1e-12);
+        assert!(saddle().normal(0.5f64, 0.5f64).z > 0f64);
+    }
+
+    #[test]
+    fn it_should_split_patches_without_changing_them() {
+        let (left, right) = saddle().split_u();
+        let (bottom, top) = saddle().split_v();
+        let expected = saddle().at(0.75f64, 0.25f64);
+        for &actual in &[right.at(0.5f64, 0.25f64), bottom.at(0.75f64, 0.5f64)] {
+            assert!((actual - expected).magnitude() < 1e-12);
+        }
+        assert!((left.at(1f64, 1f64) - right.at(0f64, 1f64)).magnitude() < 1e-12);
+        assert!((top.at(0f64, 0f64) - bottom.at(0f64, 1f64)).magnitude() < 1e-12);
+    }
+}
@@ -3,6 +3,7 @@ mod clamp;
 mod fuzzy_eq;
 mod mat4;
 mod non_nan;
+mod polynomial;
 mod samples;
 #[macro_use]
 mod xyz;
@@ -12,6 +13,7 @@ pub use self::clamp::*;
 pub use self::fuzzy_eq::*;
 pub use self::mat4::*;
 pub use self::non_nan::*;
+pub use self::polynomial::*;
 pub use self::samples::*;
 pub use self::xyz::*;
 
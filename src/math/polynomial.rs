@@ -0,0 +1,232 @@
+// pbrt pg. 118
+pub fn quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    let d = b * b - 4f64 * a * c;
+    if d < 0f64 {
+        None
+    } else {
+        let sqrt_d = d.sqrt();
+        let q = -0.5f64 * (b + (if b < 0f64 { -sqrt_d } else { sqrt_d }));
+        let (t0, t1) = (q / a, c / q);
+        if t0 > t1 {
+            Some((t1, t0))
+        } else {
+            Some((t0, t1))
+        }
+    }
+}
+
+// The largest real root of x^3 + a x^2 + b x + c, which always exists. Cardano's method when there's one
+// real root, Viete's trigonometric one when there are three.
+fn largest_cubic_root(a: f64, b: f64, c: f64) -> f64 {
+    let q = (a * a - 3f64 * b) / 9f64;
+    let r = (2f64 * a * a * a - 9f64 * a * b + 27f64 * c) / 54f64;
+    let shift = a / 3f64;
+    if r * r < q * q * q {
+        let theta = (r / (q * q * q).sqrt()).max(-1f64).min(1f64).acos();
+        -2f64 * q.sqrt() * (theta / 3f64).cos() - shift
+    } else {
+        let s = -r.signum() * (r.abs() + (r * r - q * q * q).sqrt()).cbrt();
+        let t = if s == 0f64 { 0f64 } else { q / s };
+        s + t - shift
+    }
+}
+
+// A couple of steps of Newton's method on the quartic x^4 + a x^3 + b x^2 + c x + d. The closed-form roots can
+// be quite inaccurate when the coefficients vary wildly in magnitude, which this cleans up.
+fn polish_quartic_root(x: f64, a: f64, b: f64, c: f64, d: f64) -> f64 {
+    let mut x = x;
+    for _ in 0..2 {
+        let f = (((x + a) * x + b) * x + c) * x + d;
+        let df = ((4f64 * x + 3f64 * a) * x + 2f64 * b) * x + c;
+        if df == 0f64 {
+            break;
+        }
+        let next = x - f / df;
+        if !next.is_finite() {
+            break;
+        }
+        x = next;
+    }
+    x
+}
+
+// The real roots of a x^4 + b x^3 + c x^2 + d x + e, in increasing order, by Ferrari's method.
+pub fn quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    let (b, c, d, e) = (b / a, c / a, d / a, e / a);
+    // Substituting x = y - b / 4 gives the depressed quartic y^4 + p y^2 + q y + r.
+    let shift = b / 4f64;
+    let p = c - 6f64 * shift * shift;
+    let q = d - 2f64 * c * shift + 8f64 * shift * shift * shift;
+    let r = e - d * shift + c * shift * shift - 3f64 * shift * shift * shift * shift;
+
+    let mut roots = Vec::with_capacity(4);
+    let push_quadratic_roots = |a: f64, b: f64, c: f64, roots: &mut Vec<f64>| {
+        if let Some((y0, y1)) = quadratic(a, b, c) {
+            roots.push(y0);
+            roots.push(y1);
+        }
+    };
+
+    if q.abs() < 1e-12 * (1f64 + p.abs() + r.abs()) {
+        // Biquadratic: a quadratic in y^2.
+        if let Some((z0, z1)) = quadratic(1f64, p, r) {
+            for &z in &[z0, z1] {
+                if z >= 0f64 {
+                    roots.push(z.sqrt());
+                    roots.push(-z.sqrt());
+                }
+            }
+        }
+    } else {
+        // Complete the square with a root m of the resolvent cubic 8m^3 + 8p m^2 + (2p^2 - 8r) m - q^2,
+        // which splits the quartic into (y^2 + s y + t0)(y^2 - s y + t1).
+        let m = largest_cubic_root(p, p * p / 4f64 - r, -q * q / 8f64).max(0f64);
+        let s = (2f64 * m).sqrt();
+        if s == 0f64 {
+            return vec![];
+        }
+        let half = p / 2f64 + m;
+        let offset = q / (2f64 * s);
+        push_quadratic_roots(1f64, s, half - offset, &mut roots);
+        push_quadratic_roots(1f64, -s, half + offset, &mut roots);
+    }
+
+    let mut roots: Vec<f64> = roots.into_iter()
+        .map(|y| polish_quartic_root(y - shift, b, c, d, e))
+        .filter(|x| x.is_finite())
+        .collect();
+    roots.sort_by(|x, y| x.partial_cmp(y).unwrap());
+    roots
+}
+
+// Polynomials in the power basis, lowest degree first.
+pub fn evaluate_polynomial(coefficients: &[f64], x: f64) -> f64 {
+    coefficients.iter().rev().fold(0f64, |sum, c| sum * x + c)
+}
+
+pub fn multiply_polynomials(a: &[f64], b: &[f64]) -> Vec<f64> {
+    let mut product = vec![0f64; a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            product[i + j] += x * y;
+        }
+    }
+    product
+}
+
+pub fn add_polynomials(a: &[f64], b: &[f64]) -> Vec<f64> {
+    (0..a.len().max(b.len()))
+        .map(|i| a.get(i).cloned().unwrap_or(0f64) + b.get(i).cloned().unwrap_or(0f64))
+        .collect()
+}
+
+// The real roots of a polynomial of any degree in [low, high], in increasing order. Consecutive roots of
+// the derivative split the interval into pieces where the polynomial is monotonic, each of which holds
+// at most one root that bisection can find. Roots where the polynomial only touches zero (i.e. of even
+// multiplicity) are generally missed.
+pub fn polynomial_roots_between(coefficients: &[f64], low: f64, high: f64) -> Vec<f64> {
+    const BISECTION_STEPS: usize = 64;
+
+    let scale = coefficients.iter().fold(0f64, |max, c| max.max(c.abs()));
+    let degree = match coefficients.iter().rposition(|c| c.abs() > scale * 1e-14) {
+        Some(degree) => degree,
+        None => { return vec![]; }
+    };
+    let coefficients = &coefficients[..(degree + 1)];
+    if degree == 0 {
+        return vec![];
+    } else if degree == 1 {
+        let root = -coefficients[0] / coefficients[1];
+        return if root >= low && root <= high { vec![root] } else { vec![] };
+    }
+
+    let derivative: Vec<f64> = coefficients.iter().enumerate().skip(1).map(|(i, c)| c * i as f64).collect();
+    let mut boundaries = vec![low];
+    boundaries.extend(polynomial_roots_between(&derivative, low, high));
+    boundaries.push(high);
+
+    let mut roots: Vec<f64> = vec![];
+    for pair in boundaries.windows(2) {
+        let (mut a, mut b) = (pair[0], pair[1]);
+        let (value_a, value_b) = (evaluate_polynomial(coefficients, a), evaluate_polynomial(coefficients, b));
+        if value_a == 0f64 {
+            if roots.last().map_or(true, |&last| last < a) {
+                roots.push(a);
+            }
+            continue;
+        }
+        if (value_a < 0f64) == (value_b < 0f64) && value_b != 0f64 {
+            continue;
+        }
+        let a_is_negative = value_a < 0f64;
+        for _ in 0..BISECTION_STEPS {
+            let middle = (a + b) / 2f64;
+            if (evaluate_polynomial(coefficients, middle) < 0f64) == a_is_negative {
+                a = middle;
+            } else {
+                b = middle;
+            }
+        }
+        roots.push((a + b) / 2f64);
+    }
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_roots(actual: Vec<f64>, expected: &[f64]) {
+        assert_eq!(actual.len(), expected.len(), "{:?} != {:?}", actual, expected);
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-9, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn it_should_find_four_distinct_roots() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        assert_roots(quartic(1f64, -10f64, 35f64, -50f64, 24f64), &[1f64, 2f64, 3f64, 4f64]);
+    }
+
+    #[test]
+    fn it_should_find_two_roots_of_a_quartic_with_complex_ones() {
+        // (x - 1)(x + 2)(x^2 + 1)
+        assert_roots(quartic(2f64, 2f64, -2f64, 2f64, -4f64), &[-2f64, 1f64]);
+    }
+
+    #[test]
+    fn it_should_solve_biquadratics() {
+        // (x^2 - 1)(x^2 - 4)
+        assert_roots(quartic(1f64, 0f64, -5f64, 0f64, 4f64), &[-2f64, -1f64, 1f64, 2f64]);
+    }
+
+    #[test]
+    fn it_should_find_no_roots_when_there_are_none() {
+        assert_roots(quartic(1f64, 0f64, 2f64, 0f64, 5f64), &[]);
+    }
+
+    #[test]
+    fn it_should_handle_widely_spread_roots() {
+        // (x - 0.001)(x - 1)(x - 1000)(x + 5)
+        let roots = [0.001f64, 1f64, 1000f64, -5f64];
+        let (sum, product) = (roots.iter().sum::<f64>(), roots.iter().product::<f64>());
+        let pairs = roots[0] * roots[1] + roots[0] * roots[2] + roots[0] * roots[3] + roots[1] * roots[2] + roots[1] * roots[3] + roots[2] * roots[3];
+        let triples = roots[0] * roots[1] * roots[2] + roots[0] * roots[1] * roots[3] + roots[0] * roots[2] * roots[3] + roots[1] * roots[2] * roots[3];
+        assert_roots(quartic(1f64, -sum, pairs, -triples, product), &[-5f64, 0.001f64, 1f64, 1000f64]);
+    }
+
+    #[test]
+    fn it_should_find_roots_of_high_degree_polynomials_in_an_interval() {
+        // (x - 0.1)(x - 0.2)(x - 0.5)(x - 0.9)(x - 2)(x + 1)
+        let polynomial = [-0.1f64, -0.2f64, -0.5f64, -0.9f64, -2f64, 1f64].iter()
+            .fold(vec![1f64], |product, &root| multiply_polynomials(&product, &[root, 1f64]));
+        assert_roots(polynomial_roots_between(&polynomial, 0f64, 1f64), &[0.1f64, 0.2f64, 0.5f64, 0.9f64]);
+    }
+
+    #[test]
+    fn it_should_evaluate_and_add_polynomials() {
+        let sum = add_polynomials(&[1f64, 2f64], &[0f64, 0f64, 3f64]);
+        assert_eq!(evaluate_polynomial(&sum, 2f64), 1f64 + 4f64 + 12f64);
+    }
+}
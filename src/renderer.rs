@@ -79,6 +79,7 @@ impl Renderer {
     }
 
     fn generate_ray(&self, image_x: u32, image_y: u32) -> Ray {
+        record_stat!(CameraRay);
         self.camera.get_ray(image_x as f64, image_y as f64)
     }
 
@@ -92,6 +93,7 @@ impl Renderer {
             (1f64 + sample_y as f64) / antialias as f64
         );
 
+        record_stat!(CameraRay);
         let x_jitter = rng.next_f64() * (x_max - x_min) + x_min;
         let y_jitter = rng.next_f64() * (y_max - y_min) + y_min;
 
@@ -118,12 +120,7 @@ impl Renderer {
 
         let p = intersection.location;
         let w_o = -ray.direction.as_normalized();
-        let n = {
-            match intersection.shading_geometry {
-                Some(geometry) => geometry.normal,
-                None => intersection.geometry.normal,
-            }
-        }.as_normalized();
+        let n = bsdf.normal();
 
         // TODO: Emission from area lights.
 
@@ -142,7 +139,12 @@ impl Renderer {
         if light_pdf > 0f64 && l_i.is_nonzero() {
             let bsdf_transport = bsdf.evaluate(w_o, w_i, &BXDF_SURFACE_TYPES);
 
-            if bsdf_transport.is_nonzero() && !self.scene.objects.does_intersect(&visibility_ray) {
+            // Only counted when it's actually cast, which the short-circuit below often spares.
+            let is_visible = || {
+                record_stat!(ShadowRay);
+                !self.scene.objects.does_intersect(&visibility_ray)
+            };
+            if bsdf_transport.is_nonzero() && is_visible() {
                 // TODO: Transmittance.
                 match light {
                     // If the light is a delta light, we know that w_i is spot on (because that's how delta lights work)
@@ -191,6 +193,7 @@ impl Renderer {
                                 }
                             };
                             if weight > 0f64 {
+                                record_stat!(SecondaryRay);
                                 let l_i = match self.scene.objects.intersect(&Ray::finite(p, w_i, EPSILON, INFINITY)) {
                                     Some(intersection) => {
                                         // TODO: We'll want to modify Intersection to allow us to check if we hit the right thing.
@@ -227,6 +230,7 @@ impl Renderer {
             match bsdf.choose_and_evaluate(w_o, &mut rng, &vec![(transport, SpectrumType::PerfectSpecular)]) {
                 Some((BxdfSample { color: bsdf_transport, pdf, w_i, }, _)) => {
                     if pdf > 0f64 && bsdf_transport.is_nonzero() && w_i.dot(&n) != 0f64 {
+                        record_stat!(SecondaryRay);
                         bsdf_transport * self.Li(Ray::finite(p, w_i, EPSILON, INFINITY), depth + 1) * (w_i.dot(&n).abs() / pdf)
                     } else {
                         Color::BLACK
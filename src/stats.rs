@@ -0,0 +1,244 @@
+use std::cell::Cell;
+use std::ops::{ Add, AddAssign };
+use std::fmt::{ Display, Formatter, Result };
+use image::{ RgbImage, Rgb, Pixel };
+use core::*;
+
+// Traversal statistics are only collected when built with `--features stats`. Otherwise, record_stat!
+// expands to nothing and every counter reads as zero, so the hot loops don't pay for them.
+#[cfg_attr(not(feature = "stats"), allow(dead_code))]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TraversalStats {
+    pub camera_rays: u64,
+    pub shadow_rays: u64,
+    pub secondary_rays: u64,
+    pub nodes_visited: u64,
+    // Counts every leaf item tested, at every level of nesting (e.g. both scene objects and mesh triangles).
+    pub primitives_tested: u64,
+}
+
+#[cfg_attr(not(feature = "stats"), allow(dead_code))]
+#[derive(Debug, Clone, Copy)]
+pub enum Stat {
+    CameraRay,
+    ShadowRay,
+    SecondaryRay,
+    NodeVisited,
+    PrimitiveTested,
+}
+
+thread_local! {
+    static THREAD_STATS: Cell<TraversalStats> = Cell::new(TraversalStats::default());
+}
+
+pub const ENABLED: bool = cfg!(feature = "stats");
+
+macro_rules! record_stat {
+    ($stat:ident) => {
+        #[cfg(feature = "stats")]
+        {
+            ::stats::record(::stats::Stat::$stat);
+        }
+    };
+}
+
+#[cfg_attr(not(feature = "stats"), allow(dead_code))]
+pub fn record(stat: Stat) {
+    THREAD_STATS.with(|cell| {
+        let mut stats = cell.get();
+        match stat {
+            Stat::CameraRay => stats.camera_rays += 1,
+            Stat::ShadowRay => stats.shadow_rays += 1,
+            Stat::SecondaryRay => stats.secondary_rays += 1,
+            Stat::NodeVisited => stats.nodes_visited += 1,
+            Stat::PrimitiveTested => stats.primitives_tested += 1,
+        }
+        cell.set(stats);
+    });
+}
+
+// Returns everything this thread has recorded since the last call and resets its counters.
+#[cfg_attr(not(feature = "stats"), allow(dead_code))]
+pub fn take_thread_stats() -> TraversalStats {
+    THREAD_STATS.with(|cell| cell.replace(TraversalStats::default()))
+}
+
+// What the render loop carries back for each pixel, which is nothing at all without the feature.
+#[cfg(feature = "stats")]
+pub type PixelStats = TraversalStats;
+#[cfg(not(feature = "stats"))]
+pub type PixelStats = ();
+
+// Pixels are rendered start-to-finish on one thread, so this is exactly the work of the pixel just rendered.
+#[cfg(feature = "stats")]
+pub fn take_pixel_stats() -> PixelStats {
+    take_thread_stats()
+}
+
+#[cfg(not(feature = "stats"))]
+#[inline(always)]
+pub fn take_pixel_stats() -> PixelStats {}
+
+// Each pixel's stats for the current frame's heatmap, plus the total over every frame. Without the feature,
+// it holds nothing and adding to it does nothing.
+#[derive(Default)]
+pub struct StatsCollector {
+    #[cfg(feature = "stats")]
+    pixels: Vec<(u32, u32, TraversalStats)>,
+    #[cfg(feature = "stats")]
+    total: TraversalStats,
+}
+
+impl StatsCollector {
+    pub fn new() -> StatsCollector {
+        Default::default()
+    }
+
+    pub fn begin_frame(&mut self) {
+        #[cfg(feature = "stats")]
+        {
+            self.pixels.clear();
+        }
+    }
+
+    #[cfg_attr(not(feature = "stats"), allow(unused_variables))]
+    #[inline(always)]
+    pub fn add_pixel(&mut self, x: u32, y: u32, stats: PixelStats) {
+        #[cfg(feature = "stats")]
+        {
+            self.total += stats;
+            self.pixels.push((x, y, stats));
+        }
+    }
+
+    #[cfg(feature = "stats")]
+    pub fn render_heatmap(&self, width: u32, height: u32) -> RgbImage {
+        render_heatmap(width, height, &self.pixels)
+    }
+
+    #[cfg(feature = "stats")]
+    pub fn total(&self) -> TraversalStats {
+        self.total
+    }
+}
+
+#[cfg_attr(not(feature = "stats"), allow(dead_code))]
+impl TraversalStats {
+    pub fn total_rays(&self) -> u64 {
+        self.camera_rays + self.shadow_rays + self.secondary_rays
+    }
+
+    // Arbitrary, but roughly in line with the relative costs the kd-tree's SAH assumes.
+    pub fn cost(&self) -> u64 {
+        self.nodes_visited + self.primitives_tested
+    }
+}
+
+impl Add for TraversalStats {
+    type Output = TraversalStats;
+
+    fn add(self, other: TraversalStats) -> TraversalStats {
+        TraversalStats {
+            camera_rays: self.camera_rays + other.camera_rays,
+            shadow_rays: self.shadow_rays + other.shadow_rays,
+            secondary_rays: self.secondary_rays + other.secondary_rays,
+            nodes_visited: self.nodes_visited + other.nodes_visited,
+            primitives_tested: self.primitives_tested + other.primitives_tested,
+        }
+    }
+}
+
+impl AddAssign for TraversalStats {
+    fn add_assign(&mut self, other: TraversalStats) {
+        *self = *self + other;
+    }
+}
+
+impl Display for TraversalStats {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let per_ray = |count: u64| {
+            let total = self.total_rays();
+            if total > 0 { count as f64 / total as f64 } else { 0f64 }
+        };
+        writeln!(f, "rays: {} camera, {} shadow, {} secondary", self.camera_rays, self.shadow_rays, self.secondary_rays)?;
+        writeln!(f, "nodes visited: {} ({:.1} per ray)", self.nodes_visited, per_ray(self.nodes_visited))?;
+        write!(f, "primitives tested: {} ({:.1} per ray)", self.primitives_tested, per_ray(self.primitives_tested))
+    }
+}
+
+// Maps [0, 1] onto a blue -> cyan -> green -> yellow -> red ramp.
+#[cfg_attr(not(feature = "stats"), allow(dead_code))]
+fn false_color(fraction: f64) -> Color {
+    let scaled = fraction.max(0f64).min(1f64) * 4f64;
+    let step = scaled.floor().min(3f64);
+    let t = scaled - step;
+    match step as u32 {
+        0 => Color::new(0f64, t, 1f64),
+        1 => Color::new(0f64, 1f64, 1f64 - t),
+        2 => Color::new(t, 1f64, 0f64),
+        _ => Color::new(1f64, 1f64 - t, 0f64),
+    }
+}
+
+// Costs are log-scaled, since a handful of pathological pixels would otherwise wash everything else out.
+#[cfg_attr(not(feature = "stats"), allow(dead_code))]
+pub fn render_heatmap(width: u32, height: u32, pixel_stats: &[(u32, u32, TraversalStats)]) -> RgbImage {
+    let max_cost = pixel_stats.iter().map(|&(_, _, ref stats)| stats.cost()).max().unwrap_or(0);
+    let log_max_cost = (1f64 + max_cost as f64).ln();
+
+    let mut img = RgbImage::new(width, height);
+    for &(x, y, ref stats) in pixel_stats {
+        let fraction = if log_max_cost > 0f64 { (1f64 + stats.cost() as f64).ln() / log_max_cost } else { 0f64 };
+        img.put_pixel(x, y, *Rgb::from_slice(&false_color(fraction).as_bytes()));
+    }
+    img
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_accumulate_and_reset_thread_counters() {
+        take_thread_stats();
+        record(Stat::CameraRay);
+        record(Stat::ShadowRay);
+        record(Stat::ShadowRay);
+        record(Stat::NodeVisited);
+        record(Stat::PrimitiveTested);
+        let stats = take_thread_stats();
+        assert_eq!((stats.camera_rays, stats.shadow_rays, stats.secondary_rays), (1, 2, 0));
+        assert_eq!(stats.total_rays(), 3);
+        assert_eq!(stats.cost(), 2);
+        assert_eq!(take_thread_stats().total_rays(), 0);
+    }
+
+    #[test]
+    fn it_should_add_stats_field_by_field() {
+        let a = TraversalStats { camera_rays: 1, shadow_rays: 2, secondary_rays: 3, nodes_visited: 4, primitives_tested: 5 };
+        let mut b = TraversalStats { camera_rays: 10, shadow_rays: 20, secondary_rays: 30, nodes_visited: 40, primitives_tested: 50 };
+        b += a;
+        assert_eq!((b.camera_rays, b.shadow_rays, b.secondary_rays), (11, 22, 33));
+        assert_eq!((b.nodes_visited, b.primitives_tested), (44, 55));
+        assert_eq!((a + a).cost(), 18);
+    }
+
+    #[test]
+    fn it_should_map_heatmap_endpoints_to_blue_and_red() {
+        assert_eq!(false_color(0f64).as_bytes(), [0, 0, 255]);
+        assert_eq!(false_color(1f64).as_bytes(), [255, 0, 0]);
+        // Out of range clamps.
+        assert_eq!(false_color(-1f64).as_bytes(), [0, 0, 255]);
+        assert_eq!(false_color(2f64).as_bytes(), [255, 0, 0]);
+        assert_eq!(false_color(0.5f64).as_bytes(), [0, 255, 0]);
+    }
+
+    #[test]
+    fn it_should_color_the_costliest_pixel_red() {
+        let cheap = TraversalStats::default();
+        let costly = TraversalStats { nodes_visited: 100, ..TraversalStats::default() };
+        let img = render_heatmap(2, 1, &[(0, 0, cheap), (1, 0, costly)]);
+        assert_eq!(img.get_pixel(0, 0).data, [0, 0, 255]);
+        assert_eq!(img.get_pixel(1, 0).data, [255, 0, 0]);
+    }
+}
@@ -0,0 +1,183 @@
+use math::*;
+use geometry::*;
+use core::*;
+
+// A power of two, like every other segment count.
+const MAX_SEGMENTS: usize = 64;
+
+// How many straight segments keep a bezier within tolerance of its chords. A cubic's second derivative is
+// at most 6 times its control points' largest second difference, and a chord strays from a curve by at
+// most 1/8 of that over the square of the number of segments. This only depends on the control points,
+// in either order, so patches sharing an edge agree on it.
+//
+// It's rounded up to a power of two, so of any two counts, one divides the other and the finer division
+// only adds points along the coarser one's chords.
+fn segments_for(curve: &CubicBezier, tolerance: f64) -> usize {
+    let second_difference = |a: Point, b: Point, c: Point| ((a - b) + (c - b)).magnitude();
+    let d = second_difference(curve.p0, curve.p1, curve.p2).max(second_difference(curve.p1, curve.p2, curve.p3));
+    ((0.75f64 * d / tolerance).sqrt().ceil() as usize).max(1).next_power_of_two().min(MAX_SEGMENTS)
+}
+
+// A patch edge as the polyline its neighbor sees.
+struct EdgePolyline {
+    points: Vec<Point>,
+}
+
+impl EdgePolyline {
+    fn new(curve: CubicBezier, tolerance: f64) -> EdgePolyline {
+        let segments = segments_for(&curve, tolerance);
+        EdgePolyline { points: (0..(segments + 1)).map(|k| curve.at(k as f64 / segments as f64)).collect() }
+    }
+
+    fn at(&self, t: f64) -> Point {
+        let segments = self.points.len() - 1;
+        let scaled = t * segments as f64;
+        let k = (scaled.floor() as usize).min(segments - 1);
+        let (a, b) = (self.points[k], self.points[k + 1]);
+        a + (b - a) * (scaled - k as f64)
+    }
+}
+
+// Tessellates each patch into a grid fine enough to stay within tolerance of the surface. Patches can need
+// different resolutions, so to keep neighbors from cracking apart, each boundary vertex is moved onto the
+// polyline that the edge alone calls for, which is the same one the neighbor ends up with. The grid is at
+// least as fine as that polyline and a multiple of it, so every one of the polyline's corners is a vertex.
+pub fn tessellate_bezier_patches(patches: &[BicubicBezier], tolerance: f64) -> TriangleMeshData {
+    assert!(tolerance > 0f64, "bezier patch tessellation tolerance must be positive");
+    let mut positions = Vec::<Point>::new();
+    let mut normals = Vec::<Normal>::new();
+    let mut uvs = Vec::<Uv>::new();
+    let mut indices = Vec::<TriangleIndices>::new();
+
+    for patch in patches {
+        let p = &patch.control_points;
+        let row = |j: usize| CubicBezier { p0: p[4 * j], p1: p[4 * j + 1], p2: p[4 * j + 2], p3: p[4 * j + 3] };
+        let column = |i: usize| CubicBezier { p0: p[i], p1: p[4 + i], p2: p[8 + i], p3: p[12 + i] };
+        // The surface's curves are blends of the rows (or columns), so they're no less flat than the flattest.
+        let u_segments = (0..4).map(|j| segments_for(&row(j), tolerance)).max().unwrap();
+        let v_segments = (0..4).map(|i| segments_for(&column(i), tolerance)).max().unwrap();
+        let (bottom, top) = (EdgePolyline::new(row(0), tolerance), EdgePolyline::new(row(3), tolerance));
+        let (left, right) = (EdgePolyline::new(column(0), tolerance), EdgePolyline::new(column(3), tolerance));
+
+        let start = positions.len();
+        let columns = u_segments + 1;
+        for j in 0..(v_segments + 1) {
+            let v = j as f64 / v_segments as f64;
+            for i in 0..columns {
+                let u = i as f64 / u_segments as f64;
+                positions.push(if j == 0 {
+                    bottom.at(u)
+                } else if j == v_segments {
+                    top.at(u)
+                } else if i == 0 {
+                    left.at(v)
+                } else if i == u_segments {
+                    right.at(v)
+                } else {
+                    patch.at(u, v)
+                });
+                normals.push(patch.normal(u, v).into_normalized().into_normal());
+                uvs.push(Uv(u, v));
+            }
+        }
+
+        // Collapsed edges (like the teapot lid's tip) leave some triangles with no area.
+        let is_degenerate = |a: usize, b: usize, c: usize| {
+            (positions[b] - positions[a]).cross(positions[c] - positions[a]).magnitude2() == 0f64
+        };
+        for j in 0..v_segments {
+            for i in 0..u_segments {
+                let a = start + j * columns + i;
+                let b = a + 1;
+                let c = a + columns;
+                let d = c + 1;
+                for &(x, y, z) in &[(a, b, c), (b, d, c)] {
+                    if !is_degenerate(x, y, z) {
+                        indices.push((x, y, z));
+                    }
+                }
+            }
+        }
+    }
+
+    TriangleMeshData::new(positions, Smoothing::Explicit(normals), Some(uvs), indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::INFINITY;
+    use super::*;
+
+    fn flat_patch(offset: f64) -> BicubicBezier {
+        let mut control_points = [Point::uniform(0f64); 16];
+        for j in 0..4 {
+            for i in 0..4 {
+                control_points[4 * j + i] = Point::new(offset + i as f64 / 3f64, j as f64 / 3f64, 0f64);
+            }
+        }
+        BicubicBezier { control_points }
+    }
+
+    #[test]
+    fn it_should_use_a_single_quad_for_a_flat_patch() {
+        let mesh = tessellate_bezier_patches(&[flat_patch(0f64)], 1e-3f64);
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.indices.len(), 2);
+        assert!(mesh.normals.as_ref().unwrap()[0].z > 1f64 - 1e-9);
+    }
+
+    #[test]
+    fn it_should_keep_shared_edges_together() {
+        // The left patch bulges in the middle, so it's divided more finely than its flat neighbor, even
+        // along the straight edge they share.
+        let mut bulging = flat_patch(-1f64);
+        for &index in &[5, 6, 9, 10] {
+            bulging.control_points[index].z = 0.5f64;
+        }
+        let flat = flat_patch(0f64);
+        let mesh = tessellate_bezier_patches(&[bulging, flat], 1e-3f64);
+        // Every vertex on x = 0 has to lie on the flat patch's edge, which is the straight line z = 0.
+        for p in mesh.positions.iter().filter(|p| p.x.abs() < 1e-12) {
+            assert!(p.z.abs() < 1e-12);
+        }
+        assert!(mesh.positions.iter().filter(|p| p.x.abs() < 1e-12).count() > 4);
+    }
+
+    // Distance from p to the nearest point on the polyline through points, in order.
+    fn distance_to_polyline(p: Point, points: &[Point]) -> f64 {
+        points.windows(2).map(|segment| {
+            let (a, b) = (segment[0], segment[1]);
+            let t = ((p - a).dot(&(b - a)) / (b - a).magnitude2()).max(0f64).min(1f64);
+            (p - (a + (b - a) * t)).magnitude()
+        }).fold(INFINITY, f64::min)
+    }
+
+    #[test]
+    fn it_should_keep_curved_shared_edges_together() {
+        // The shared edge at x = 0 bows out slightly, and the left patch bulges a lot more inside, so each
+        // divides the edge differently.
+        let (mut bulging, mut flat) = (flat_patch(-1f64), flat_patch(0f64));
+        for &index in &[5, 6, 9, 10] {
+            bulging.control_points[index].z = 0.5f64;
+        }
+        for &(left, right) in &[(7, 4), (11, 8)] {
+            bulging.control_points[left].z = 0.05f64;
+            flat.control_points[right].z = 0.05f64;
+        }
+        let edge = |patch: BicubicBezier| {
+            let mesh = tessellate_bezier_patches(&[patch], 1e-3f64);
+            let mut edge: Vec<Point> = mesh.positions.into_iter().filter(|p| p.x.abs() < 1e-12).collect();
+            edge.sort_by(|a, b| a.y.partial_cmp(&b.y).unwrap());
+            edge.dedup_by(|a, b| (*a - *b).magnitude() < 1e-12);
+            edge
+        };
+        let (left, right) = (edge(bulging), edge(flat));
+        assert!(left.len() > right.len(), "{} and {} vertices", left.len(), right.len());
+        for &p in left.iter() {
+            assert!(distance_to_polyline(p, &right) < 1e-12, "{:?} is off the right patch's edge", p);
+        }
+        for &p in right.iter() {
+            assert!(left.iter().any(|&q| (p - q).magnitude() < 1e-12), "{:?} isn't a vertex of the left patch", p);
+        }
+    }
+}
@@ -20,7 +20,7 @@ impl ClothClosure {
     }
 }
 
-// TODO: Switch to TriangleMeshBuilder?
+// TODO: Switch to weld_vertices?
 // Would be slower but much simpler
 pub fn create_cloth(curves: Vec<Box<Curve>>, tessellation_factor: usize, closure: ClothClosure) -> TriangleMeshData {
     let number_of_curves = curves.len();
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use math::*;
 use geometry::*;
 use core::*;
@@ -16,19 +17,169 @@ impl DisplacementMap {
             max,
         }
     }
+
+    fn amount(&self, uv: Uv) -> f64 {
+        self.texture.get_color(uv).average() * (self.max - self.min) + self.min
+    }
 }
 
-pub fn displace_triangle_mesh(map: DisplacementMap, data: TriangleMeshData, smoothing: Smoothing) -> TriangleMeshData {
-    let map_extent = map.max - map.min;
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DisplacementDirection {
+    // Scales positions away from the origin, which only makes sense for meshes like a unit sphere.
+    Radial,
+    // Moves positions along their vertex normals.
+    Normal,
+}
+
+// Displaces each vertex by the map sampled at its UV, after splitting every triangle into four `subdivisions`
+// times so there are enough vertices to pick up the map's detail.
+pub fn displace_triangle_mesh(map: DisplacementMap, data: TriangleMeshData, direction: DisplacementDirection, subdivisions: u32, smoothing: Smoothing) -> TriangleMeshData {
+    let data = (0..subdivisions).fold(data, |data, _| subdivide_in_place(data));
     let uvs = data.uvs.expect("cannot displace a mesh without uvs");
-    let new_positions: Vec<Point> = data.positions
-        .into_iter()
-        .enumerate()
-        .map(|(i, position)| {
-            let scale = map.texture.get_color(uvs[i]).average() * map_extent + map.min;
-            position * scale
-        })
-        .collect();
+    let new_positions: Vec<Point> = match direction {
+        DisplacementDirection::Radial => data.positions
+            .iter()
+            .enumerate()
+            .map(|(i, &position)| position * map.amount(uvs[i]))
+            .collect(),
+        DisplacementDirection::Normal => {
+            // Vertices duplicated along UV seams have to move the same way or the mesh tears open, so that
+            // goes for normals the mesh came with too.
+            let normals = match data.normals {
+                Some(ref normals) => average_by_position(&data.positions, normals),
+                None => welded_normals(&data.positions, &data.indices),
+            };
+            data.positions
+                .iter()
+                .enumerate()
+                .map(|(i, &position)| position + normals[i].as_normalized() * map.amount(uvs[i]))
+                .collect()
+        }
+    };
 
     TriangleMeshData::new(new_positions, smoothing, Some(uvs), data.indices)
 }
+
+// Splits every triangle into four at its edge midpoints without moving anything, unlike Loop subdivision.
+fn subdivide_in_place(data: TriangleMeshData) -> TriangleMeshData {
+    let TriangleMeshData { mut positions, indices, mut normals, mut uvs, .. } = data;
+    let mut midpoints = HashMap::<(usize, usize), usize>::new();
+    let mut new_indices = Vec::<TriangleIndices>::with_capacity(indices.len() * 4);
+    for &(a, b, c) in &indices {
+        let mut midpoint = |i: usize, j: usize| {
+            *midpoints.entry((i.min(j), i.max(j))).or_insert_with(|| {
+                positions.push((positions[i] + positions[j]) * 0.5f64);
+                if let Some(ref mut normals) = normals {
+                    let normal = normals[i] + normals[j];
+                    normals.push(if normal.magnitude2() > 0f64 { normal.into_normalized() } else { normals[i] });
+                }
+                if let Some(ref mut uvs) = uvs {
+                    uvs.push((uvs[i] + uvs[j]) * 0.5f64);
+                }
+                positions.len() - 1
+            })
+        };
+        let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+        new_indices.push((a, ab, ca));
+        new_indices.push((ab, b, bc));
+        new_indices.push((ca, bc, c));
+        new_indices.push((ab, bc, ca));
+    }
+
+    let smoothing = normals.map_or(Smoothing::None, Smoothing::Explicit);
+    TriangleMeshData::new(positions, smoothing, uvs, new_indices)
+}
+
+// Adding zero turns -0 into 0 so they weld.
+fn position_key(p: &Point) -> (u64, u64, u64) {
+    ((p.x + 0f64).to_bits(), (p.y + 0f64).to_bits(), (p.z + 0f64).to_bits())
+}
+
+fn normalized_sums(positions: &[Point], sums: HashMap<(u64, u64, u64), Normal>) -> Vec<Normal> {
+    positions
+        .iter()
+        .map(|p| sums.get(&position_key(p)).cloned().unwrap_or(Normal::uniform(0f64)))
+        .map(|n| if n.magnitude2() > 0f64 { n.into_normalized() } else { n })
+        .collect()
+}
+
+// Area-weighted vertex normals, shared by all vertices at the same position.
+fn welded_normals(positions: &[Point], indices: &[TriangleIndices]) -> Vec<Normal> {
+    let mut sums = HashMap::<(u64, u64, u64), Normal>::new();
+    for &(i0, i1, i2) in indices {
+        let normal = (positions[i1] - positions[i0]).cross(positions[i2] - positions[i0]).into_normal();
+        for &i in &[i0, i1, i2] {
+            let sum = sums.entry(position_key(&positions[i])).or_insert(Normal::uniform(0f64));
+            *sum = *sum + normal;
+        }
+    }
+    normalized_sums(positions, sums)
+}
+
+// The average of the given normals of all vertices at the same position.
+fn average_by_position(positions: &[Point], normals: &[Normal]) -> Vec<Normal> {
+    let mut sums = HashMap::<(u64, u64, u64), Normal>::new();
+    for (position, normal) in positions.iter().zip(normals) {
+        let sum = sums.entry(position_key(position)).or_insert(Normal::uniform(0f64));
+        *sum = *sum + normal.as_normalized();
+    }
+    normalized_sums(positions, sums)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quad() -> TriangleMeshData {
+        TriangleMeshData::new(
+            vec![Point::new(0f64, 0f64, 0f64), Point::new(1f64, 0f64, 0f64), Point::new(1f64, 1f64, 0f64), Point::new(0f64, 1f64, 0f64)],
+            Smoothing::None,
+            Some(vec![Uv(0f64, 0f64), Uv(1f64, 0f64), Uv(1f64, 1f64), Uv(0f64, 1f64)]),
+            vec![(0, 1, 2), (0, 2, 3)],
+        )
+    }
+
+    #[test]
+    fn it_should_displace_along_normals() {
+        let map = DisplacementMap::new(Box::new(Color::new(0.5f64, 0.5f64, 0.5f64)), 0f64, 2f64);
+        let mesh = displace_triangle_mesh(map, quad(), DisplacementDirection::Normal, 0, Smoothing::None);
+        assert!(mesh.positions.iter().all(|p| (p.z - 1f64).abs() < 1e-12));
+    }
+
+    #[test]
+    fn it_should_subdivide_without_moving_anything() {
+        let map = DisplacementMap::new(Box::new(Color::BLACK), 0f64, 1f64);
+        let mesh = displace_triangle_mesh(map, quad(), DisplacementDirection::Normal, 2, Smoothing::None);
+        assert_eq!(mesh.indices.len(), 2 * 16);
+        // A 5 by 5 grid, with the diagonal's vertices shared between both triangles.
+        assert_eq!(mesh.positions.len(), 25);
+        assert!(mesh.positions.iter().all(|p| p.z == 0f64));
+        let uvs = mesh.uvs.unwrap();
+        assert!(mesh.positions.iter().zip(uvs.iter()).all(|(p, uv)| p.x == uv.0 && p.y == uv.1));
+    }
+
+    // A tent folded along x = 0, with the ridge's vertices duplicated on each side, like a UV seam.
+    fn tent(smoothing: Smoothing) -> TriangleMeshData {
+        TriangleMeshData::new(
+            vec![
+                Point::new(-1f64, 0f64, 0f64), Point::new(0f64, 0f64, 1f64), Point::new(0f64, 1f64, 1f64),
+                Point::new(0f64, 0f64, 1f64), Point::new(1f64, 0f64, 0f64), Point::new(0f64, 1f64, 1f64),
+            ],
+            smoothing,
+            Some(vec![Uv(0f64, 0f64), Uv(1f64, 0f64), Uv(1f64, 1f64), Uv(0f64, 0f64), Uv(1f64, 0f64), Uv(0f64, 1f64)]),
+            vec![(0, 1, 2), (3, 4, 5)],
+        )
+    }
+
+    #[test]
+    fn it_should_keep_seams_closed() {
+        // Without normals, and with implicit ones, which aren't shared across the seam.
+        for smoothing in vec![Smoothing::None, Smoothing::Implicit] {
+            let map = DisplacementMap::new(Box::new(Color::WHITE), 0f64, 1f64);
+            let mesh = displace_triangle_mesh(map, tent(smoothing), DisplacementDirection::Normal, 0, Smoothing::None);
+            assert_eq!(mesh.positions[1], mesh.positions[3]);
+            assert_eq!(mesh.positions[2], mesh.positions[5]);
+            assert!(mesh.positions[1].z > 1f64 && mesh.positions[1].x.abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use math::*;
+use geometry::*;
+use core::*;
+
+// The same triangles a Heightfield intersects directly, as a mesh to displace, subdivide or export.
+pub fn tessellate_heightfield(field: &Heightfield) -> TriangleMeshData {
+    let (columns, rows) = (field.columns(), field.rows());
+    let grid = || (0..rows).flat_map(move |j| (0..columns).map(move |i| (i, j)));
+    let positions: Vec<Point> = grid().map(|(i, j)| field.position(i, j)).collect();
+    let normals: Vec<Normal> = grid().map(|(i, j)| field.normal(i, j).into_normal()).collect();
+    let uvs: Vec<Uv> = grid().map(|(i, j)| field.uv(i, j)).collect();
+
+    let index = |(i, j): (usize, usize)| j * columns + i;
+    let mut indices = Vec::<TriangleIndices>::new();
+    for j in 0..(rows - 1) {
+        for i in 0..(columns - 1) {
+            for triangle in Heightfield::cell_triangles(i, j).iter() {
+                indices.push((index(triangle[0]), index(triangle[1]), index(triangle[2])));
+            }
+        }
+    }
+
+    TriangleMeshData::new(positions, Smoothing::Explicit(normals), Some(uvs), indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_agree_with_the_direct_heightfield() {
+        let heights = vec![vec![0f64, 0.5f64, 0.2f64], vec![0.1f64, 1f64, 0.3f64], vec![0f64, 0.4f64, 0.6f64]];
+        let field = Heightfield::new(heights, Vec3::new(2f64, 1f64, 2f64));
+        let mesh = tessellate_heightfield(&field);
+        assert_eq!(mesh.positions.len(), 9);
+        assert_eq!(mesh.indices.len(), 8);
+
+        let mesh = mesh.into_triangle_mesh();
+        let r = Ray::half_infinite(Point::new(0.3f64, 5f64, -0.4f64), -Vec3::Y_AXIS);
+        let (direct, tessellated) = (field.intersect(&r).unwrap(), mesh.intersect(&r).unwrap());
+        assert!((direct.distance - tessellated.distance).abs() < 1e-9);
+        assert!((direct.uv.0 - tessellated.uv.0).abs() < 1e-9 && (direct.uv.1 - tessellated.uv.1).abs() < 1e-9);
+        assert!(tessellated.geometry.normal.as_normalized().dot(&direct.geometry.normal.as_normalized()) > 1f64 - 1e-9);
+    }
+}
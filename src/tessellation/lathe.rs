@@ -0,0 +1,100 @@
+use std::f64::consts::PI;
+use math::*;
+use core::*;
+use geometry::*;
+
+// Tessellates a profile spun around +y, the same surface as Revolution but from any Curve. Profile points
+// are (radius, height) in x and y, and u goes around the axis while v follows the profile, so normals face
+// outward for profiles drawn bottom to top.
+//
+// The seam at u = 0/1 is duplicated so that UVs don't wrap backwards across it, which would break implicit
+// smoothing there; normals are worked out from the profile instead.
+pub fn create_lathe(profile: Box<Curve>, segments: usize, profile_segments: usize) -> TriangleMeshData {
+    assert!(segments >= 3, "a lathe needs at least 3 segments around its axis");
+    assert!(profile_segments >= 1, "a lathe needs at least 1 segment along its profile");
+
+    // The profile's tangent by central differences, one-sided at the ends.
+    let h = 0.25f64 / profile_segments as f64;
+    let tangent = |t: f64| profile.at((t + h).min(1f64)) - profile.at((t - h).max(0f64));
+
+    let columns = segments + 1;
+    let rows = profile_segments + 1;
+    let mut positions = Vec::<Point>::with_capacity(rows * columns);
+    let mut normals = Vec::<Normal>::with_capacity(rows * columns);
+    let mut uvs = Vec::<Uv>::with_capacity(rows * columns);
+    for row in 0..rows {
+        let v = row as f64 / profile_segments as f64;
+        let point = profile.at(v);
+        let direction = tangent(v);
+        for column in 0..columns {
+            let u = column as f64 / segments as f64;
+            let (sin, cos) = (u * 2f64 * PI).sin_cos();
+            positions.push(Point::new(point.x * cos, point.y, -point.x * sin));
+            // The profile's normal in the xy-plane is its tangent turned clockwise, then spun like the point.
+            let normal = Vec3::new(direction.y * cos, -direction.x, -direction.y * sin);
+            normals.push(if normal.magnitude2() > 0f64 { normal.into_normalized() } else { Vec3::Y_AXIS }.into_normal());
+            uvs.push(Uv(u, v));
+        }
+    }
+
+    let mut indices = Vec::<TriangleIndices>::with_capacity(2 * segments * profile_segments);
+    // Triangles that collapse where the profile touches the axis would only get in the way.
+    let is_degenerate = |a: usize, b: usize, c: usize| {
+        (positions[b] - positions[a]).cross(positions[c] - positions[a]).magnitude2() == 0f64
+    };
+    for row in 0..profile_segments {
+        for column in 0..segments {
+            let a = row * columns + column;
+            let b = a + 1;
+            let c = a + columns;
+            let d = c + 1;
+            for &(x, y, z) in &[(a, b, c), (b, d, c)] {
+                if !is_degenerate(x, y, z) {
+                    indices.push((x, y, z));
+                }
+            }
+        }
+    }
+
+    TriangleMeshData::new(positions, Smoothing::Explicit(normals), Some(uvs), indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cone() -> Box<Curve> {
+        Box::new(CubicBezier {
+            p0: Point::new(1f64, 0f64, 0f64),
+            p1: Point::new(2f64 / 3f64, 1f64 / 3f64, 0f64),
+            p2: Point::new(1f64 / 3f64, 2f64 / 3f64, 0f64),
+            p3: Point::new(0f64, 1f64, 0f64),
+        })
+    }
+
+    #[test]
+    fn it_should_lay_out_a_grid_with_a_duplicated_seam() {
+        let mesh = create_lathe(cone(), 8, 4);
+        assert_eq!(mesh.positions.len(), 9 * 5);
+        let uvs = mesh.uvs.as_ref().unwrap();
+        assert_eq!(uvs[8].0, 1f64);
+        assert!((mesh.positions[8] - mesh.positions[0]).magnitude() < 1e-9);
+    }
+
+    #[test]
+    fn it_should_drop_triangles_at_the_apex() {
+        let mesh = create_lathe(cone(), 8, 4);
+        assert_eq!(mesh.indices.len(), 2 * 8 * 4 - 8);
+    }
+
+    #[test]
+    fn it_should_face_outward() {
+        let mesh = create_lathe(cone(), 8, 4);
+        let expected = Vec3::new(1f64, 1f64, 0f64).as_normalized();
+        let normal = mesh.normals.as_ref().unwrap()[0];
+        assert!(normal.dot(&expected) > 1f64 - 1e-9);
+        let (a, b, c) = mesh.indices[0];
+        let p = &mesh.positions;
+        assert!((p[b] - p[a]).cross(p[c] - p[a]).dot(&normal) > 0f64);
+    }
+}
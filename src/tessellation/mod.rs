@@ -1,7 +1,17 @@
+mod bezier_patch;
 mod cloth;
 mod displacement;
+mod heightfield;
+mod lathe;
 mod sphere;
+mod subdivision;
+mod sweep;
 
+pub use self::bezier_patch::*;
 pub use self::cloth::*;
 pub use self::displacement::*;
+pub use self::heightfield::*;
+pub use self::lathe::*;
 pub use self::sphere::*;
+pub use self::subdivision::*;
+pub use self::sweep::*;
@@ -1,8 +1,10 @@
-use std::collections::HashMap;
 use math::*;
 use core::*;
 use geometry::*;
 
+// Vertices from neighboring triangles are computed the same way, so they only need to be close to exactly equal.
+const WELD_EPSILON: f64 = 1e-9f64;
+
 pub fn tessellate_sphere(depth: u32, smoothing: Smoothing) -> TriangleMeshData {
     const X_MAX: Point = Point { x:  1f64, y:  0f64, z:  0f64 };
     const X_MIN: Point = Point { x: -1f64, y:  0f64, z:  0f64 };
@@ -11,10 +13,7 @@ pub fn tessellate_sphere(depth: u32, smoothing: Smoothing) -> TriangleMeshData {
     const Z_MIN: Point = Point { x:  0f64, y:  0f64, z: -1f64 };
     const Z_MAX: Point = Point { x:  0f64, y:  0f64, z:  1f64 };
 
-    let expected_positions = 6usize * 3usize.pow(depth);
-    let expected_triangles = 8usize * 4usize.pow(depth);
-    let hash_precision = 1f64 + 2f64.powi(depth as i32);
-    let mut builder = TriangleMeshBuilder::new_with_expected_size(hash_precision, expected_positions, expected_triangles);
+    let mut triangles = Vec::with_capacity(8usize * 4usize.pow(depth));
     // TODO: move to lazy static
     let starting_triangles = vec![
         (Y_MAX, X_MAX, Z_MIN),
@@ -27,23 +26,20 @@ pub fn tessellate_sphere(depth: u32, smoothing: Smoothing) -> TriangleMeshData {
         (Y_MIN, Z_MIN, X_MAX),
     ];
     for triangle in starting_triangles {
-        divide_triangle(triangle, &mut builder, 0, depth);
+        divide_triangle(triangle, &mut triangles, 0, depth);
     }
 
-    builder.build(smoothing)
+    // Every triangle gets its own vertices, which are welded before smoothing so it sees the neighbors.
+    let positions: Vec<Point> = triangles.iter().flat_map(|&(a, b, c)| vec![a, b, c]).collect();
+    let uvs = positions.iter().map(|&p| sphere_uv_for_normalized_point(p)).collect();
+    let indices = (0..triangles.len()).map(|i| (3 * i, 3 * i + 1, 3 * i + 2)).collect();
+    let welded = weld_vertices(TriangleMeshData::new(positions, Smoothing::None, Some(uvs), indices), WELD_EPSILON);
+    TriangleMeshData::new(welded.positions, smoothing, welded.uvs, welded.indices)
 }
 
-fn divide_triangle(triangle: (Point, Point, Point), builder: &mut TriangleMeshBuilder, current_depth: u32, depth_limit: u32) {
+fn divide_triangle(triangle: (Point, Point, Point), triangles: &mut Vec<(Point, Point, Point)>, current_depth: u32, depth_limit: u32) {
     if current_depth >= depth_limit {
-        builder.add_triangles(
-            &vec![triangle.0, triangle.1, triangle.2],
-            &vec![
-                sphere_uv_for_normalized_point(triangle.0),
-                sphere_uv_for_normalized_point(triangle.1),
-                sphere_uv_for_normalized_point(triangle.2),
-            ],
-            &vec![(0, 1, 2)],
-        );
+        triangles.push(triangle);
     } else {
         let midpoints = (
             (triangle.0 + triangle.1).into_vector().into_normalized().into_point(),
@@ -58,78 +54,23 @@ fn divide_triangle(triangle: (Point, Point, Point), builder: &mut TriangleMeshBu
             (midpoints.0, midpoints.1, midpoints.2),
         ];
         for next_triangle in next_triangles {
-            divide_triangle(next_triangle, builder, next_depth, depth_limit);
+            divide_triangle(next_triangle, triangles, next_depth, depth_limit);
         }
     }
 }
 
-// f64 not hashable ._.
-// Move to xyz.rz?
-#[derive(Debug, Eq, PartialEq, Hash)]
-struct HashableXyz {
-    pub x: usize,
-    pub y: usize,
-    pub z: usize,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-fn as_hashable<T: Xyz>(xyz: &T, precision: f64) -> HashableXyz {
-    HashableXyz {
-        x: (xyz.x() * precision) as usize,
-        y: (xyz.y() * precision) as usize,
-        z: (xyz.z() * precision) as usize,
-    }
-}
-
-// TODO: Should probably be moved into triangle_mesh.rs
-struct TriangleMeshBuilder {
-    precision: f64,
-    positions: Vec<Point>,
-    uvs: Vec<Uv>,
-    indices: Vec<TriangleIndices>,
-    point_mapping: HashMap<HashableXyz, usize>,
-}
-
-impl TriangleMeshBuilder {
-    pub fn new_with_expected_size(precision: f64, expected_positions: usize, expected_triangles: usize) -> TriangleMeshBuilder {
-        TriangleMeshBuilder {
-            precision,
-            positions: Vec::with_capacity(expected_positions),
-            uvs: Vec::with_capacity(expected_positions),
-            indices: Vec::with_capacity(expected_triangles),
-            point_mapping: HashMap::with_capacity(expected_positions),
+    #[test]
+    fn it_should_share_vertices_between_triangles() {
+        let mesh = tessellate_sphere(3, Smoothing::Implicit);
+        // Euler's formula, for 512 triangles and so 768 edges.
+        assert_eq!(mesh.indices.len(), 512);
+        assert_eq!(mesh.positions.len(), 2 + 768 - 512);
+        for (position, normal) in mesh.positions.iter().zip(mesh.normals.as_ref().unwrap()) {
+            assert!((position.into_vector() - normal.into_vector()).magnitude() < 0.05f64);
         }
     }
-
-    pub fn add_triangles(&mut self, positions: &[Point], uvs: &[Uv], indices: &[TriangleIndices]) {
-        let mut local_points_mapping = HashMap::<usize, usize>::new();
-
-        for (local_index, position) in positions.iter().enumerate() {
-            let hashed_position = as_hashable(position, self.precision);
-            let seen_point = self.point_mapping.get(&hashed_position).map(|p| p.clone());
-            let index = match seen_point {
-                Some(p) => p,
-                None => {
-                    let p = self.positions.len();
-                    self.positions.push(*position);
-                    self.uvs.push(uvs[local_index]);
-                    self.point_mapping.insert(hashed_position, p);
-                    p
-                }
-            };
-            local_points_mapping.insert(local_index, index);
-        }
-
-        for triangle in indices {
-            self.indices.push((
-                *local_points_mapping.get(&triangle.0).unwrap(),
-                *local_points_mapping.get(&triangle.1).unwrap(),
-                *local_points_mapping.get(&triangle.2).unwrap(),
-            ));
-        }
-    }
-
-    pub fn build(self, smoothing: Smoothing) -> TriangleMeshData {
-        TriangleMeshData::new(self.positions, smoothing, Some(self.uvs), self.indices)
-    }
 }
-
@@ -0,0 +1,436 @@
+use std::collections::{ HashMap, HashSet };
+use std::f64::consts::PI;
+use math::*;
+use core::*;
+use geometry::*;
+
+// A mesh of arbitrary polygons, for Catmull-Clark subdivision. Faces list their vertices counterclockwise
+// when seen from outside, like TriangleMeshData's triangles do.
+pub struct PolygonMesh {
+    pub positions: Vec<Point>,
+    pub uvs: Option<Vec<Uv>>,
+    pub faces: Vec<Vec<usize>>,
+}
+
+impl PolygonMesh {
+    pub fn new(positions: Vec<Point>, uvs: Option<Vec<Uv>>, faces: Vec<Vec<usize>>) -> PolygonMesh {
+        for face in &faces {
+            assert!(face.len() >= 3, "polygon mesh faces need at least 3 vertices");
+            assert!(face.iter().all(|&i| i < positions.len()), "polygon mesh face refers to a missing vertex");
+        }
+        if let Some(ref uvs) = uvs {
+            assert_eq!(positions.len(), uvs.len());
+        }
+        PolygonMesh { positions, uvs, faces }
+    }
+
+    pub fn from_triangles(data: TriangleMeshData) -> PolygonMesh {
+        let faces = data.indices.iter().map(|&(a, b, c)| vec![a, b, c]).collect();
+        PolygonMesh { positions: data.positions, uvs: data.uvs, faces }
+    }
+
+    // Fans each polygon out from its first vertex.
+    fn triangles(&self) -> Vec<TriangleIndices> {
+        self.faces.iter()
+            .flat_map(|face| (1..(face.len() - 1)).map(move |i| (face[0], face[i], face[i + 1])))
+            .collect()
+    }
+}
+
+fn edge_key(a: usize, b: usize) -> (usize, usize) {
+    if a < b { (a, b) } else { (b, a) }
+}
+
+// Which edges are sharp, and who neighbors whom. Boundary edges, edges shared by more than two faces and
+// creases are all sharp, and sharp edges are subdivided like a cubic B-spline along the edge, ignoring
+// the faces on either side.
+struct Topology {
+    edge_faces: HashMap<(usize, usize), Vec<usize>>,
+    sharp_edges: HashSet<(usize, usize)>,
+    neighbors: Vec<Vec<usize>>,
+    sharp_neighbors: Vec<Vec<usize>>,
+    vertex_faces: Vec<Vec<usize>>,
+}
+
+enum VertexRule {
+    Smooth,
+    Crease(usize, usize),
+    // Where more than two sharp edges meet, the vertex stays put.
+    Corner,
+}
+
+impl Topology {
+    fn new(mesh: &PolygonMesh, creases: &HashSet<(usize, usize)>) -> Topology {
+        let mut edge_faces = HashMap::<(usize, usize), Vec<usize>>::new();
+        let mut vertex_faces = vec![vec![]; mesh.positions.len()];
+        for (face_index, face) in mesh.faces.iter().enumerate() {
+            for i in 0..face.len() {
+                edge_faces.entry(edge_key(face[i], face[(i + 1) % face.len()])).or_insert_with(Vec::new).push(face_index);
+                vertex_faces[face[i]].push(face_index);
+            }
+        }
+
+        let sharp_edges: HashSet<(usize, usize)> = edge_faces.iter()
+            .filter(|&(key, faces)| faces.len() != 2 || creases.contains(key))
+            .map(|(key, _)| *key)
+            .collect();
+        let mut neighbors = vec![vec![]; mesh.positions.len()];
+        let mut sharp_neighbors = vec![vec![]; mesh.positions.len()];
+        for &(a, b) in edge_faces.keys() {
+            neighbors[a].push(b);
+            neighbors[b].push(a);
+            if sharp_edges.contains(&(a, b)) {
+                sharp_neighbors[a].push(b);
+                sharp_neighbors[b].push(a);
+            }
+        }
+
+        Topology { edge_faces, sharp_edges, neighbors, sharp_neighbors, vertex_faces }
+    }
+
+    // A single sharp edge (a dart) fades out into the surface, so it gets the smooth rule too. Vertices on a
+    // boundary that only touch one face are the corners of open meshes, which stay where they are.
+    fn vertex_rule(&self, vertex: usize) -> VertexRule {
+        if self.vertex_faces[vertex].len() == 1 {
+            return VertexRule::Corner;
+        }
+        match self.sharp_neighbors[vertex].len() {
+            0 | 1 => VertexRule::Smooth,
+            2 => VertexRule::Crease(self.sharp_neighbors[vertex][0], self.sharp_neighbors[vertex][1]),
+            _ => VertexRule::Corner,
+        }
+    }
+
+    // The faces around an interior vertex in order, with each face's vertices rotated to start at it, or
+    // None if the faces don't close up around it.
+    fn ordered_ring(&self, mesh: &PolygonMesh, vertex: usize) -> Option<Vec<Vec<usize>>> {
+        let faces: Vec<Vec<usize>> = self.vertex_faces[vertex].iter()
+            .map(|&f| {
+                let face = &mesh.faces[f];
+                let start = face.iter().position(|&v| v == vertex).unwrap();
+                face[start..].iter().chain(face[..start].iter()).cloned().collect()
+            })
+            .collect();
+        if faces.is_empty() || faces.len() != self.neighbors[vertex].len() {
+            return None;
+        }
+        // Neighboring faces cross their shared edge in opposite directions, so each face's last vertex is
+        // the next one's second.
+        let by_last: HashMap<usize, usize> = faces.iter().enumerate().map(|(i, face)| (face[face.len() - 1], i)).collect();
+        let mut order = vec![0usize];
+        while order.len() < faces.len() {
+            let current = &faces[order[order.len() - 1]];
+            let next = *by_last.get(&current[1])?;
+            if next == order[0] {
+                return None;
+            }
+            order.push(next);
+        }
+        let last = &faces[order[order.len() - 1]];
+        if by_last.get(&last[1]) != Some(&order[0]) {
+            return None;
+        }
+        Some(order.into_iter().map(|i| faces[i].clone()).collect())
+    }
+}
+
+struct Refined {
+    mesh: PolygonMesh,
+    creases: HashSet<(usize, usize)>,
+}
+
+fn creases_for_children(topology: &Topology, creases: &HashSet<(usize, usize)>, edge_vertices: &HashMap<(usize, usize), usize>) -> HashSet<(usize, usize)> {
+    // Boundaries stay sharp on their own, so only explicit creases need carrying down to the children.
+    creases.iter()
+        .filter(|&key| topology.edge_faces.contains_key(key))
+        .flat_map(|&(a, b)| {
+            let middle = edge_vertices[&(a, b)];
+            vec![edge_key(a, middle), edge_key(middle, b)]
+        })
+        .collect()
+}
+
+fn loop_step(mesh: &PolygonMesh, creases: &HashSet<(usize, usize)>) -> Refined {
+    let topology = Topology::new(mesh, creases);
+    let p = &mesh.positions;
+
+    let mut positions: Vec<Point> = (0..p.len())
+        .map(|v| match topology.vertex_rule(v) {
+            VertexRule::Corner => p[v],
+            VertexRule::Crease(a, b) => p[v] * 0.75f64 + (p[a] + p[b].into_vector()) * 0.125f64,
+            VertexRule::Smooth => {
+                let n = topology.neighbors[v].len();
+                // Warren's weights.
+                let beta = if n == 3 { 3f64 / 16f64 } else { 3f64 / (8f64 * n as f64) };
+                let sum = topology.neighbors[v].iter().fold(Vec3::uniform(0f64), |sum, &i| sum + p[i].into_vector());
+                p[v] * (1f64 - n as f64 * beta) + sum * beta
+            }
+        })
+        .collect();
+    let mut uvs = mesh.uvs.clone();
+
+    let mut edge_vertices = HashMap::<(usize, usize), usize>::new();
+    let mut keys: Vec<&(usize, usize)> = topology.edge_faces.keys().collect();
+    keys.sort();
+    for &key in keys {
+        let (a, b) = key;
+        let position = if topology.sharp_edges.contains(&key) {
+            p[a] + (p[b] - p[a]) * 0.5f64
+        } else {
+            let opposite = |f: usize| *mesh.faces[f].iter().find(|&&v| v != a && v != b).unwrap();
+            let faces = &topology.edge_faces[&key];
+            let (c, d) = (opposite(faces[0]), opposite(faces[1]));
+            (p[a] + p[b].into_vector()) * 0.375f64 + (p[c] + p[d].into_vector()) * 0.125f64
+        };
+        edge_vertices.insert(key, positions.len());
+        positions.push(position);
+        if let Some(ref mut uvs) = uvs {
+            let middle = uvs[a] * 0.5f64 + uvs[b] * 0.5f64;
+            uvs.push(middle);
+        }
+    }
+
+    let faces = mesh.faces.iter()
+        .flat_map(|face| {
+            assert_eq!(face.len(), 3, "loop subdivision only works on triangles");
+            let (a, b, c) = (face[0], face[1], face[2]);
+            let (ab, bc, ca) = (edge_vertices[&edge_key(a, b)], edge_vertices[&edge_key(b, c)], edge_vertices[&edge_key(c, a)]);
+            vec![vec![a, ab, ca], vec![ab, b, bc], vec![ca, bc, c], vec![ab, bc, ca]]
+        })
+        .collect();
+
+    Refined {
+        creases: creases_for_children(&topology, creases, &edge_vertices),
+        mesh: PolygonMesh { positions, uvs, faces },
+    }
+}
+
+fn catmull_clark_step(mesh: &PolygonMesh, creases: &HashSet<(usize, usize)>) -> Refined {
+    let topology = Topology::new(mesh, creases);
+    let p = &mesh.positions;
+    let average = |points: &mut Iterator<Item = Point>| {
+        let (sum, count) = points.fold((Vec3::uniform(0f64), 0usize), |(sum, count), point| (sum + point.into_vector(), count + 1));
+        (sum / count as f64).into_point()
+    };
+
+    let face_points: Vec<Point> = mesh.faces.iter().map(|face| average(&mut face.iter().map(|&v| p[v]))).collect();
+
+    let mut positions: Vec<Point> = (0..p.len())
+        .map(|v| match topology.vertex_rule(v) {
+            VertexRule::Corner => p[v],
+            VertexRule::Crease(a, b) => p[v] * 0.75f64 + (p[a] + p[b].into_vector()) * 0.125f64,
+            VertexRule::Smooth => {
+                let n = topology.neighbors[v].len() as f64;
+                let f = average(&mut topology.vertex_faces[v].iter().map(|&face| face_points[face]));
+                let r = average(&mut topology.neighbors[v].iter().map(|&i| p[v] + (p[i] - p[v]) * 0.5f64));
+                (f + r.into_vector() * 2f64 + p[v].into_vector() * (n - 3f64)) / n
+            }
+        })
+        .collect();
+    let mut uvs = mesh.uvs.clone();
+
+    let face_start = positions.len();
+    positions.extend(face_points.iter().cloned());
+    if let Some(ref mut uvs) = uvs {
+        let face_uvs: Vec<Uv> = mesh.faces.iter()
+            .map(|face| face.iter().fold(Uv(0f64, 0f64), |sum, &v| sum + uvs[v] * (1f64 / face.len() as f64)))
+            .collect();
+        uvs.extend(face_uvs);
+    }
+
+    let mut edge_vertices = HashMap::<(usize, usize), usize>::new();
+    let mut keys: Vec<&(usize, usize)> = topology.edge_faces.keys().collect();
+    keys.sort();
+    for &key in keys {
+        let (a, b) = key;
+        let position = if topology.sharp_edges.contains(&key) {
+            p[a] + (p[b] - p[a]) * 0.5f64
+        } else {
+            let faces = &topology.edge_faces[&key];
+            (p[a] + p[b].into_vector() + face_points[faces[0]].into_vector() + face_points[faces[1]].into_vector()) * 0.25f64
+        };
+        edge_vertices.insert(key, positions.len());
+        positions.push(position);
+        if let Some(ref mut uvs) = uvs {
+            let middle = uvs[a] * 0.5f64 + uvs[b] * 0.5f64;
+            uvs.push(middle);
+        }
+    }
+
+    let faces = mesh.faces.iter().enumerate()
+        .flat_map(|(f, face)| {
+            let count = face.len();
+            let edge_vertices = &edge_vertices;
+            (0..count).map(move |i| {
+                let (previous, current, next) = (face[(i + count - 1) % count], face[i], face[(i + 1) % count]);
+                vec![current, edge_vertices[&edge_key(current, next)], face_start + f, edge_vertices[&edge_key(previous, current)]]
+            })
+        })
+        .collect();
+
+    Refined {
+        creases: creases_for_children(&topology, creases, &edge_vertices),
+        mesh: PolygonMesh { positions, uvs, faces },
+    }
+}
+
+// Normals of the limit surface at smooth interior vertices, from the tangent masks of Halstead et al. 1993
+// (Loop's, for triangles) and of the same paper for Catmull-Clark's quads. Elsewhere, the surrounding
+// faces' normals are averaged. Either way, the result faces the same way as the faces around it.
+fn limit_normals(mesh: &PolygonMesh, creases: &HashSet<(usize, usize)>) -> Vec<Normal> {
+    let topology = Topology::new(mesh, creases);
+    let p = &mesh.positions;
+    let face_normal = |face: &Vec<usize>| {
+        (1..(face.len() - 1)).fold(Vec3::uniform(0f64), |sum, i| sum + (p[face[i]] - p[face[0]]).cross(p[face[i + 1]] - p[face[0]]))
+    };
+
+    (0..p.len())
+        .map(|v| {
+            let average = topology.vertex_faces[v].iter().fold(Vec3::uniform(0f64), |sum, &f| sum + face_normal(&mesh.faces[f]));
+            let ring = match topology.vertex_rule(v) {
+                VertexRule::Smooth if topology.sharp_neighbors[v].is_empty() => topology.ordered_ring(mesh, v),
+                _ => None,
+            };
+            let limit = ring.and_then(|ring| {
+                let n = ring.len();
+                let angle = |i: usize| 2f64 * PI * i as f64 / n as f64;
+                let (mut t1, mut t2) = (Vec3::uniform(0f64), Vec3::uniform(0f64));
+                if ring.iter().all(|face| face.len() == 3) {
+                    for (i, face) in ring.iter().enumerate() {
+                        let edge = p[face[1]] - p[v];
+                        t1 = t1 + edge * angle(i).cos();
+                        t2 = t2 + edge * angle(i).sin();
+                    }
+                } else if ring.iter().all(|face| face.len() == 4) {
+                    let a = 1f64 + angle(1).cos() + (PI / n as f64).cos() * (2f64 * (9f64 + angle(1).cos())).sqrt();
+                    for (i, face) in ring.iter().enumerate() {
+                        let (edge, diagonal) = (p[face[1]] - p[v], p[face[2]] - p[v]);
+                        // Each face's diagonal sits between its previous edge and this one.
+                        t1 = t1 + edge * (a * angle(i).cos()) + diagonal * (angle(i + n - 1).cos() + angle(i).cos());
+                        t2 = t2 + edge * (a * angle(i).sin()) + diagonal * (angle(i + n - 1).sin() + angle(i).sin());
+                    }
+                } else {
+                    return None;
+                }
+                let normal = t1.cross(t2);
+                if normal.magnitude2() > 0f64 { Some(normal) } else { None }
+            });
+            let normal = match limit {
+                Some(normal) => if normal.dot(&average) < 0f64 { -normal } else { normal },
+                None => average,
+            };
+            if normal.magnitude2() > 0f64 { normal.into_normalized().into_normal() } else { Normal::new(0f64, 1f64, 0f64) }
+        })
+        .collect()
+}
+
+fn subdivide(mesh: PolygonMesh, levels: u32, creases: Vec<(usize, usize)>, step: fn(&PolygonMesh, &HashSet<(usize, usize)>) -> Refined) -> TriangleMeshData {
+    let mut refined = Refined { mesh, creases: creases.into_iter().map(|(a, b)| edge_key(a, b)).collect() };
+    for _ in 0..levels {
+        refined = step(&refined.mesh, &refined.creases);
+    }
+    let normals = limit_normals(&refined.mesh, &refined.creases);
+    let triangles = refined.mesh.triangles();
+    TriangleMeshData::new(refined.mesh.positions, Smoothing::Explicit(normals), refined.mesh.uvs, triangles)
+}
+
+// Loop subdivision of a triangle mesh. Creases are pairs of vertex indices into the original mesh; edges on
+// its boundary, including seams where vertices are duplicated for the sake of UVs, are always kept sharp.
+// UVs are interpolated linearly rather than smoothed, so textures stay where they were put.
+pub fn loop_subdivide(data: TriangleMeshData, levels: u32, creases: Vec<(usize, usize)>) -> TriangleMeshData {
+    subdivide(PolygonMesh::from_triangles(data), levels, creases, loop_step)
+}
+
+// Catmull-Clark subdivision of a polygon mesh (ideally mostly quads), with the same creases and UVs as
+// loop_subdivide. Everything is quads after the first level.
+pub fn catmull_clark_subdivide(mesh: PolygonMesh, levels: u32, creases: Vec<(usize, usize)>) -> TriangleMeshData {
+    subdivide(mesh, levels, creases, catmull_clark_step)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cube() -> PolygonMesh {
+        let positions = vec![
+            Point::new(-1f64, -1f64, -1f64), Point::new(1f64, -1f64, -1f64),
+            Point::new(1f64, 1f64, -1f64), Point::new(-1f64, 1f64, -1f64),
+            Point::new(-1f64, -1f64, 1f64), Point::new(1f64, -1f64, 1f64),
+            Point::new(1f64, 1f64, 1f64), Point::new(-1f64, 1f64, 1f64),
+        ];
+        let faces = vec![
+            vec![0, 3, 2, 1], vec![4, 5, 6, 7],
+            vec![0, 1, 5, 4], vec![2, 3, 7, 6],
+            vec![0, 4, 7, 3], vec![1, 2, 6, 5],
+        ];
+        PolygonMesh::new(positions, None, faces)
+    }
+
+    fn octahedron() -> TriangleMeshData {
+        let positions = vec![
+            Point::new(1f64, 0f64, 0f64), Point::new(-1f64, 0f64, 0f64),
+            Point::new(0f64, 1f64, 0f64), Point::new(0f64, -1f64, 0f64),
+            Point::new(0f64, 0f64, 1f64), Point::new(0f64, 0f64, -1f64),
+        ];
+        let indices = vec![
+            (2, 4, 0), (2, 0, 5), (2, 5, 1), (2, 1, 4),
+            (3, 0, 4), (3, 5, 0), (3, 1, 5), (3, 4, 1),
+        ];
+        TriangleMeshData::new(positions, Smoothing::None, None, indices)
+    }
+
+    fn assert_normals_face_outward(mesh: &TriangleMeshData) {
+        for (position, normal) in mesh.positions.iter().zip(mesh.normals.as_ref().unwrap()) {
+            assert!(normal.dot(position) > 0f64);
+        }
+    }
+
+    #[test]
+    fn it_should_quadruple_triangles_with_loop() {
+        let mesh = loop_subdivide(octahedron(), 2, vec![]);
+        assert_eq!(mesh.indices.len(), 8 * 16);
+        assert_normals_face_outward(&mesh);
+        // The octahedron's symmetry puts the limit normal at its tips right along the axes.
+        assert!(mesh.normals.as_ref().unwrap()[2].y > 1f64 - 1e-9);
+    }
+
+    #[test]
+    fn it_should_round_off_a_cube_with_catmull_clark() {
+        let mesh = catmull_clark_subdivide(cube(), 2, vec![]);
+        assert_eq!(mesh.indices.len(), 6 * 16 * 2);
+        assert_normals_face_outward(&mesh);
+        // Corners get pulled in.
+        assert!(mesh.positions[0].x > -1f64 && mesh.positions[0].x < -0.5f64);
+        // Catmull-Clark's first step moves a cube's corner to 5/9 of the way out.
+        let once = catmull_clark_subdivide(cube(), 1, vec![]);
+        assert!((once.positions[6].x - 5f64 / 9f64).abs() < 1e-12);
+    }
+
+    #[test]
+    fn it_should_keep_creased_corners_in_place() {
+        let creases = vec![(0, 1), (1, 2), (2, 3), (3, 0), (0, 4), (1, 5), (2, 6), (3, 7), (4, 5), (5, 6), (6, 7), (7, 4)];
+        let mesh = catmull_clark_subdivide(cube(), 2, creases);
+        assert_eq!(mesh.positions[0].x, -1f64);
+        // Every edge is sharp, so the faces stay flat.
+        assert!(mesh.positions.iter().all(|p| p.x.abs().max(p.y.abs()).max(p.z.abs()) == 1f64));
+    }
+
+    #[test]
+    fn it_should_follow_the_boundary_of_an_open_mesh() {
+        // A square of two triangles, which stays flat. The corners in only one triangle stay put.
+        let positions = vec![
+            Point::new(0f64, 0f64, 0f64), Point::new(1f64, 0f64, 0f64),
+            Point::new(1f64, 0f64, -1f64), Point::new(0f64, 0f64, -1f64),
+        ];
+        let uvs = vec![Uv(0f64, 0f64), Uv(1f64, 0f64), Uv(1f64, 1f64), Uv(0f64, 1f64)];
+        let data = TriangleMeshData::new(positions, Smoothing::None, Some(uvs), vec![(0, 1, 2), (0, 2, 3)]);
+        let mesh = loop_subdivide(data, 1, vec![]);
+        assert_eq!(mesh.positions[1].x, 1f64);
+        assert!(mesh.positions.iter().all(|p| p.y == 0f64));
+        assert!(mesh.normals.as_ref().unwrap().iter().all(|n| n.y > 1f64 - 1e-9));
+        // UVs aren't smoothed; the new vertex on the edge from 0 to 1 gets the middle of theirs.
+        let uvs = mesh.uvs.as_ref().unwrap();
+        assert_eq!((uvs[0].0, uvs[0].1), (0f64, 0f64));
+        assert_eq!((uvs[4].0, uvs[4].1), (0.5f64, 0f64));
+    }
+}
@@ -0,0 +1,290 @@
+use std::f64::consts::PI;
+use math::*;
+use core::*;
+use geometry::*;
+
+// A closed 2D cross-section to sweep along a path. Profiles live in a plane perpendicular to the path,
+// with x along the path's normal and y along its binormal. Curves use their points' x and y, ignoring z.
+pub enum SweepProfile {
+    Circle { radius: f64, segments: usize },
+    Polygon(Vec<(f64, f64)>),
+    // The curve should end where it starts; the end point is dropped in favor of the start.
+    Curve { curve: Box<Curve>, segments: usize },
+}
+
+impl SweepProfile {
+    // The profile's points, counterclockwise, without repeating the first at the end.
+    fn points(&self) -> Vec<(f64, f64)> {
+        let points: Vec<(f64, f64)> = match *self {
+            SweepProfile::Circle { radius, segments } => (0..segments)
+                .map(|i| (i as f64 / segments as f64 * 2f64 * PI).sin_cos())
+                .map(|(sin, cos)| (radius * cos, radius * sin))
+                .collect(),
+            SweepProfile::Polygon(ref points) => points.clone(),
+            SweepProfile::Curve { ref curve, segments } => (0..segments)
+                .map(|i| curve.at(i as f64 / segments as f64))
+                .map(|p| (p.x, p.y))
+                .collect(),
+        };
+        assert!(points.len() >= 3, "sweep profiles need at least 3 points");
+
+        // Shoelace formula; clockwise profiles would turn the mesh inside out.
+        let signed_area: f64 = (0..points.len())
+            .map(|i| {
+                let (a, b) = (points[i], points[(i + 1) % points.len()]);
+                a.0 * b.1 - b.0 * a.1
+            })
+            .sum();
+        if signed_area < 0f64 {
+            points.into_iter().rev().collect()
+        } else {
+            points
+        }
+    }
+
+    // Polygons keep their hard edges; circles and curves are smoothed.
+    fn is_smooth(&self) -> bool {
+        match *self {
+            SweepProfile::Polygon(_) => false,
+            _ => true,
+        }
+    }
+}
+
+#[derive(PartialEq)]
+pub enum SweepEnds {
+    Open,
+    // Closes both ends with flat caps, fanned out from the profile's centroid, which works for any profile
+    // that's star-shaped around it.
+    Cap,
+    // For paths that end where they start: joins the ends up, and spreads out the twist needed to make the
+    // frames meet along the whole length.
+    Join,
+}
+
+// Rotation-minimizing frames along the path by the double reflection method (Wang et al. 2008), which keeps
+// the profile from twisting any more than the path makes it. Returns each sample's (center, normal,
+// binormal, tangent).
+fn rotation_minimizing_frames(path: &Curve, path_segments: usize, is_loop: bool) -> Vec<(Point, Vec3, Vec3, Vec3)> {
+    let h = 0.25f64 / path_segments as f64;
+    let at = |t: f64| if is_loop {
+        path.at(t - t.floor())
+    } else {
+        path.at(t.max(0f64).min(1f64))
+    };
+
+    let centers: Vec<Point> = (0..(path_segments + 1)).map(|i| at(i as f64 / path_segments as f64)).collect();
+    let mut tangents: Vec<Vec3> = Vec::with_capacity(path_segments + 1);
+    for i in 0..(path_segments + 1) {
+        let t = i as f64 / path_segments as f64;
+        let difference = at(t + h) - at(t - h);
+        // Control points doubled up at an end leave no direction there, so borrow the previous one.
+        tangents.push(if difference.magnitude2() > 0f64 {
+            difference.into_normalized()
+        } else if i > 0 {
+            tangents[i - 1]
+        } else {
+            (centers[1] - centers[0]).into_normalized()
+        });
+    }
+
+    let up = if tangents[0].cross(Vec3::Y_AXIS).magnitude2() > 1e-6 { Vec3::Y_AXIS } else { Vec3::X_AXIS };
+    let mut normals = vec![up.cross(tangents[0]).into_normalized()];
+    for i in 0..path_segments {
+        let reflect = |v: Vec3, axis: Vec3| {
+            let length2 = axis.magnitude2();
+            if length2 > 0f64 { v - axis * (2f64 * axis.dot(&v) / length2) } else { v }
+        };
+        let step = centers[i + 1] - centers[i];
+        let reflected_normal = reflect(normals[i], step);
+        let reflected_tangent = reflect(tangents[i], step);
+        let normal = reflect(reflected_normal, tangents[i + 1] - reflected_tangent);
+        normals.push(normal.into_normalized());
+    }
+
+    // Transport around a loop generally comes back rotated, so unwind that bit by bit along the way.
+    let correction = if is_loop {
+        let (last, first) = (normals[path_segments], normals[0]);
+        last.cross(first).dot(&tangents[0]).atan2(last.dot(&first))
+    } else {
+        0f64
+    };
+
+    (0..(path_segments + 1))
+        .map(|i| {
+            let (tangent, normal) = (tangents[i], normals[i]);
+            let binormal = tangent.cross(normal);
+            let (sin, cos) = (correction * i as f64 / path_segments as f64).sin_cos();
+            (centers[i], normal * cos + binormal * sin, binormal * cos - normal * sin, tangent)
+        })
+        .collect()
+}
+
+// Extrudes a profile along a path into a tube. u goes around the profile by arc length and v along the path.
+// Like the lathe, the seams are duplicated so that UVs don't wrap.
+pub fn create_sweep(profile: SweepProfile, path: Box<Curve>, path_segments: usize, ends: SweepEnds) -> TriangleMeshData {
+    assert!(path_segments >= 1, "a sweep needs at least 1 segment along its path");
+    let points = profile.points();
+    let is_smooth = profile.is_smooth();
+    let frames = rotation_minimizing_frames(path.as_ref(), path_segments, ends == SweepEnds::Join);
+
+    let count = points.len();
+    let columns = count + 1;
+    let perimeter: Vec<f64> = (0..columns)
+        .scan(0f64, |length, j| {
+            let current = *length;
+            let (a, b) = (points[j % count], points[(j + 1) % count]);
+            *length += ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt();
+            Some(current)
+        })
+        .collect();
+    // Vertex normals in the profile's plane: the direction between the neighbors, turned clockwise.
+    let profile_normals: Vec<(f64, f64)> = (0..count)
+        .map(|j| {
+            let (previous, next) = (points[(j + count - 1) % count], points[(j + 1) % count]);
+            (next.1 - previous.1, previous.0 - next.0)
+        })
+        .collect();
+
+    let mut positions = Vec::<Point>::new();
+    let mut normals = Vec::<Normal>::new();
+    let mut uvs = Vec::<Uv>::new();
+    for (i, &(center, normal, binormal, _)) in frames.iter().enumerate() {
+        for j in 0..columns {
+            let (x, y) = points[j % count];
+            let (nx, ny) = profile_normals[j % count];
+            positions.push(center + normal * x + binormal * y);
+            normals.push((normal * nx + binormal * ny).into_normalized().into_normal());
+            uvs.push(Uv(perimeter[j] / perimeter[count], i as f64 / path_segments as f64));
+        }
+    }
+
+    let mut indices = Vec::<TriangleIndices>::with_capacity(2 * count * path_segments + 2 * count);
+    for i in 0..path_segments {
+        for j in 0..count {
+            let a = i * columns + j;
+            let b = a + 1;
+            let c = a + columns;
+            let d = c + 1;
+            indices.push((a, b, c));
+            indices.push((b, d, c));
+        }
+    }
+
+    if ends == SweepEnds::Cap {
+        let centroid = points.iter().fold((0f64, 0f64), |sum, p| (sum.0 + p.0 / count as f64, sum.1 + p.1 / count as f64));
+        let extent = points.iter().fold(0f64, |extent, p| extent.max((p.0 - centroid.0).abs()).max((p.1 - centroid.1).abs()));
+        let cap_uv = |p: (f64, f64)| Uv(0.5f64 + (p.0 - centroid.0) / (2f64 * extent), 0.5f64 + (p.1 - centroid.1) / (2f64 * extent));
+
+        for &(frame, facing) in &[(&frames[0], -1f64), (&frames[path_segments], 1f64)] {
+            let &(center, normal, binormal, tangent) = frame;
+            let start = positions.len();
+            for &p in [centroid].iter().chain(points.iter()) {
+                positions.push(center + normal * p.0 + binormal * p.1);
+                normals.push((tangent * facing).into_normal());
+                uvs.push(cap_uv(p));
+            }
+            for j in 0..count {
+                let (a, b) = (start + 1 + j, start + 1 + (j + 1) % count);
+                // The profile is counterclockwise looking back down the tangent, so the end cap keeps that
+                // winding and the start cap reverses it.
+                indices.push(if facing > 0f64 { (start, a, b) } else { (start, b, a) });
+            }
+        }
+    }
+
+    let smoothing = if is_smooth { Smoothing::Explicit(normals) } else { Smoothing::None };
+    TriangleMeshData::new(positions, smoothing, Some(uvs), indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn straight_path() -> Box<Curve> {
+        Box::new(CubicBezier {
+            p0: Point::new(0f64, 0f64, 0f64),
+            p1: Point::new(0f64, 0f64, 1f64),
+            p2: Point::new(0f64, 0f64, 2f64),
+            p3: Point::new(0f64, 0f64, 3f64),
+        })
+    }
+
+    // A quarter of a circle in the xz-plane, approximated by a bezier.
+    fn bent_path() -> Box<Curve> {
+        let k = 0.5523f64;
+        Box::new(CubicBezier {
+            p0: Point::new(1f64, 0f64, 0f64),
+            p1: Point::new(1f64, 0f64, k),
+            p2: Point::new(k, 0f64, 1f64),
+            p3: Point::new(0f64, 0f64, 1f64),
+        })
+    }
+
+    fn square() -> SweepProfile {
+        SweepProfile::Polygon(vec![(-0.5f64, -0.5f64), (0.5f64, -0.5f64), (0.5f64, 0.5f64), (-0.5f64, 0.5f64)])
+    }
+
+    #[test]
+    fn it_should_keep_rings_at_a_fixed_distance_from_the_path() {
+        let mesh = create_sweep(SweepProfile::Circle { radius: 0.25f64, segments: 12 }, bent_path(), 16, SweepEnds::Open);
+        let path = bent_path();
+        for i in 0..17 {
+            let center = path.at(i as f64 / 16f64);
+            for j in 0..13 {
+                let distance = (mesh.positions[i * 13 + j] - center).magnitude();
+                assert!((distance - 0.25f64).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn it_should_not_twist_along_a_planar_path() {
+        // The path lies flat, so the frames should only ever turn about y.
+        let frames = rotation_minimizing_frames(bent_path().as_ref(), 32, false);
+        let first_binormal = frames[0].2;
+        for &(_, _, binormal, _) in &frames {
+            assert!(binormal.dot(&first_binormal).abs() > 1f64 - 1e-9);
+        }
+    }
+
+    #[test]
+    fn it_should_face_outward() {
+        let mesh = create_sweep(SweepProfile::Circle { radius: 1f64, segments: 8 }, straight_path(), 4, SweepEnds::Open);
+        let (a, b, c) = mesh.indices[0];
+        let p = &mesh.positions;
+        let face = (p[b] - p[a]).cross(p[c] - p[a]);
+        let outward = (p[a] + (p[b] - p[a]) / 2f64) - Point::new(0f64, 0f64, p[a].z);
+        assert!(face.dot(&outward) > 0f64);
+    }
+
+    #[test]
+    fn it_should_cap_the_ends() {
+        let mesh = create_sweep(square(), straight_path(), 3, SweepEnds::Cap);
+        assert_eq!(mesh.indices.len(), 2 * 4 * 3 + 2 * 4);
+        // The last triangle belongs to the end cap, at z = 3, which should face along +z.
+        let (a, b, c) = mesh.indices[mesh.indices.len() - 1];
+        let p = &mesh.positions;
+        assert!((p[a].z - 3f64).abs() < 1e-9);
+        assert!((p[b] - p[a]).cross(p[c] - p[a]).z > 0f64);
+    }
+
+    #[test]
+    fn it_should_meet_itself_when_joined() {
+        let circle = CurvePath {
+            curves: (0..4).map(|quarter| {
+                let (sin, cos) = (quarter as f64 * PI / 2f64).sin_cos();
+                let rotate = |x: f64, z: f64| Point::new(x * cos - z * sin, 0f64, x * sin + z * cos);
+                let k = 0.5523f64;
+                Box::new(CubicBezier { p0: rotate(1f64, 0f64), p1: rotate(1f64, k), p2: rotate(k, 1f64), p3: rotate(0f64, 1f64) }) as Box<Curve>
+            }).collect(),
+        };
+        // Tilt the square so that it would visibly fail to meet if the frames didn't line up.
+        let profile = SweepProfile::Polygon(vec![(0f64, -0.2f64), (0.1f64, 0f64), (0f64, 0.2f64), (-0.1f64, 0f64)]);
+        let mesh = create_sweep(profile, Box::new(circle), 32, SweepEnds::Join);
+        let columns = 5;
+        for j in 0..columns {
+            assert!((mesh.positions[32 * columns + j] - mesh.positions[j]).magnitude() < 1e-6);
+        }
+    }
+}
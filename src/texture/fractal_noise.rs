@@ -0,0 +1,30 @@
+use noise::{ Fbm, MultiFractal, NoiseModule, Seedable };
+use core::*;
+
+// Fractal Perlin noise over UV space, blending from color_one where it's lowest to color_two where it's
+// highest. Mostly useful as a procedural displacement or roughness map.
+#[derive(Debug)]
+pub struct NoiseTexture {
+    fbm: Fbm<f64>,
+    color_one: Color,
+    color_two: Color,
+}
+
+impl NoiseTexture {
+    pub fn new(seed: usize, frequency: f64, octaves: usize, color_one: Color, color_two: Color) -> NoiseTexture {
+        NoiseTexture {
+            fbm: Fbm::new().set_seed(seed).set_frequency(frequency).set_octaves(octaves),
+            color_one,
+            color_two,
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn get_color(&self, uv: Uv) -> Color {
+        let Uv(u, v) = uv;
+        // fBm is roughly within [-1, 1].
+        let t = ((self.fbm.get([u, v]) + 1f64) / 2f64).max(0f64).min(1f64);
+        self.color_one * (1f64 - t) + self.color_two * t
+    }
+}
@@ -1,11 +1,13 @@
 mod checkerboard;
 mod color;
 mod debug;
+mod fractal_noise;
 mod image;
 // mod wood;
 
 pub use self::checkerboard::*;
 pub use self::color::*;
 pub use self::debug::*;
+pub use self::fractal_noise::*;
 pub use self::image::*;
 // pub use self::wood::*;